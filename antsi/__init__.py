@@ -5,6 +5,26 @@ Built around a delightfully simple markup language, there's no messing about wit
 code conversion, handling of overlapping styles, and terminal support is automatically handled for you.
 """
 
-from ._antsi import ColorizeError, colorize, escape
+from ._antsi import (
+    ColorizeError,
+    annotate,
+    colorize,
+    colorize_all,
+    escape,
+    strip,
+    to_html,
+    truncate,
+    width,
+)
 
-__all__ = ["ColorizeError", "colorize", "escape"]
+__all__ = [
+    "ColorizeError",
+    "annotate",
+    "colorize",
+    "colorize_all",
+    "escape",
+    "strip",
+    "to_html",
+    "truncate",
+    "width",
+]
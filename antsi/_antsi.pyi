@@ -1,9 +1,44 @@
-class ColorizeError(Exception): ...
+from typing import Literal, TypedDict
 
+class _ErrorDetails(TypedDict):
+    message: str
+    code: str
+    file: str | None
+    span: tuple[int, int]
+    line: int
+    column: int
+    at: str
+
+class ColorizeError(Exception):
+    errors: list[_ErrorDetails]
+    json: str
+    index: int | None
+
+def annotate(source: str, file: str | None = "inline") -> str: ...
 def colorize(
-    source: str,
-    file: str = "inline",
+    source: str | bytes | bytearray,
+    file: str | None = "inline",
     wrap: int | None = None,
     supports_color: bool = True,
+    respect_no_color: bool = False,
+    strip_ansi: bool = False,
+    reset_mode: Literal["targeted", "full"] = "targeted",
+    collapse_redundant: bool = True,
+    minimize: bool = False,
+    suppress_blink: bool = False,
+    suppress_colors: bool = False,
+    suppress_decorations: bool = False,
+    reopen_on_newline: bool = False,
+    max_depth: int = 128,
 ) -> str: ...
+def colorize_all(sources: list[str], file: str | None = "inline") -> list[str]: ...
 def escape(source: str) -> str: ...
+def strip(source: str, file: str | None = "inline") -> str: ...
+def to_html(source: str, file: str | None = "inline") -> str: ...
+def truncate(
+    source: str,
+    width: int,
+    ellipsis: str | None = None,
+    file: str | None = "inline",
+) -> str: ...
+def width(source: str, file: str | None = "inline") -> int: ...
@@ -0,0 +1,63 @@
+#![no_main]
+
+use antsi::{colorize, strip, Options, ResetMode};
+use libfuzzer_sys::fuzz_target;
+use std::collections::HashMap;
+
+fuzz_target!(|source: &str| {
+    for reset_mode in [ResetMode::Targeted, ResetMode::Full] {
+        let options = Options {
+            reset_mode,
+            ..Options::default()
+        };
+
+        let Ok(output) = colorize(source, options) else {
+            continue;
+        };
+
+        // escape characters in the source, whether written directly or escaped, are passed through
+        // as-is, so only markup that doesn't contain any can be expected to balance
+        let escaped = strip(source).is_ok_and(|text| text.contains('\x1b'));
+        if source.contains('\x1b') || escaped {
+            continue;
+        }
+
+        assert_balanced(source, &output);
+    }
+});
+
+/// Check that every property set by an SGR sequence in the output is undone by the end
+fn assert_balanced(source: &str, output: &str) {
+    // each active property, keyed by the code that undoes it
+    let mut active = HashMap::<u8, u8>::new();
+
+    for sequence in output.split('\x1b').skip(1) {
+        let (codes, _) = sequence
+            .strip_prefix('[')
+            .and_then(|s| s.split_once('m'))
+            .unwrap_or_else(|| panic!("{source:?} produced malformed {output:?}"));
+
+        let mut codes = codes.split(';').map(|code| code.parse::<u8>().unwrap());
+        while let Some(code) = codes.next() {
+            match code {
+                0 => active.clear(),
+                30..=37 | 90..=97 => drop(active.insert(39, code)),
+                40..=47 | 100..=107 => drop(active.insert(49, code)),
+                // underline colors are followed by the palette and the index of the color
+                58 => drop(active.insert(59, codes.nth(1).unwrap())),
+                39 | 49 | 59 => drop(active.remove(&code)),
+                1 | 2 => drop(active.insert(code + 100, 22)),
+                3..=9 => drop(active.insert(code + 100, if code == 6 { 25 } else { code + 20 })),
+                22..=29 => {
+                    active.retain(|&key, &mut reset| matches!(key, 39 | 49 | 59) || reset != code)
+                }
+                _ => panic!("{source:?} produced unexpected code {code} in {output:?}"),
+            }
+        }
+    }
+
+    assert!(
+        active.is_empty(),
+        "{source:?} left {active:?} active in {output:?}"
+    );
+}
@@ -0,0 +1,107 @@
+use crate::{
+    ast::{Color, CurrentStyle},
+    error::Error,
+    spans::spans,
+};
+
+/// Convert styled markup to a human-readable description of how it is displayed, without using
+/// any escape codes
+///
+/// Each run of styled text is wrapped in markers listing the style it is displayed with, i.e.
+/// `⟨fg=red,bold⟩text⟨/⟩`. The styles are resolved the same way as [`spans`], so nested markup
+/// shows everything it inherits from the enclosing markup. Colors are listed as `fg`, `bg`, and
+/// `ul`, followed by the names of the decorations. Unstyled text is left as-is, and any ANSI escape
+/// sequences present in the source are dropped.
+pub fn annotate(source: &str) -> Result<String, Vec<Error>> {
+    let mut result = String::with_capacity(source.len());
+    for (text, style) in spans(source)? {
+        let properties = properties(&style);
+        if properties.is_empty() {
+            result.push_str(&text);
+            continue;
+        }
+
+        result.push('⟨');
+        result.push_str(&properties.join(","));
+        result.push('⟩');
+        result.push_str(&text);
+        result.push_str("⟨/⟩");
+    }
+
+    Ok(result)
+}
+
+/// Describe each property of the style that differs from the terminal's default
+fn properties(style: &CurrentStyle) -> Vec<String> {
+    let colors = [
+        ("fg", style.foreground()),
+        ("bg", style.background()),
+        ("ul", style.underline_color()),
+    ];
+
+    colors
+        .into_iter()
+        .filter(|(_, color)| *color != Color::Default)
+        .map(|(name, color)| format!("{name}={color}"))
+        .chain(style.decoration().iter().map(|d| d.name().to_owned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::annotate;
+
+    #[test]
+    fn unstyled() {
+        assert_eq!(annotate("plain text").unwrap(), "plain text");
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(annotate("").unwrap(), "");
+        assert_eq!(annotate("[fg:red]()").unwrap(), "");
+    }
+
+    #[test]
+    fn styled() {
+        assert_eq!(
+            annotate("before [fg:red;deco:bold](text) after").unwrap(),
+            "before ⟨fg=red,bold⟩text⟨/⟩ after"
+        );
+    }
+
+    #[test]
+    fn every_property() {
+        assert_eq!(
+            annotate("[fg:bright-blue;bg:white;ul:red;deco:underline,italic](text)").unwrap(),
+            "⟨fg=bright-blue,bg=white,ul=red,underline,italic⟩text⟨/⟩"
+        );
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(
+            annotate("[fg:red](a [bg:blue;deco:bold](b [fg:default](c)) [reset;deco:italic](d)) e")
+                .unwrap(),
+            "⟨fg=red⟩a ⟨/⟩⟨fg=red,bg=blue,bold⟩b ⟨/⟩⟨bg=blue,bold⟩c⟨/⟩⟨fg=red⟩ ⟨/⟩⟨italic⟩d⟨/⟩ e"
+        );
+    }
+
+    #[test]
+    fn adjacent_runs_with_the_same_style_are_merged() {
+        assert_eq!(
+            annotate("[fg:red](a)[fg:red](b) [fg:red]([deco:bold](c))").unwrap(),
+            "⟨fg=red⟩ab⟨/⟩ ⟨fg=red,bold⟩c⟨/⟩"
+        );
+    }
+
+    #[test]
+    fn existing_escape_sequences_are_removed() {
+        assert_eq!(annotate("\x1b[1mbold\x1b[22m").unwrap(), "bold");
+    }
+
+    #[test]
+    fn invalid_markup() {
+        assert!(annotate("[fg:red](unclosed").is_err());
+    }
+}
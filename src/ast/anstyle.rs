@@ -0,0 +1,248 @@
+use super::{Color, Decoration, Style};
+use ::anstyle::{AnsiColor, Effects};
+use std::fmt::{Display, Formatter};
+
+/// The [`anstyle::Style`](::anstyle::Style) has properties that can't be represented by a [`Style`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct UnsupportedStyleError;
+
+impl std::error::Error for UnsupportedStyleError {}
+
+impl Display for UnsupportedStyleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("style cannot be represented using styled markup")
+    }
+}
+
+/// Every color paired with its equivalent from the 16-color palette
+const COLORS: [(Color, AnsiColor); 16] = [
+    (Color::Black, AnsiColor::Black),
+    (Color::Red, AnsiColor::Red),
+    (Color::Green, AnsiColor::Green),
+    (Color::Yellow, AnsiColor::Yellow),
+    (Color::Blue, AnsiColor::Blue),
+    (Color::Magenta, AnsiColor::Magenta),
+    (Color::Cyan, AnsiColor::Cyan),
+    (Color::White, AnsiColor::White),
+    (Color::BrightBlack, AnsiColor::BrightBlack),
+    (Color::BrightRed, AnsiColor::BrightRed),
+    (Color::BrightGreen, AnsiColor::BrightGreen),
+    (Color::BrightYellow, AnsiColor::BrightYellow),
+    (Color::BrightBlue, AnsiColor::BrightBlue),
+    (Color::BrightMagenta, AnsiColor::BrightMagenta),
+    (Color::BrightCyan, AnsiColor::BrightCyan),
+    (Color::BrightWhite, AnsiColor::BrightWhite),
+];
+
+/// Every decoration paired with its equivalent effect
+///
+/// Both blinking speeds become [`Effects::BLINK`], which is converted back to a slow blink.
+const DECORATIONS: [(Decoration, Effects); 9] = [
+    (Decoration::Bold, Effects::BOLD),
+    (Decoration::Dim, Effects::DIMMED),
+    (Decoration::Italic, Effects::ITALIC),
+    (Decoration::Underline, Effects::UNDERLINE),
+    (Decoration::SlowBlink, Effects::BLINK),
+    (Decoration::FastBlink, Effects::BLINK),
+    (Decoration::Invert, Effects::INVERT),
+    (Decoration::Hide, Effects::HIDDEN),
+    (Decoration::StrikeThrough, Effects::STRIKETHROUGH),
+];
+
+/// Convert to the equivalent color, if there is one
+///
+/// The default color has no equivalent, as `anstyle` leaves the color unchanged instead.
+impl From<Color> for Option<::anstyle::Color> {
+    fn from(color: Color) -> Self {
+        COLORS
+            .iter()
+            .find(|(c, _)| *c == color)
+            .map(|(_, ansi)| ::anstyle::Color::Ansi(*ansi))
+    }
+}
+
+/// Convert a color from the 16-color palette
+///
+/// Colors from the 256-color palette are accepted if they are one of the first 16 colors.
+impl TryFrom<::anstyle::Color> for Color {
+    type Error = UnsupportedStyleError;
+
+    fn try_from(color: ::anstyle::Color) -> Result<Self, Self::Error> {
+        let ansi = match color {
+            ::anstyle::Color::Ansi(ansi) => Some(ansi),
+            ::anstyle::Color::Ansi256(color) => color.into_ansi(),
+            ::anstyle::Color::Rgb(_) => None,
+        };
+
+        COLORS
+            .iter()
+            .find(|(_, a)| Some(*a) == ansi)
+            .map(|(color, _)| *color)
+            .ok_or(UnsupportedStyleError)
+    }
+}
+
+/// Convert the style to an [`anstyle::Style`](::anstyle::Style)
+///
+/// The conversion is lossy: `anstyle` has no way to represent the `default` color (it leaves the
+/// color unset instead), the blinking speed, or clearing the inherited styles with `reset`.
+impl From<&Style> for ::anstyle::Style {
+    fn from(style: &Style) -> Self {
+        let mut effects = Effects::new();
+        for decoration in style.decoration.iter().flatten() {
+            if let Some((_, effect)) = DECORATIONS.iter().find(|(d, _)| d == decoration) {
+                effects = effects.insert(*effect);
+            }
+        }
+
+        ::anstyle::Style::new()
+            .fg_color(style.foreground.and_then(Into::into))
+            .bg_color(style.background.and_then(Into::into))
+            .underline_color(style.underline_color.and_then(Into::into))
+            .effects(effects)
+    }
+}
+
+impl From<Style> for ::anstyle::Style {
+    fn from(style: Style) -> Self {
+        Self::from(&style)
+    }
+}
+
+/// Convert an [`anstyle::Style`](::anstyle::Style) to a style
+///
+/// Fails if the style uses colors outside the 16-color palette, or an effect without an equivalent
+/// decoration (i.e. double or curly underlines).
+impl TryFrom<::anstyle::Style> for Style {
+    type Error = UnsupportedStyleError;
+
+    fn try_from(style: ::anstyle::Style) -> Result<Self, Self::Error> {
+        let mut result = Style::new();
+        if let Some(color) = style.get_fg_color() {
+            result = result.foreground(color.try_into()?);
+        }
+        if let Some(color) = style.get_bg_color() {
+            result = result.background(color.try_into()?);
+        }
+        if let Some(color) = style.get_underline_color() {
+            result = result.underline_color(color.try_into()?);
+        }
+
+        let mut remaining = style.get_effects();
+        for (decoration, effect) in DECORATIONS {
+            if remaining.contains(effect) {
+                result = result.decoration(decoration);
+                remaining = remaining.remove(effect);
+            }
+        }
+
+        if remaining.is_plain() {
+            Ok(result)
+        } else {
+            Err(UnsupportedStyleError)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnsupportedStyleError;
+    use crate::ast::{Color, Decoration, Style};
+    use ::anstyle::{Ansi256Color, AnsiColor, Effects, RgbColor};
+
+    #[test]
+    fn colors() {
+        let style = ::anstyle::Style::from(Style::fg(Color::Red).background(Color::BrightBlue));
+        assert_eq!(style.get_fg_color(), Some(AnsiColor::Red.into()));
+        assert_eq!(style.get_bg_color(), Some(AnsiColor::BrightBlue.into()));
+    }
+
+    #[test]
+    fn default_color_is_unset() {
+        let style = ::anstyle::Style::from(Style::fg(Color::Default));
+        assert_eq!(style, ::anstyle::Style::new());
+    }
+
+    #[test]
+    fn decorations() {
+        let style = ::anstyle::Style::from(
+            Style::deco(Decoration::Bold)
+                .decoration(Decoration::Dim)
+                .decoration(Decoration::Italic)
+                .decoration(Decoration::Underline)
+                .decoration(Decoration::FastBlink)
+                .decoration(Decoration::Invert)
+                .decoration(Decoration::Hide)
+                .decoration(Decoration::StrikeThrough),
+        );
+        assert_eq!(
+            style.get_effects(),
+            Effects::BOLD
+                | Effects::DIMMED
+                | Effects::ITALIC
+                | Effects::UNDERLINE
+                | Effects::BLINK
+                | Effects::INVERT
+                | Effects::HIDDEN
+                | Effects::STRIKETHROUGH
+        );
+    }
+
+    #[test]
+    fn reset_is_dropped() {
+        let style = Style {
+            reset: true,
+            ..Style::fg(Color::Green)
+        };
+        assert_eq!(::anstyle::Style::from(style), AnsiColor::Green.on_default());
+    }
+
+    #[test]
+    fn round_trips() {
+        let style = Style::fg(Color::Yellow)
+            .background(Color::Black)
+            .decoration(Decoration::Bold)
+            .decoration(Decoration::SlowBlink);
+        let converted = ::anstyle::Style::from(&style);
+        assert_eq!(Style::try_from(converted), Ok(style));
+    }
+
+    #[test]
+    fn underline_color_round_trips() {
+        let style = Style::deco(Decoration::Underline).underline_color(Color::BrightMagenta);
+        let converted = ::anstyle::Style::from(&style);
+        assert_eq!(
+            converted.get_underline_color(),
+            Some(AnsiColor::BrightMagenta.into())
+        );
+        assert_eq!(Style::try_from(converted), Ok(style));
+    }
+
+    #[test]
+    fn from_plain_style() {
+        assert_eq!(Style::try_from(::anstyle::Style::new()), Ok(Style::new()));
+    }
+
+    #[test]
+    fn from_low_256_color() {
+        let style = ::anstyle::Style::new().fg_color(Some(Ansi256Color(9).into()));
+        assert_eq!(Style::try_from(style), Ok(Style::fg(Color::BrightRed)));
+    }
+
+    #[test]
+    fn from_unsupported_colors() {
+        for color in [Ansi256Color(208).into(), RgbColor(1, 2, 3).into()] {
+            let style = ::anstyle::Style::new().bg_color(Some(color));
+            assert_eq!(Style::try_from(style), Err(UnsupportedStyleError));
+        }
+
+        let style = ::anstyle::Style::new().underline_color(Some(RgbColor(1, 2, 3).into()));
+        assert_eq!(Style::try_from(style), Err(UnsupportedStyleError));
+    }
+
+    #[test]
+    fn from_unsupported_effects() {
+        let style = ::anstyle::Style::new().effects(Effects::BOLD | Effects::CURLY_UNDERLINE);
+        assert_eq!(Style::try_from(style), Err(UnsupportedStyleError));
+    }
+}
@@ -4,7 +4,7 @@ use std::{
 };
 
 /// The provided [`Color`] name was invalid
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct InvalidColorError;
 
 impl std::error::Error for InvalidColorError {}
@@ -17,15 +17,44 @@ impl Display for InvalidColorError {
 
 macro_rules! colors {
     (
-        $( $( #[ $meta:meta ] )* $color:ident $fg:literal $bg:literal ( $names:pat ) ),* $(,)?
+        $(
+            $( #[ $meta:meta ] )*
+            $color:ident $fg:literal $bg:literal $ul:literal ( $name:literal $( | $alias:literal )* )
+        ),* $(,)?
     ) => {
         /// Available standard ANSI colors
-        #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+        #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(rename_all = "kebab-case")
+        )]
         pub enum Color {
             $( $( #[$meta] )* $color, )*
         }
 
         impl Color {
+            /// All the available colors
+            pub(crate) const ALL: &'static [Color] = &[ $( Color::$color, )* ];
+
+            /// Iterate over every color, including [`Color::Default`], in the order they are
+            /// declared
+            pub fn all() -> impl Iterator<Item = Color> {
+                Self::ALL.iter().copied()
+            }
+
+            /// Every name accepted in styled markup along with the color it refers to
+            pub(crate) const NAMES: &'static [(&'static str, Color)] = &[
+                $( ($name, Color::$color), $( ($alias, Color::$color), )* )*
+            ];
+
+            /// The canonical name of the color in styled markup
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $( Color::$color => $name, )*
+                }
+            }
+
             /// Convert to the foreground ANSI code
             pub fn foreground_code(&self) -> &'static str {
                 match self {
@@ -39,39 +68,313 @@ macro_rules! colors {
                     $( Color::$color => stringify!($bg), )*
                 }
             }
+
+            /// Convert to the ANSI code for the color of underlines
+            ///
+            /// Underline colors are only supported by some terminals (i.e. kitty, WezTerm, and
+            /// VTE-based terminals), and can only be set using the 256-color palette.
+            pub fn underline_code(&self) -> &'static str {
+                match self {
+                    $( Color::$color => $ul, )*
+                }
+            }
         }
 
+        /// Parse a color from its name in styled markup
+        ///
+        /// Names are case-insensitive and bright variants use the `bright-` prefix (i.e.
+        /// `bright-red`). The prefix can also be written with an underscore (i.e. `bright_red`),
+        /// although the hyphenated name is always used when displaying the color.
         impl FromStr for Color {
             type Err = InvalidColorError;
 
             fn from_str(name: &str) -> Result<Self, Self::Err> {
-                Ok(match name.to_ascii_lowercase().as_str() {
-                    $( $names => Color::$color, )*
+                let name = name.to_ascii_lowercase();
+                let name = match name.strip_prefix("bright_") {
+                    Some(color) => format!("bright-{color}"),
+                    None => name,
+                };
+
+                Ok(match name.as_str() {
+                    $( $name $( | $alias )* => Color::$color, )*
                     _ => return Err(InvalidColorError),
                 })
             }
         }
+
+        /// Display the canonical name of the color, the inverse of [`Color::from_str`]
+        impl Display for Color {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
     };
 }
 
+// the name of each color is only listed here. the lexer's pattern for colors can't reference this
+// table, so the tests check that it accepts every name.
 colors! {
-    Black   30 40 ("black"),
-    Red     31 41 ("red"),
-    Green   32 42 ("green"),
-    Yellow  33 43 ("yellow"),
-    Blue    34 44 ("blue"),
-    Magenta 35 45 ("magenta"),
-    Cyan    36 46 ("cyan"),
-    White   37 47 ("white"),
+    Black   30 40 "58;5;0" ("black"),
+    Red     31 41 "58;5;1" ("red"),
+    Green   32 42 "58;5;2" ("green"),
+    Yellow  33 43 "58;5;3" ("yellow"),
+    Blue    34 44 "58;5;4" ("blue"),
+    Magenta 35 45 "58;5;5" ("magenta"),
+    Cyan    36 46 "58;5;6" ("cyan"),
+    White   37 47 "58;5;7" ("white"),
+    // clearing an inherited color leaves the terminal's default color
     #[default]
-    Default 39 49 ("default"),
-
-    BrightBlack   90 100 ("bright-black"),
-    BrightRed     91 101 ("bright-red"),
-    BrightGreen   92 102 ("bright-green"),
-    BrightYellow  93 103 ("bright-yellow"),
-    BrightBlue    94 104 ("bright-blue"),
-    BrightMagenta 95 105 ("bright-magenta"),
-    BrightCyan    96 106 ("bright-cyan"),
-    BrightWhite   97 107 ("bright-white"),
+    Default 39 49 "59" ("default" | "none" | "unset"),
+
+    // gray is usually rendered as bright black in the 16-color palette
+    BrightBlack   90 100 "58;5;8" ("bright-black" | "gray" | "grey"),
+    BrightRed     91 101 "58;5;9" ("bright-red"),
+    BrightGreen   92 102 "58;5;10" ("bright-green"),
+    BrightYellow  93 103 "58;5;11" ("bright-yellow"),
+    BrightBlue    94 104 "58;5;12" ("bright-blue"),
+    BrightMagenta 95 105 "58;5;13" ("bright-magenta"),
+    BrightCyan    96 106 "58;5;14" ("bright-cyan"),
+    BrightWhite   97 107 "58;5;15" ("bright-white"),
+}
+
+impl Color {
+    /// The conventional xterm value of the color as red, green, and blue components
+    ///
+    /// Returns `None` for [`Color::Default`], as it depends on the terminal.
+    pub fn rgb(&self) -> Option<(u8, u8, u8)> {
+        Some(match self {
+            Color::Black => (0x00, 0x00, 0x00),
+            Color::Red => (0xcd, 0x00, 0x00),
+            Color::Green => (0x00, 0xcd, 0x00),
+            Color::Yellow => (0xcd, 0xcd, 0x00),
+            Color::Blue => (0x00, 0x00, 0xee),
+            Color::Magenta => (0xcd, 0x00, 0xcd),
+            Color::Cyan => (0x00, 0xcd, 0xcd),
+            Color::White => (0xe5, 0xe5, 0xe5),
+            Color::Default => return None,
+            Color::BrightBlack => (0x7f, 0x7f, 0x7f),
+            Color::BrightRed => (0xff, 0x00, 0x00),
+            Color::BrightGreen => (0x00, 0xff, 0x00),
+            Color::BrightYellow => (0xff, 0xff, 0x00),
+            Color::BrightBlue => (0x5c, 0x5c, 0xff),
+            Color::BrightMagenta => (0xff, 0x00, 0xff),
+            Color::BrightCyan => (0x00, 0xff, 0xff),
+            Color::BrightWhite => (0xff, 0xff, 0xff),
+        })
+    }
+
+    /// Find the named color that looks closest to an arbitrary sRGB color
+    ///
+    /// Distances are weighted by the average amount of red in the two colors (the "redmean"
+    /// approximation), which follows human perception more closely than a plain euclidean distance
+    /// while staying cheap to compute. [`Color::Default`] is never returned.
+    pub fn nearest(red: u8, green: u8, blue: u8) -> Color {
+        let distance = |color: &Color| {
+            let (r, g, b) = color.rgb().expect("default color is skipped");
+            let mean = (i32::from(red) + i32::from(r)) / 2;
+            let dr = i32::from(red) - i32::from(r);
+            let dg = i32::from(green) - i32::from(g);
+            let db = i32::from(blue) - i32::from(b);
+            (((512 + mean) * dr * dr) >> 8) + 4 * dg * dg + (((767 - mean) * db * db) >> 8)
+        };
+
+        Color::ALL
+            .iter()
+            .filter(|color| **color != Color::Default)
+            .min_by_key(|color| distance(color))
+            .copied()
+            .expect("there are named colors")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, InvalidColorError};
+    use crate::{
+        ast::{Style, Token},
+        parser::{Event, Events, Parser},
+    };
+    use std::str::FromStr;
+
+    #[test]
+    fn from_str_standard() {
+        assert_eq!(Color::from_str("red"), Ok(Color::Red));
+    }
+
+    #[test]
+    fn from_str_bright() {
+        assert_eq!(Color::from_str("bright-cyan"), Ok(Color::BrightCyan));
+    }
+
+    #[test]
+    fn from_str_default() {
+        assert_eq!(Color::from_str("default"), Ok(Color::Default));
+    }
+
+    #[test]
+    fn from_str_ignores_case() {
+        assert_eq!(Color::from_str("Bright-BLUE"), Ok(Color::BrightBlue));
+    }
+
+    #[test]
+    fn from_str_underscore_prefix() {
+        assert_eq!(Color::from_str("bright_green"), Ok(Color::BrightGreen));
+        assert_eq!(Color::from_str("BRIGHT_Black"), Ok(Color::BrightBlack));
+        assert_eq!(Color::BrightGreen.to_string(), "bright-green");
+    }
+
+    #[test]
+    fn underscore_prefix_is_accepted_by_both_parsers() {
+        let source = "[fg:bright_green;bg:Bright_Blue](text)";
+        let expected = Style::fg(Color::BrightGreen).background(Color::BrightBlue);
+
+        let (tokens, errors) = Parser::new(source).parse();
+        assert!(errors.is_empty(), "{errors:?}");
+        assert!(matches!(tokens.as_slice(), [Token::Styled { style, .. }] if *style == expected));
+
+        let events = Events::new(source).collect::<Result<Vec<_>, _>>();
+        assert_eq!(events.unwrap()[0], Event::StyleOpen(expected));
+    }
+
+    #[test]
+    fn from_str_invalid() {
+        assert_eq!(Color::from_str("pink"), Err(InvalidColorError));
+        assert_eq!(Color::from_str("bright-"), Err(InvalidColorError));
+        assert_eq!(Color::from_str("bright_"), Err(InvalidColorError));
+        assert_eq!(Color::from_str("red_bright"), Err(InvalidColorError));
+        assert_eq!(Color::from_str(""), Err(InvalidColorError));
+    }
+
+    #[test]
+    fn names_cover_all_colors() {
+        for color in Color::ALL {
+            assert!(Color::NAMES.iter().any(|(_, c)| c == color), "{color:?}");
+        }
+    }
+
+    #[test]
+    fn gray_is_bright_black() {
+        assert_eq!(Color::from_str("gray"), Ok(Color::BrightBlack));
+        assert_eq!(Color::from_str("GREY"), Ok(Color::BrightBlack));
+        assert_eq!(Color::BrightBlack.to_string(), "bright-black");
+        assert_eq!(Color::from_str("bright-gray"), Err(InvalidColorError));
+    }
+
+    #[test]
+    fn none_and_unset_are_default() {
+        for name in ["none", "unset", "NONE"] {
+            assert_eq!(Color::from_str(name), Ok(Color::Default), "{name}");
+        }
+        assert_eq!(Color::Default.to_string(), "default");
+    }
+
+    #[test]
+    fn none_is_accepted_by_the_parser() {
+        let (tokens, errors) = Parser::new("[fg:none;bg:unset](text)").parse();
+        assert!(errors.is_empty(), "{errors:?}");
+        assert!(matches!(
+            tokens.as_slice(),
+            [Token::Styled { style, .. }] if *style == Style::fg(Color::Default).background(Color::Default)
+        ));
+    }
+
+    #[test]
+    fn gray_is_accepted_by_both_parsers() {
+        for name in ["gray", "grey"] {
+            for tag in ["fg", "bg"] {
+                let source = format!("[{tag}:{name}](text)");
+                let expected = match tag {
+                    "fg" => Style::fg(Color::BrightBlack),
+                    _ => Style::bg(Color::BrightBlack),
+                };
+
+                let (tokens, errors) = Parser::new(&source).parse();
+                assert!(errors.is_empty(), "{source}: {errors:?}");
+                assert!(
+                    matches!(tokens.as_slice(), [Token::Styled { style, .. }] if *style == expected),
+                    "{source}"
+                );
+
+                let events = Events::new(&source).collect::<Result<Vec<_>, _>>();
+                assert_eq!(events.unwrap()[0], Event::StyleOpen(expected), "{source}");
+            }
+        }
+    }
+
+    #[test]
+    fn names_round_trip_through_from_str() {
+        for (name, color) in Color::NAMES {
+            assert_eq!(Color::from_str(name), Ok(*color), "{name}");
+            assert_eq!(Color::from_str(&name.to_uppercase()), Ok(*color), "{name}");
+        }
+    }
+
+    #[test]
+    fn name_round_trips_through_from_str() {
+        for color in Color::ALL {
+            assert_eq!(Color::from_str(color.name()), Ok(*color), "{color:?}");
+            assert_eq!(color.to_string(), color.name());
+        }
+    }
+
+    #[test]
+    fn all_colors() {
+        assert_eq!(Color::all().count(), 17);
+        assert_eq!(Color::all().next(), Some(Color::Black));
+        for color in Color::all() {
+            assert_eq!(color.to_string().parse::<Color>(), Ok(color));
+        }
+    }
+
+    #[test]
+    fn name_is_kebab_case() {
+        assert_eq!(Color::Red.name(), "red");
+        assert_eq!(Color::BrightRed.name(), "bright-red");
+        assert_eq!(Color::Default.to_string(), "default");
+    }
+
+    #[test]
+    fn rgb_only_missing_for_default() {
+        for color in Color::ALL {
+            assert_eq!(color.rgb().is_none(), *color == Color::Default, "{color:?}");
+        }
+    }
+
+    #[test]
+    fn nearest_exact_matches() {
+        for color in Color::ALL.iter().filter(|c| **c != Color::Default) {
+            let (r, g, b) = color.rgb().unwrap();
+            assert_eq!(Color::nearest(r, g, b), *color);
+        }
+    }
+
+    #[test]
+    fn nearest_known_values() {
+        assert_eq!(Color::nearest(128, 0, 0), Color::Red);
+        assert_eq!(Color::nearest(255, 165, 0), Color::Yellow);
+        assert_eq!(Color::nearest(128, 128, 128), Color::BrightBlack);
+        assert_eq!(Color::nearest(192, 192, 192), Color::White);
+        assert_eq!(Color::nearest(30, 30, 30), Color::Black);
+        assert_eq!(Color::nearest(100, 149, 237), Color::BrightBlue);
+        assert_eq!(Color::nearest(0, 180, 180), Color::Cyan);
+        assert_eq!(Color::nearest(255, 105, 180), Color::Magenta);
+    }
+
+    #[test]
+    fn names_are_accepted_by_both_parsers() {
+        for (name, color) in Color::NAMES {
+            let source = format!("[fg:{name};bg:{name}](text)");
+            let expected = Style::fg(*color).background(*color);
+
+            let (tokens, errors) = Parser::new(&source).parse();
+            assert!(errors.is_empty(), "{name}: {errors:?}");
+            assert!(
+                matches!(tokens.as_slice(), [Token::Styled { style, .. }] if *style == expected),
+                "{name}"
+            );
+
+            let events = Events::new(&source).collect::<Result<Vec<_>, _>>();
+            assert_eq!(events.unwrap()[0], Event::StyleOpen(expected), "{name}");
+        }
+    }
 }
@@ -0,0 +1,225 @@
+use super::{Color, Decoration, Style};
+use ::crossterm::style::{Attribute, ContentStyle};
+
+impl From<Color> for ::crossterm::style::Color {
+    fn from(color: Color) -> Self {
+        use ::crossterm::style::Color::*;
+
+        // crossterm names the standard colors "dark" and the bright colors after the color itself
+        match color {
+            Color::Black => Black,
+            Color::Red => DarkRed,
+            Color::Green => DarkGreen,
+            Color::Yellow => DarkYellow,
+            Color::Blue => DarkBlue,
+            Color::Magenta => DarkMagenta,
+            Color::Cyan => DarkCyan,
+            Color::White => Grey,
+            Color::BrightBlack => DarkGrey,
+            Color::BrightRed => Red,
+            Color::BrightGreen => Green,
+            Color::BrightYellow => Yellow,
+            Color::BrightBlue => Blue,
+            Color::BrightMagenta => Magenta,
+            Color::BrightCyan => Cyan,
+            Color::BrightWhite => White,
+            Color::Default => Reset,
+        }
+    }
+}
+
+impl From<Decoration> for Attribute {
+    fn from(decoration: Decoration) -> Self {
+        match decoration {
+            Decoration::Bold => Attribute::Bold,
+            Decoration::Dim => Attribute::Dim,
+            Decoration::Italic => Attribute::Italic,
+            Decoration::Underline => Attribute::Underlined,
+            Decoration::FastBlink => Attribute::RapidBlink,
+            Decoration::SlowBlink => Attribute::SlowBlink,
+            Decoration::Invert => Attribute::Reverse,
+            Decoration::Hide => Attribute::Hidden,
+            Decoration::StrikeThrough => Attribute::CrossedOut,
+        }
+    }
+}
+
+/// The decorations that are removed by each attribute
+const REMOVED_BY: [(&[Decoration], Attribute); 7] = [
+    (
+        &[Decoration::Bold, Decoration::Dim],
+        Attribute::NormalIntensity,
+    ),
+    (&[Decoration::Italic], Attribute::NoItalic),
+    (&[Decoration::Underline], Attribute::NoUnderline),
+    (
+        &[Decoration::FastBlink, Decoration::SlowBlink],
+        Attribute::NoBlink,
+    ),
+    (&[Decoration::Invert], Attribute::NoReverse),
+    (&[Decoration::Hide], Attribute::NoHidden),
+    (&[Decoration::StrikeThrough], Attribute::NotCrossedOut),
+];
+
+/// Convert the style to a [`ContentStyle`] for rendering with `crossterm`
+///
+/// Every color and decoration has an equivalent, including the underline color. As `crossterm`
+/// applies attributes after the colors, `reset` can't use [`Attribute::Reset`]. Instead, the
+/// colors default to [`Color::Reset`](::crossterm::style::Color::Reset) and the attributes that
+/// remove decorations are set for every decoration the style doesn't apply. The only exception is
+/// `bold` and `dim`, which are removed together, so an inherited `dim` is kept when the style is
+/// `bold` and vice versa.
+impl From<&Style> for ContentStyle {
+    fn from(style: &Style) -> Self {
+        let mut result = ContentStyle::new();
+        result.foreground_color = style.foreground.map(Into::into);
+        result.background_color = style.background.map(Into::into);
+        result.underline_color = style.underline_color.map(Into::into);
+
+        for decoration in style.decoration.iter().flatten() {
+            result.attributes.set((*decoration).into());
+        }
+
+        if style.reset {
+            let applied = |decoration| {
+                style
+                    .decoration
+                    .as_ref()
+                    .is_some_and(|applied| applied.contains(decoration))
+            };
+
+            result
+                .foreground_color
+                .get_or_insert(::crossterm::style::Color::Reset);
+            result
+                .background_color
+                .get_or_insert(::crossterm::style::Color::Reset);
+            result
+                .underline_color
+                .get_or_insert(::crossterm::style::Color::Reset);
+            for (decorations, attribute) in REMOVED_BY {
+                if !decorations.iter().any(applied) {
+                    result.attributes.set(attribute);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl From<Style> for ContentStyle {
+    fn from(style: Style) -> Self {
+        Self::from(&style)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Color, Decoration, Style};
+    use ::crossterm::style::{Attribute, Attributes, ContentStyle, Stylize};
+
+    #[test]
+    fn colors() {
+        let style = ContentStyle::from(Style::fg(Color::Red).background(Color::BrightRed));
+        assert_eq!(style, ContentStyle::new().dark_red().on_red());
+    }
+
+    #[test]
+    fn every_color_has_the_same_index() {
+        for color in Color::all().filter(|&color| color != Color::Default) {
+            // crossterm writes colors using their index in the 256-color palette
+            let code = color.foreground_code().parse::<u8>().unwrap();
+            let index = if code >= 90 { code - 90 + 8 } else { code - 30 };
+
+            let written = ContentStyle::from(Style::fg(color)).apply("").to_string();
+            assert!(
+                written.starts_with(&format!("\x1b[38;5;{index}m")),
+                "{color}: {written:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn underline_color() {
+        let style =
+            ContentStyle::from(Style::deco(Decoration::Underline).underline_color(Color::Blue));
+        assert_eq!(
+            style.underline_color,
+            Some(::crossterm::style::Color::DarkBlue)
+        );
+    }
+
+    #[test]
+    fn default_color_is_reset() {
+        let style = ContentStyle::from(Style::fg(Color::Default));
+        assert_eq!(
+            style.foreground_color,
+            Some(::crossterm::style::Color::Reset)
+        );
+    }
+
+    #[test]
+    fn decorations() {
+        let style = ContentStyle::from(
+            Style::deco(Decoration::Bold)
+                .decoration(Decoration::Dim)
+                .decoration(Decoration::Italic)
+                .decoration(Decoration::Underline)
+                .decoration(Decoration::FastBlink)
+                .decoration(Decoration::SlowBlink)
+                .decoration(Decoration::Invert)
+                .decoration(Decoration::Hide)
+                .decoration(Decoration::StrikeThrough),
+        );
+        assert_eq!(
+            style.attributes,
+            Attributes::from(
+                [
+                    Attribute::Bold,
+                    Attribute::Dim,
+                    Attribute::Italic,
+                    Attribute::Underlined,
+                    Attribute::RapidBlink,
+                    Attribute::SlowBlink,
+                    Attribute::Reverse,
+                    Attribute::Hidden,
+                    Attribute::CrossedOut,
+                ]
+                .as_slice()
+            )
+        );
+    }
+
+    #[test]
+    fn reset() {
+        let style = ContentStyle::from(Style {
+            reset: true,
+            ..Style::fg(Color::Red).decoration(Decoration::Italic)
+        });
+        assert_eq!(
+            style,
+            ContentStyle::new()
+                .dark_red()
+                .on(::crossterm::style::Color::Reset)
+                .underline(::crossterm::style::Color::Reset)
+                .italic()
+                .attribute(Attribute::NormalIntensity)
+                .attribute(Attribute::NoUnderline)
+                .attribute(Attribute::NoBlink)
+                .attribute(Attribute::NoReverse)
+                .attribute(Attribute::NoHidden)
+                .attribute(Attribute::NotCrossedOut)
+        );
+    }
+
+    #[test]
+    fn reset_keeps_applied_intensity() {
+        let style = ContentStyle::from(Style {
+            reset: true,
+            ..Style::deco(Decoration::Bold)
+        });
+        assert!(style.attributes.has(Attribute::Bold));
+        assert!(!style.attributes.has(Attribute::NormalIntensity));
+    }
+}
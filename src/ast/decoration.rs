@@ -4,7 +4,7 @@ use std::{
 };
 
 /// The provided [`Decoration`] name was invalid
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct InvalidDecorationError;
 
 impl std::error::Error for InvalidDecorationError {}
@@ -17,15 +17,42 @@ impl Display for InvalidDecorationError {
 
 macro_rules! decorations {
     (
-        $( $decoration:ident $apply:literal $remove:literal ( $names:pat ) ),* $(,)?
+        $(
+            $decoration:ident $apply:literal $remove:literal ( $name:literal $( | $alias:literal )* )
+        ),* $(,)?
     ) => {
         /// Available standard ANSI text decorations
         #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(rename_all = "kebab-case")
+        )]
         pub enum Decoration {
             $( $decoration, )*
         }
 
         impl Decoration {
+            /// All the available decorations
+            pub(crate) const ALL: &'static [Decoration] = &[ $( Decoration::$decoration, )* ];
+
+            /// Iterate over every decoration, in the order they are declared
+            pub fn all() -> impl Iterator<Item = Decoration> {
+                Self::ALL.iter().copied()
+            }
+
+            /// Every name accepted in styled markup along with the decoration it refers to
+            pub(crate) const NAMES: &'static [(&'static str, Decoration)] = &[
+                $( ($name, Decoration::$decoration), $( ($alias, Decoration::$decoration), )* )*
+            ];
+
+            /// The canonical name of the decoration in styled markup
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $( Decoration::$decoration => $name, )*
+                }
+            }
+
             /// Convert to the ANSI code for applying the styling
             pub fn apply_code(&self) -> &'static str {
                 match self {
@@ -41,19 +68,33 @@ macro_rules! decorations {
             }
         }
 
+        /// Parse a decoration from its name in styled markup
+        ///
+        /// Names are case-insensitive and accept the same aliases as styled markup (i.e. `faint`
+        /// for [`Decoration::Dim`]).
         impl FromStr for Decoration {
             type Err = InvalidDecorationError;
 
             fn from_str(name: &str) -> Result<Self, Self::Err> {
                 Ok(match name.to_ascii_lowercase().as_str() {
-                    $( $names => Decoration::$decoration, )*
+                    $( $name $( | $alias )* => Decoration::$decoration, )*
                     _ => return Err(InvalidDecorationError),
                 })
             }
         }
+
+        /// Display the canonical name of the decoration, the inverse of [`Decoration::from_str`]
+        impl Display for Decoration {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
     };
 }
 
+// the names of each decoration, including any aliases, are only listed here. the first name is the
+// canonical one. the lexer's pattern for decorations can't reference this table, so the tests check
+// that it accepts every name.
 decorations! {
     Bold          1 22 ("bold"),
     Dim           2 22 ("dim" | "faint"),
@@ -63,5 +104,117 @@ decorations! {
     FastBlink     6 25 ("fast-blink" | "blink-fast"),
     Invert        7 27 ("invert" | "reverse"),
     Hide          8 28 ("hide" | "conceal"),
-    StrikeThrough 9 29 ("strikethrough" | "strike-through"),
+    StrikeThrough 9 29 ("strike-through" | "strikethrough"),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoration, InvalidDecorationError};
+    use crate::{
+        ast::{Style, Token},
+        parser::{Event, Events, Parser},
+    };
+    use std::str::FromStr;
+
+    #[test]
+    fn from_str_canonical() {
+        assert_eq!(Decoration::from_str("bold"), Ok(Decoration::Bold));
+        assert_eq!(
+            Decoration::from_str("fast-blink"),
+            Ok(Decoration::FastBlink)
+        );
+    }
+
+    #[test]
+    fn from_str_aliases() {
+        assert_eq!(Decoration::from_str("faint"), Ok(Decoration::Dim));
+        assert_eq!(
+            Decoration::from_str("blink-slow"),
+            Ok(Decoration::SlowBlink)
+        );
+        assert_eq!(Decoration::from_str("reverse"), Ok(Decoration::Invert));
+        assert_eq!(Decoration::from_str("conceal"), Ok(Decoration::Hide));
+        assert_eq!(
+            Decoration::from_str("strikethrough"),
+            Ok(Decoration::StrikeThrough)
+        );
+        assert_eq!(
+            Decoration::from_str("strike-through"),
+            Ok(Decoration::StrikeThrough)
+        );
+    }
+
+    #[test]
+    fn from_str_ignores_case() {
+        assert_eq!(Decoration::from_str("ITALIC"), Ok(Decoration::Italic));
+    }
+
+    #[test]
+    fn from_str_invalid() {
+        assert_eq!(Decoration::from_str("blink"), Err(InvalidDecorationError));
+        assert_eq!(Decoration::from_str(""), Err(InvalidDecorationError));
+    }
+
+    #[test]
+    fn names_cover_all_decorations() {
+        for decoration in Decoration::ALL {
+            assert!(
+                Decoration::NAMES.iter().any(|(_, d)| d == decoration),
+                "{decoration:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn names_round_trip_through_from_str() {
+        for (name, decoration) in Decoration::NAMES {
+            assert_eq!(Decoration::from_str(name), Ok(*decoration), "{name}");
+        }
+    }
+
+    #[test]
+    fn name_round_trips_through_from_str() {
+        for decoration in Decoration::ALL {
+            assert_eq!(
+                Decoration::from_str(decoration.name()),
+                Ok(*decoration),
+                "{decoration:?}"
+            );
+            assert_eq!(decoration.to_string(), decoration.name());
+        }
+    }
+
+    #[test]
+    fn all_decorations() {
+        assert_eq!(Decoration::all().count(), 9);
+        assert_eq!(Decoration::all().next(), Some(Decoration::Bold));
+        for decoration in Decoration::all() {
+            assert_eq!(decoration.to_string().parse::<Decoration>(), Ok(decoration));
+        }
+    }
+
+    #[test]
+    fn name_is_kebab_case() {
+        assert_eq!(Decoration::Bold.name(), "bold");
+        assert_eq!(Decoration::SlowBlink.name(), "slow-blink");
+        assert_eq!(Decoration::StrikeThrough.to_string(), "strike-through");
+    }
+
+    #[test]
+    fn names_are_accepted_by_both_parsers() {
+        for (name, decoration) in Decoration::NAMES {
+            let source = format!("[deco:{name}](text)");
+            let expected = Style::deco(*decoration);
+
+            let (tokens, errors) = Parser::new(&source).parse();
+            assert!(errors.is_empty(), "{name}: {errors:?}");
+            assert!(
+                matches!(tokens.as_slice(), [Token::Styled { style, .. }] if *style == expected),
+                "{name}"
+            );
+
+            let events = Events::new(&source).collect::<Result<Vec<_>, _>>();
+            assert_eq!(events.unwrap()[0], Event::StyleOpen(expected), "{name}");
+        }
+    }
 }
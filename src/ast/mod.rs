@@ -1,11 +1,16 @@
+#[cfg(feature = "anstyle")]
+mod anstyle;
 mod color;
+#[cfg(feature = "crossterm")]
+mod crossterm;
 mod decoration;
 mod style;
+mod termcolor;
 mod token;
 
-#[allow(unused_imports)]
+#[cfg(feature = "anstyle")]
+pub use anstyle::UnsupportedStyleError;
 pub use color::{Color, InvalidColorError};
-#[allow(unused_imports)]
 pub use decoration::{Decoration, InvalidDecorationError};
 pub use style::{CurrentStyle, Style};
-pub use token::{Token, Tokens};
+pub use token::{Segments, Token, Tokens};
@@ -1,26 +1,141 @@
 use super::{Color, Decoration};
 use indexmap::IndexSet;
+use std::fmt::{Display, Formatter};
 
 /// Styles that can be applied to a piece of text
-#[derive(Clone, Debug, Default)]
-#[cfg_attr(test, derive(Eq, PartialEq))]
+///
+/// Styles can either be populated directly or built up fluently:
+///
+/// ```
+/// use antsi::{Color, Decoration, Style};
+///
+/// let style = Style::new()
+///     .foreground(Color::Red)
+///     .background(Color::Blue)
+///     .decoration(Decoration::Bold);
+/// ```
+///
+/// They can also be parsed from a style specifier, the same as in styled markup:
+///
+/// ```
+/// use antsi::{Color, Style};
+///
+/// let style = "[fg:red;deco:bold]".parse::<Style>().unwrap();
+/// assert_eq!(style.foreground, Some(Color::Red));
+/// ```
+///
+/// The [`Default`] style has every property unset (`None`), so all of them are inherited from the
+/// enclosing styles. A style with a single property can be created directly from a [`Color`]
+/// (the foreground) or a [`Decoration`], or using [`Style::fg`], [`Style::bg`], and [`Style::deco`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct Style {
     /// The foreground color
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub foreground: Option<Color>,
     /// The background color
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub background: Option<Color>,
+    /// The color of underlines, which is only supported by some terminals
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub underline_color: Option<Color>,
     /// Additional text decoration (i.e. bold, italic, underline, etc.)
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub decoration: Option<IndexSet<Decoration>>,
+    /// Whether to clear all the inherited styles before applying this one
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub reset: bool,
 }
 
 impl Style {
+    /// Create a new style without any properties
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a style with only a foreground color
+    pub fn fg(color: Color) -> Self {
+        Self::new().foreground(color)
+    }
+
+    /// Create a style with only a background color
+    pub fn bg(color: Color) -> Self {
+        Self::new().background(color)
+    }
+
+    /// Create a style with only a single text decoration
+    pub fn deco(decoration: Decoration) -> Self {
+        Self::new().decoration(decoration)
+    }
+
+    /// Set the foreground color
+    pub fn foreground(mut self, color: Color) -> Self {
+        self.foreground = Some(color);
+        self
+    }
+
+    /// Set the background color
+    pub fn background(mut self, color: Color) -> Self {
+        self.background = Some(color);
+        self
+    }
+
+    /// Set the color of underlines
+    ///
+    /// The color is only visible on text that is underlined, either by this style or a parent.
+    pub fn underline_color(mut self, color: Color) -> Self {
+        self.underline_color = Some(color);
+        self
+    }
+
+    /// Add a text decoration
+    ///
+    /// Decorations accumulate, so this can be called multiple times to apply several decorations.
+    pub fn decoration(mut self, decoration: Decoration) -> Self {
+        self.decoration
+            .get_or_insert_with(IndexSet::new)
+            .insert(decoration);
+        self
+    }
+
+    /// Combine with the style of a child, with the child taking precedence
+    ///
+    /// Colors set by the child replace those of the parent, while decorations are combined, the
+    /// same as [`CurrentStyle::extend`].
+    pub(crate) fn extend(&self, child: &Style) -> Style {
+        if child.reset {
+            return child.clone();
+        }
+
+        let decoration = match (&self.decoration, &child.decoration) {
+            (Some(parent), Some(child)) => Some(parent.union(child).copied().collect()),
+            (parent, child) => child.as_ref().or(parent.as_ref()).cloned(),
+        };
+
+        Style {
+            foreground: child.foreground.or(self.foreground),
+            background: child.background.or(self.background),
+            underline_color: child.underline_color.or(self.underline_color),
+            decoration,
+            reset: self.reset,
+        }
+    }
+
     /// Check if the style has any properties
-    fn is_empty(&self) -> bool {
+    pub(crate) fn is_empty(&self) -> bool {
         let has_decorations = match &self.decoration {
             Some(decorations) => decorations.is_empty(),
             None => true,
         };
-        self.foreground.is_none() && self.background.is_none() && has_decorations
+        self.foreground.is_none()
+            && self.background.is_none()
+            && self.underline_color.is_none()
+            && has_decorations
+            && !self.reset
     }
 
     /// Apply the current style to the text
@@ -29,30 +144,7 @@ impl Style {
             return;
         }
 
-        // typically we'll only have a foreground and single decoration
-        let mut codes = Vec::with_capacity(2);
-
-        if let Some(foreground) = self.foreground {
-            if foreground != parent.foreground {
-                codes.push(foreground.foreground_code())
-            }
-        }
-
-        if let Some(background) = self.background {
-            if background != parent.background {
-                codes.push(background.background_code());
-            }
-        }
-
-        if let Some(decorations) = &self.decoration {
-            codes.extend(
-                decorations
-                    .difference(&parent.decoration)
-                    .map(Decoration::apply_code),
-            );
-        }
-
-        self.append_codes(codes, output);
+        self.append_codes(parent.diff(self), output);
     }
 
     /// Reset the style to what it was previously
@@ -61,6 +153,12 @@ impl Style {
             return;
         }
 
+        // nothing can be selectively undone after a full reset, so the parent is re-applied
+        if self.reset {
+            self.reset_fully(parent, output);
+            return;
+        }
+
         // typically we'll only have a foreground and single decoration
         let mut codes = Vec::with_capacity(2);
 
@@ -76,6 +174,12 @@ impl Style {
             }
         }
 
+        if let Some(underline_color) = self.underline_color {
+            if underline_color != parent.underline_color {
+                codes.push(parent.underline_color.underline_code());
+            }
+        }
+
         if let Some(decorations) = &self.decoration {
             codes.extend(
                 decorations
@@ -87,6 +191,19 @@ impl Style {
         self.append_codes(codes, output);
     }
 
+    /// Reset the style to what it was previously by clearing every style and re-applying the
+    /// parent's styles
+    pub fn reset_fully(&self, parent: &CurrentStyle, output: &mut String) {
+        if self.is_empty() {
+            return;
+        }
+
+        let mut codes = vec!["0"];
+        codes.extend(parent.codes());
+
+        self.append_codes(codes, output);
+    }
+
     /// Append the ANSI codes to the output
     fn append_codes(&self, codes: Vec<&str>, output: &mut String) {
         if codes.is_empty() {
@@ -99,27 +216,157 @@ impl Style {
     }
 }
 
+/// Display the style as a style specifier in styled markup (i.e. `[reset;fg:red;deco:bold]`)
+///
+/// A style without any properties is displayed as `[]`, which is not valid markup.
+impl Display for Style {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut specifiers = Vec::new();
+        if self.reset {
+            specifiers.push(String::from("reset"));
+        }
+        if let Some(color) = self.foreground {
+            specifiers.push(format!("fg:{color}"));
+        }
+        if let Some(color) = self.background {
+            specifiers.push(format!("bg:{color}"));
+        }
+        if let Some(color) = self.underline_color {
+            specifiers.push(format!("ul:{color}"));
+        }
+        if let Some(decorations) = self.decoration.as_ref().filter(|d| !d.is_empty()) {
+            let names = decorations.iter().map(Decoration::name).collect::<Vec<_>>();
+            specifiers.push(format!("deco:{}", names.join(",")));
+        }
+
+        write!(f, "[{}]", specifiers.join(";"))
+    }
+}
+
+impl From<Color> for Style {
+    fn from(color: Color) -> Self {
+        Style::fg(color)
+    }
+}
+
+impl From<Decoration> for Style {
+    fn from(decoration: Decoration) -> Self {
+        Style::deco(decoration)
+    }
+}
+
 /// The current styles applied to a piece of text
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct CurrentStyle {
     foreground: Color,
     background: Color,
+    underline_color: Color,
     decoration: IndexSet<Decoration>,
 }
 
 impl CurrentStyle {
+    /// The foreground color
+    pub fn foreground(&self) -> Color {
+        self.foreground
+    }
+
+    /// The background color
+    pub fn background(&self) -> Color {
+        self.background
+    }
+
+    /// The color of underlines
+    pub fn underline_color(&self) -> Color {
+        self.underline_color
+    }
+
+    /// The text decorations
+    pub fn decoration(&self) -> &IndexSet<Decoration> {
+        &self.decoration
+    }
+
     /// Extend the current style with additional styles from a token
+    ///
+    /// If the token resets the styles, none of the current styles are kept.
     pub fn extend(&self, style: &Style) -> CurrentStyle {
-        let mut current = CurrentStyle::clone(self);
+        let mut current = if style.reset {
+            CurrentStyle::default()
+        } else {
+            CurrentStyle::clone(self)
+        };
 
         current.foreground = style.foreground.unwrap_or(current.foreground);
         current.background = style.background.unwrap_or(current.background);
+        current.underline_color = style.underline_color.unwrap_or(current.underline_color);
         if let Some(decoration) = &style.decoration {
             current.decoration.extend(decoration.iter());
         }
 
         current
     }
+
+    /// The SGR codes needed to go from the current style to one with the child style applied
+    ///
+    /// Only the properties the child changes are included, so the result is empty when the child
+    /// doesn't change anything. If the child resets the styles, the codes start with `0` and
+    /// include every property the child sets.
+    pub fn diff(&self, child: &Style) -> Vec<&'static str> {
+        // typically we'll only have a foreground and single decoration
+        let mut codes = Vec::with_capacity(2);
+
+        // once reset, the styles are applied as if there was no parent
+        let reset_parent = CurrentStyle::default();
+        let parent = if child.reset {
+            codes.push("0");
+            &reset_parent
+        } else {
+            self
+        };
+
+        if let Some(foreground) = child.foreground {
+            if foreground != parent.foreground {
+                codes.push(foreground.foreground_code())
+            }
+        }
+
+        if let Some(background) = child.background {
+            if background != parent.background {
+                codes.push(background.background_code());
+            }
+        }
+
+        if let Some(underline_color) = child.underline_color {
+            if underline_color != parent.underline_color {
+                codes.push(underline_color.underline_code());
+            }
+        }
+
+        if let Some(decorations) = &child.decoration {
+            codes.extend(
+                decorations
+                    .difference(&parent.decoration)
+                    .map(Decoration::apply_code),
+            );
+        }
+
+        codes
+    }
+
+    /// The codes needed to apply every style from scratch, i.e. after a full reset
+    pub(crate) fn codes(&self) -> Vec<&'static str> {
+        let mut codes = Vec::new();
+        if self.foreground != Color::Default {
+            codes.push(self.foreground.foreground_code());
+        }
+        if self.background != Color::Default {
+            codes.push(self.background.background_code());
+        }
+        if self.underline_color != Color::Default {
+            codes.push(self.underline_color.underline_code());
+        }
+        codes.extend(self.decoration.iter().map(Decoration::apply_code));
+        codes
+    }
 }
 
 impl From<Style> for CurrentStyle {
@@ -127,6 +374,7 @@ impl From<Style> for CurrentStyle {
         CurrentStyle {
             foreground: style.foreground.unwrap_or_default(),
             background: style.background.unwrap_or_default(),
+            underline_color: style.underline_color.unwrap_or_default(),
             decoration: style.decoration.unwrap_or_default(),
         }
     }
@@ -134,7 +382,25 @@ impl From<Style> for CurrentStyle {
 
 #[cfg(test)]
 mod tests {
-    use super::{CurrentStyle, Style};
+    use super::{Color, CurrentStyle, Decoration, Style};
+
+    #[test]
+    fn display_specifier() {
+        assert_eq!(style!(fg: Red;).to_string(), "[fg:red]");
+        assert_eq!(
+            style!(reset; fg: BrightBlue; bg: Default; deco: Bold, StrikeThrough;).to_string(),
+            "[reset;fg:bright-blue;bg:default;deco:bold,strike-through]"
+        );
+        assert_eq!(style!().to_string(), "[]");
+    }
+
+    #[test]
+    fn display_underline_color() {
+        assert_eq!(
+            style!(ul: Red; deco: Underline;).to_string(),
+            "[ul:red;deco:underline]"
+        );
+    }
 
     #[test]
     fn default_is_empty() {
@@ -154,6 +420,12 @@ mod tests {
         assert!(!style.is_empty());
     }
 
+    #[test]
+    fn is_empty_with_underline_color() {
+        let style = style!(ul: Red;);
+        assert!(!style.is_empty());
+    }
+
     #[test]
     fn is_empty_with_single_decoration() {
         let style = style!(deco: Bold;);
@@ -166,6 +438,83 @@ mod tests {
         assert!(!style.is_empty());
     }
 
+    #[test]
+    fn new_is_empty() {
+        assert_eq!(Style::new(), style!());
+    }
+
+    #[test]
+    fn builder_foreground() {
+        assert_eq!(Style::new().foreground(Color::Red), style!(fg: Red;));
+    }
+
+    #[test]
+    fn builder_background() {
+        assert_eq!(Style::new().background(Color::Blue), style!(bg: Blue;));
+    }
+
+    #[test]
+    fn builder_underline_color() {
+        assert_eq!(Style::new().underline_color(Color::Red), style!(ul: Red;));
+    }
+
+    #[test]
+    fn builder_single_decoration() {
+        assert_eq!(
+            Style::new().decoration(Decoration::Bold),
+            style!(deco: Bold;)
+        );
+    }
+
+    #[test]
+    fn builder_multiple_decorations_accumulate() {
+        let style = Style::new()
+            .decoration(Decoration::Bold)
+            .decoration(Decoration::Italic)
+            .decoration(Decoration::Bold);
+        assert_eq!(style, style!(deco: Bold, Italic;));
+    }
+
+    #[test]
+    fn builder_repeated_color_last_wins() {
+        let style = Style::new().foreground(Color::Red).foreground(Color::Green);
+        assert_eq!(style, style!(fg: Green;));
+    }
+
+    #[test]
+    fn builder_all_properties() {
+        let style = Style::new()
+            .foreground(Color::Red)
+            .background(Color::Blue)
+            .decoration(Decoration::Bold);
+        assert_eq!(style, style!(fg: Red; bg: Blue; deco: Bold;));
+    }
+
+    #[test]
+    fn default_has_no_properties() {
+        let style = Style::default();
+        assert_eq!(style.foreground, None);
+        assert_eq!(style.background, None);
+        assert_eq!(style.decoration, None);
+    }
+
+    #[test]
+    fn single_property_constructors() {
+        assert_eq!(Style::fg(Color::Red), style!(fg: Red;));
+        assert_eq!(Style::bg(Color::Blue), style!(bg: Blue;));
+        assert_eq!(Style::deco(Decoration::Bold), style!(deco: Bold;));
+    }
+
+    #[test]
+    fn from_color_sets_foreground() {
+        assert_eq!(Style::from(Color::Green), style!(fg: Green;));
+    }
+
+    #[test]
+    fn from_decoration() {
+        assert_eq!(Style::from(Decoration::Italic), style!(deco: Italic;));
+    }
+
     /// Create a sequence of tests
     macro_rules! simple_tests {
     (
@@ -204,6 +553,13 @@ mod tests {
         apply_foreground_identical_to_parent: style!(fg: Red;), style!(fg: Red;) => "",
         apply_background_different_from_parent: style!(bg: Red;), style!(bg: Blue;) => "\x1b[41m",
         apply_background_identical_to_parent: style!(bg: Red;), style!(bg: Red;) => "",
+        apply_underline_color_different_from_parent: style!(ul: Red;), style!(ul: Blue;) => "\x1b[58;5;1m",
+        apply_underline_color_identical_to_parent: style!(ul: Red;), style!(ul: Red;) => "",
+        apply_underline_color_default_under_colored_parent: style!(ul: Default;), style!(ul: Blue;) => "\x1b[59m",
+        apply_background_default_under_colored_parent: style!(bg: Default;), style!(bg: Blue;) => "\x1b[49m",
+        apply_foreground_default_without_parent: style!(fg: Default;), style!() => "",
+        apply_foreground_default_under_colored_parent: style!(fg: Default;), style!(fg: Red;) => "\x1b[39m",
+        apply_background_default_without_parent: style!(bg: Default;), style!() => "",
         apply_single_decoration_different_from_parent: style!(deco: Bold;), style!(deco: Dim;) => "\x1b[1m",
         apply_multiple_decoration_different_from_parent: style!(deco: Bold, Italic;), style!(deco: Dim, Hide;) => "\x1b[1;3m",
         apply_single_decoration_identical_to_parent: style!(deco: Bold;), style!(deco: Bold;) => "",
@@ -241,6 +597,10 @@ mod tests {
         apply_background_and_multiple_decorations_parent_matches_first_decoration: style!(bg: Blue; deco: Bold, Italic;), style!(deco: Bold;) => "\x1b[44;3m",
         apply_background_and_multiple_decorations_parent_matches_second_decoration: style!(bg: Blue; deco: Bold, Italic;), style!(deco: Italic;) => "\x1b[44;1m",
         apply_background_and_mulitple_decorations_parent_matches_both_decorations: style!(bg: Blue; deco: Bold, Italic;), style!(deco: Bold, Italic;) => "\x1b[44m",
+
+        apply_full_reset_no_parent: style!(reset;), style!() => "\x1b[0m",
+        apply_full_reset_under_styled_parent: style!(reset;), style!(fg: Red; bg: Blue; deco: Bold;) => "\x1b[0m",
+        apply_full_reset_reapplies_parent_styles: style!(reset; fg: Red; deco: Bold;), style!(fg: Red; deco: Bold;) => "\x1b[0;31;1m",
     }
 
     simple_tests! {
@@ -283,6 +643,12 @@ mod tests {
         ansi_code_background_bright_cyan: style!(bg: BrightCyan;), style!() => "\x1b[106m",
         ansi_code_background_bright_white: style!(bg: BrightWhite;), style!() => "\x1b[107m",
 
+        ansi_code_underline_color_black: style!(ul: Black;), style!() => "\x1b[58;5;0m",
+        ansi_code_underline_color_white: style!(ul: White;), style!() => "\x1b[58;5;7m",
+        ansi_code_underline_color_bright_black: style!(ul: BrightBlack;), style!() => "\x1b[58;5;8m",
+        ansi_code_underline_color_bright_white: style!(ul: BrightWhite;), style!() => "\x1b[58;5;15m",
+        ansi_code_underline_color_default: style!(ul: Default;), style!(ul: Red;) => "\x1b[59m",
+
         ansi_code_decoration_bold: style!(deco: Bold;), style!() => "\x1b[1m",
         ansi_code_decoration_dim: style!(deco: Dim;), style!() => "\x1b[2m",
         ansi_code_decoration_italic: style!(deco: Italic;), style!() => "\x1b[3m",
@@ -300,6 +666,24 @@ mod tests {
         output
     }
 
+    #[test]
+    fn reset_fully_reapplies_parent() {
+        let mut output = String::new();
+
+        let style = style!(fg: Red; deco: Italic;);
+        let parent = style!(bg: Blue; deco: Bold;);
+        style.reset_fully(&parent.into(), &mut output);
+
+        assert_eq!(output, "\x1b[0;44;1m");
+    }
+
+    #[test]
+    fn reset_fully_empty_style() {
+        let mut output = String::new();
+        style!().reset_fully(&style!(fg: Red;).into(), &mut output);
+        assert_eq!(output, "");
+    }
+
     #[test]
     fn reset_appends_to_end_of_output() {
         let mut output = String::from("existing");
@@ -317,6 +701,11 @@ mod tests {
         reset_foreground_identical_to_parent: style!(fg: Red;), style!(fg: Red;) => "",
         reset_background_different_from_parent: style!(bg: Red;), style!(bg: Blue;) => "\x1b[44m",
         reset_background_identical_to_parent: style!(bg: Red;), style!(bg: Red;) => "",
+        reset_underline_color_different_from_parent: style!(ul: Red;), style!(ul: BrightBlue;) => "\x1b[58;5;12m",
+        reset_underline_color_identical_to_parent: style!(ul: Red;), style!(ul: Red;) => "",
+        reset_underline_color_no_parent: style!(ul: Red; deco: Underline;), style!() => "\x1b[59;24m",
+        reset_foreground_default_under_colored_parent: style!(fg: Default;), style!(fg: Red;) => "\x1b[31m",
+        reset_background_default_under_colored_parent: style!(bg: Default;), style!(bg: Blue;) => "\x1b[44m",
         reset_single_decoration_different_from_parent: style!(deco: Bold;), style!(deco: Dim;) => "\x1b[22m",
         reset_multiple_decoration_different_from_parent: style!(deco: Bold, Italic;), style!(deco: Dim, Hide;) => "\x1b[22;23m",
         reset_single_decoration_identical_to_parent: style!(deco: Bold;), style!(deco: Bold;) => "",
@@ -354,5 +743,142 @@ mod tests {
         reset_background_and_multiple_decorations_parent_matches_first_decoration: style!(bg: Blue; deco: Bold, Italic;), style!(deco: Bold;) => "\x1b[49;23m",
         reset_background_and_multiple_decorations_parent_matches_second_decoration: style!(bg: Blue; deco: Bold, Italic;), style!(deco: Italic;) => "\x1b[49;22m",
         reset_background_and_mulitple_decorations_parent_matches_both_decorations: style!(bg: Blue; deco: Bold, Italic;), style!(deco: Bold, Italic;) => "\x1b[49m",
+
+        reset_full_reset_no_parent: style!(reset;), style!() => "\x1b[0m",
+        reset_full_reset_restores_parent: style!(reset;), style!(fg: Red; bg: Blue; deco: Bold, Italic;) => "\x1b[0;31;44;1;3m",
+        reset_full_reset_with_styles_restores_parent: style!(reset; fg: Red;), style!(fg: Red; deco: Bold;) => "\x1b[0;31;1m",
+    }
+
+    #[test]
+    fn current_style_extend_with_reset_drops_parent() {
+        let parent = CurrentStyle::from(style!(fg: Red; bg: Blue; deco: Bold;));
+        let current = parent.extend(&style!(reset; deco: Italic;));
+
+        assert_eq!(current.foreground(), Color::Default);
+        assert_eq!(current.background(), Color::Default);
+        assert_eq!(
+            current.decoration().iter().copied().collect::<Vec<_>>(),
+            vec![Decoration::Italic]
+        );
+    }
+
+    #[test]
+    fn current_style_extend_keeps_ancestor_colors() {
+        let grandparent = CurrentStyle::default().extend(&style!(fg: Red;));
+        let parent = grandparent.extend(&style!(deco: Bold;));
+        assert_eq!(parent.foreground(), Color::Red);
+
+        let mut output = String::new();
+        style!(fg: Default;).apply(&parent, &mut output);
+        assert_eq!(output, "\x1b[39m");
+
+        let current = parent.extend(&style!(fg: Default;));
+        assert_eq!(current.foreground(), Color::Default);
+    }
+
+    #[test]
+    fn diff_from_default() {
+        let current = CurrentStyle::default();
+        assert_eq!(current.diff(&style!()), Vec::<&str>::new());
+        assert_eq!(current.diff(&style!(fg: Red;)), vec!["31"]);
+        assert_eq!(current.diff(&style!(bg: Blue;)), vec!["44"]);
+        assert_eq!(current.diff(&style!(deco: Bold;)), vec!["1"]);
+        assert_eq!(
+            current.diff(&style!(fg: Red; bg: Blue; deco: Bold, Italic;)),
+            vec!["31", "44", "1", "3"]
+        );
+    }
+
+    #[test]
+    fn diff_skips_unchanged_properties() {
+        let current = CurrentStyle::from(style!(fg: Red; bg: Blue; deco: Bold;));
+        assert_eq!(current.diff(&style!(fg: Red;)), Vec::<&str>::new());
+        assert_eq!(current.diff(&style!(bg: Blue;)), Vec::<&str>::new());
+        assert_eq!(current.diff(&style!(deco: Bold;)), Vec::<&str>::new());
+        assert_eq!(
+            current.diff(&style!(fg: Red; bg: Blue; deco: Bold;)),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn diff_with_overlapping_properties() {
+        let current = CurrentStyle::from(style!(fg: Red; bg: Blue; deco: Bold;));
+        assert_eq!(current.diff(&style!(fg: Green;)), vec!["32"]);
+        assert_eq!(current.diff(&style!(bg: Default;)), vec!["49"]);
+        assert_eq!(current.diff(&style!(deco: Bold, Italic;)), vec!["3"]);
+        assert_eq!(
+            current.diff(&style!(fg: Red; bg: Green; deco: Underline;)),
+            vec!["42", "4"]
+        );
+    }
+
+    #[test]
+    fn diff_with_reset() {
+        let current = CurrentStyle::from(style!(fg: Red; deco: Bold;));
+        assert_eq!(current.diff(&style!(reset;)), vec!["0"]);
+        assert_eq!(
+            current.diff(&style!(reset; fg: Red; deco: Bold;)),
+            vec!["0", "31", "1"]
+        );
+    }
+
+    #[test]
+    fn apply_matches_diff() {
+        let current = CurrentStyle::from(style!(fg: Red; deco: Bold;));
+        let child = style!(fg: Blue; bg: Yellow; deco: Bold, Underline;);
+
+        let mut output = String::new();
+        child.apply(&current, &mut output);
+        assert_eq!(output, format!("\x1b[{}m", current.diff(&child).join(";")));
+    }
+
+    #[test]
+    fn extend_with_reset_child_replaces_parent() {
+        let parent = style!(fg: Red; deco: Bold;);
+        assert_eq!(
+            parent.extend(&style!(reset; bg: Blue;)),
+            style!(reset; bg: Blue;)
+        );
+    }
+
+    #[test]
+    fn extend_keeps_parent_reset() {
+        let parent = style!(reset; fg: Red;);
+        assert_eq!(
+            parent.extend(&style!(bg: Blue;)),
+            style!(reset; fg: Red; bg: Blue;)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    mod serde {
+        use super::Style;
+
+        #[test]
+        fn serde_round_trip() {
+            let style = style!(fg: BrightRed; bg: Default; deco: Bold, StrikeThrough;);
+
+            let serialized = serde_json::to_string(&style).unwrap();
+            assert_eq!(
+                serialized,
+                r#"{"foreground":"bright-red","background":"default","decoration":["bold","strike-through"]}"#
+            );
+
+            let deserialized = serde_json::from_str::<Style>(&serialized).unwrap();
+            assert_eq!(deserialized, style);
+        }
+
+        #[test]
+        fn serde_missing_fields_are_unset() {
+            let style = serde_json::from_str::<Style>(r#"{"background":"blue"}"#).unwrap();
+            assert_eq!(style, style!(bg: Blue;));
+            assert_eq!(serde_json::to_string(&style!()).unwrap(), "{}");
+        }
+
+        #[test]
+        fn serde_unknown_color_is_rejected() {
+            assert!(serde_json::from_str::<Style>(r#"{"foreground":"pink"}"#).is_err());
+        }
     }
 }
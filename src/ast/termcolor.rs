@@ -0,0 +1,138 @@
+use super::{Color, Decoration, Style};
+use ::termcolor::ColorSpec;
+
+/// Convert to the equivalent color, if there is one
+///
+/// The bright colors use their index in the 256-color palette, as [`ColorSpec::set_intense`] would
+/// brighten both the foreground and background. The default color has no equivalent, as
+/// `termcolor` leaves the color unset instead.
+impl From<Color> for Option<::termcolor::Color> {
+    fn from(color: Color) -> Self {
+        use ::termcolor::Color::*;
+
+        Some(match color {
+            Color::Black => Black,
+            Color::Red => Red,
+            Color::Green => Green,
+            Color::Yellow => Yellow,
+            Color::Blue => Blue,
+            Color::Magenta => Magenta,
+            Color::Cyan => Cyan,
+            Color::White => White,
+            Color::BrightBlack => Ansi256(8),
+            Color::BrightRed => Ansi256(9),
+            Color::BrightGreen => Ansi256(10),
+            Color::BrightYellow => Ansi256(11),
+            Color::BrightBlue => Ansi256(12),
+            Color::BrightMagenta => Ansi256(13),
+            Color::BrightCyan => Ansi256(14),
+            Color::BrightWhite => Ansi256(15),
+            Color::Default => return None,
+        })
+    }
+}
+
+/// Convert the style to a [`ColorSpec`] for use with a [`WriteColor`](::termcolor::WriteColor)
+///
+/// Only bold, dim, italic, underline, and strike-through have an equivalent setting, so blinking,
+/// inverted, and hidden text are dropped, along with the underline color. The `reset` flag is
+/// dropped as well, as `termcolor` resets every property whenever a specification is applied.
+impl From<&Style> for ColorSpec {
+    fn from(style: &Style) -> Self {
+        let mut spec = ColorSpec::new();
+        spec.set_fg(style.foreground.and_then(Into::into))
+            .set_bg(style.background.and_then(Into::into));
+
+        for decoration in style.decoration.iter().flatten() {
+            match decoration {
+                Decoration::Bold => spec.set_bold(true),
+                Decoration::Dim => spec.set_dimmed(true),
+                Decoration::Italic => spec.set_italic(true),
+                Decoration::Underline => spec.set_underline(true),
+                Decoration::StrikeThrough => spec.set_strikethrough(true),
+                Decoration::FastBlink
+                | Decoration::SlowBlink
+                | Decoration::Invert
+                | Decoration::Hide => continue,
+            };
+        }
+
+        spec
+    }
+}
+
+impl From<Style> for ColorSpec {
+    fn from(style: Style) -> Self {
+        Self::from(&style)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Color, Decoration, Style};
+    use ::termcolor::{Buffer, ColorSpec, WriteColor};
+
+    #[test]
+    fn colors() {
+        let spec = ColorSpec::from(Style::fg(Color::Red).background(Color::Blue));
+        assert_eq!(spec.fg(), Some(&::termcolor::Color::Red));
+        assert_eq!(spec.bg(), Some(&::termcolor::Color::Blue));
+        assert!(!spec.intense());
+    }
+
+    #[test]
+    fn bright_colors() {
+        let spec = ColorSpec::from(Style::fg(Color::BrightRed).background(Color::White));
+        assert_eq!(spec.fg(), Some(&::termcolor::Color::Ansi256(9)));
+        assert_eq!(spec.bg(), Some(&::termcolor::Color::White));
+    }
+
+    #[test]
+    fn default_color_is_unset() {
+        let spec = ColorSpec::from(Style::fg(Color::Default).background(Color::Default));
+        assert!(spec.is_none());
+    }
+
+    #[test]
+    fn decorations() {
+        let spec = ColorSpec::from(
+            Style::deco(Decoration::Bold)
+                .decoration(Decoration::Dim)
+                .decoration(Decoration::Italic)
+                .decoration(Decoration::Underline)
+                .decoration(Decoration::StrikeThrough),
+        );
+        assert!(spec.bold());
+        assert!(spec.dimmed());
+        assert!(spec.italic());
+        assert!(spec.underline());
+        assert!(spec.strikethrough());
+    }
+
+    #[test]
+    fn unsupported_decorations_are_dropped() {
+        let spec = ColorSpec::from(
+            Style::deco(Decoration::SlowBlink)
+                .decoration(Decoration::FastBlink)
+                .decoration(Decoration::Invert)
+                .decoration(Decoration::Hide),
+        );
+        assert!(spec.is_none());
+    }
+
+    #[test]
+    fn writes_matching_codes() {
+        let mut buffer = Buffer::ansi();
+        buffer
+            .set_color(
+                &Style::fg(Color::BrightGreen)
+                    .decoration(Decoration::Bold)
+                    .into(),
+            )
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.into_inner()).unwrap(),
+            "\x1b[0m\x1b[1m\x1b[38;5;10m"
+        );
+    }
+}
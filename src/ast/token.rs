@@ -1,70 +1,171 @@
-use super::Style;
+use super::{CurrentStyle, Style};
+use crate::{error::Error, parser::Parser};
+use std::{borrow::Cow, ops::Deref, slice};
+use text_size::TextRange;
 
 /// One or more pieces of text that either apply additional styling or inherit from the parent
 /// styles.
+///
+/// Text is borrowed from the source whenever possible, and is only allocated when it has to be
+/// merged with other pieces of text. Every token keeps the range of the source it was parsed from.
 #[derive(Clone, Debug)]
 #[cfg_attr(test, derive(Eq, PartialEq))]
-pub enum Token {
-    /// A piece of text that does not modify the styling
-    Content(String),
+pub enum Token<'source> {
+    /// A piece of text that does not modify the styling, along with where it is in the source
+    Content(Cow<'source, str>, TextRange),
+    /// An ANSI escape sequence that was already present in the source, along with where it is in
+    /// the source
+    Raw(&'source str, TextRange),
     /// One or more pieces of text that with additional styling
     Styled {
         /// The pieces of text the style applies to
-        content: Vec<Token>,
+        content: Vec<Token<'source>>,
         /// The style to apply
         style: Style,
+        /// Where the markup is in the source, from the start of the style specifier to the end of
+        /// the content
+        span: TextRange,
     },
 }
 
+impl Token<'_> {
+    /// The range of the source the token was parsed from
+    ///
+    /// Content that was merged from multiple pieces of text, i.e. around escape sequences, covers
+    /// all the pieces.
+    pub fn span(&self) -> TextRange {
+        match self {
+            Token::Content(_, span) | Token::Raw(_, span) | Token::Styled { span, .. } => *span,
+        }
+    }
+}
+
+/// Tokens can be nested arbitrarily deep, so the content of styled tokens is dropped using an
+/// explicit stack rather than recursively
+impl Drop for Token<'_> {
+    fn drop(&mut self) {
+        let Token::Styled { content, .. } = self else {
+            return;
+        };
+
+        let mut stack = std::mem::take(content);
+        while let Some(mut token) = stack.pop() {
+            if let Token::Styled { content, .. } = &mut token {
+                stack.append(content);
+            }
+        }
+    }
+}
+
 /// A sequence of [`Token`]s
 #[derive(Clone, Debug, Default)]
 #[cfg_attr(test, derive(Eq, PartialEq))]
-pub struct Tokens(Vec<Token>);
+pub struct Tokens<'source>(Vec<Token<'source>>);
 
-impl From<Vec<Token>> for Tokens {
-    fn from(tokens: Vec<Token>) -> Self {
+impl<'source> From<Vec<Token<'source>>> for Tokens<'source> {
+    fn from(tokens: Vec<Token<'source>>) -> Self {
         Tokens(tokens)
     }
 }
 
-impl From<Tokens> for Vec<Token> {
-    fn from(tokens: Tokens) -> Self {
+impl<'source> From<Tokens<'source>> for Vec<Token<'source>> {
+    fn from(tokens: Tokens<'source>) -> Self {
         tokens.0
     }
 }
 
-impl Tokens {
+impl<'source> Deref for Tokens<'source> {
+    type Target = [Token<'source>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'source> AsRef<[Token<'source>]> for Tokens<'source> {
+    fn as_ref(&self) -> &[Token<'source>] {
+        &self.0
+    }
+}
+
+impl<'source> Tokens<'source> {
+    /// Parse a piece of text, potentially containing styled markup, into a sequence of tokens
+    pub fn parse(source: &'source str) -> Result<Self, Vec<Error>> {
+        let (tokens, errors) = Parser::new(source).parse();
+        if errors.is_empty() {
+            Ok(Tokens(tokens))
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parse a piece of styled markup and add its tokens to the end of the sequence
+    ///
+    /// Unstyled text at the start of the markup is merged into unstyled text at the end of the
+    /// sequence, the same as [`Tokens::extend`]. The ranges of the new tokens refer to `source`
+    /// rather than any source the existing tokens were parsed from. Nothing is added if the markup
+    /// is invalid.
+    pub fn extend_from_markup(&mut self, source: &'source str) -> Result<(), Vec<Error>> {
+        let tokens = Tokens::parse(source)?;
+        self.extend(tokens);
+        Ok(())
+    }
+
+    /// Iterate over each piece of text along with the style it is displayed with
+    ///
+    /// The styles of all the enclosing tokens are resolved the same way as
+    /// [`CurrentStyle::extend`], so the text can be drawn without knowing the structure of the
+    /// tokens. Empty text and ANSI escape sequences that were already present in the source are
+    /// skipped.
+    pub fn iter(&self) -> Segments<'_, 'source> {
+        Segments {
+            stack: vec![(self.0.iter(), CurrentStyle::default())],
+        }
+    }
+
     /// Add a new token to the end of the sequence
-    pub fn push(&mut self, token: Token) {
+    pub fn push(&mut self, token: Token<'source>) {
         self.0.push(token)
     }
 
-    /// Add a string to the end of the sequence
+    /// Add a string from the given range of the source to the end of the sequence
     ///
     /// If the last token in the sequence is an unstyled piece of text, it will be appended directly
-    /// to the token. Otherwise, a new content token will be created.
-    pub fn push_str(&mut self, s: &str) {
+    /// to the token and its range extended to cover the string. Otherwise, a new content token will
+    /// be created that borrows the string.
+    pub fn push_str(&mut self, s: &'source str, span: TextRange) {
         match self.0.last_mut() {
-            Some(Token::Content(content)) => content.push_str(s),
-            Some(Token::Styled { .. }) | None => self.0.push(Token::Content(s.to_string())),
+            Some(Token::Content(content, existing)) => {
+                content.to_mut().push_str(s);
+                *existing = existing.cover(span);
+            }
+            Some(Token::Raw(..) | Token::Styled { .. }) | None => {
+                self.0.push(Token::Content(Cow::Borrowed(s), span))
+            }
         }
     }
 
-    /// Add a character to the end of the sequence
+    /// Add a character from the given range of the source to the end of the sequence
     ///
     /// If the last token in the sequence is an unstyled piece of text, it will be appended directly
-    /// to the token. Otherwise, a new content token will be created.
-    pub fn push_char(&mut self, ch: char) {
+    /// to the token and its range extended to cover the character. Otherwise, a new content token
+    /// will be created.
+    pub fn push_char(&mut self, ch: char, span: TextRange) {
         match self.0.last_mut() {
-            Some(Token::Content(content)) => content.push(ch),
-            Some(Token::Styled { .. }) | None => self.0.push(Token::Content(ch.to_string())),
+            Some(Token::Content(content, existing)) => {
+                content.to_mut().push(ch);
+                *existing = existing.cover(span);
+            }
+            Some(Token::Raw(..) | Token::Styled { .. }) | None => self
+                .0
+                .push(Token::Content(Cow::Owned(ch.to_string()), span)),
         }
     }
 
     /// Add all the tokens from another sequence onto the current one
     pub fn extend<T>(&mut self, other: T)
     where
-        T: Into<Tokens>,
+        T: Into<Tokens<'source>>,
     {
         let other = other.into();
         if other.0.is_empty() {
@@ -75,42 +176,114 @@ impl Tokens {
         }
 
         let mut tokens = other.0.into_iter().peekable();
-        if let Some(Token::Content(last)) = self.0.last_mut() {
-            while let Some(Token::Content(content)) = tokens.peek() {
-                last.push_str(content);
+        if let Some(Token::Content(last, last_span)) = self.0.last_mut() {
+            while let Some(Token::Content(content, span)) = tokens.peek() {
+                last.to_mut().push_str(content);
+                *last_span = last_span.cover(*span);
                 tokens.next();
             }
         }
 
         self.0.extend(tokens);
     }
+
+    /// Merge styled tokens whose only child is another styled token into a single token
+    ///
+    /// The styles are combined with the child taking precedence, the same as
+    /// [`CurrentStyle::extend`](super::CurrentStyle::extend). Styled tokens with any other content
+    /// are left as-is. This reduces the number of escape codes needed for deeply nested markup.
+    pub fn normalize(&mut self) {
+        normalize(&mut self.0);
+    }
+}
+
+/// An iterator over the resolved pieces of text in a sequence of tokens
+///
+/// Created by [`Tokens::iter`].
+pub struct Segments<'tokens, 'source> {
+    stack: Vec<(slice::Iter<'tokens, Token<'source>>, CurrentStyle)>,
+}
+
+impl<'tokens> Iterator for Segments<'tokens, '_> {
+    type Item = (&'tokens str, CurrentStyle);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (tokens, current) = self.stack.last_mut()?;
+            match tokens.next() {
+                Some(Token::Content(content, _)) if !content.is_empty() => {
+                    return Some((content, current.clone()));
+                }
+                Some(Token::Content(..) | Token::Raw(..)) => {}
+                Some(Token::Styled { content, style, .. }) => {
+                    let style = current.extend(style);
+                    self.stack.push((content.iter(), style));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Merge styled tokens whose only child is another styled token, throughout the whole tree
+///
+/// The tree is walked with an explicit stack so arbitrarily deep nesting can't overflow the call
+/// stack. Combining the styles from the outside in gives the same result as from the inside out.
+fn normalize(tokens: &mut [Token]) {
+    let mut stack = vec![tokens];
+    while let Some(tokens) = stack.pop() {
+        for token in tokens {
+            let Token::Styled { content, style, .. } = token else {
+                continue;
+            };
+
+            while let [Token::Styled {
+                content: child_content,
+                style: child_style,
+                ..
+            }] = content.as_mut_slice()
+            {
+                let merged = style.extend(child_style);
+                let grandchildren = std::mem::take(child_content);
+
+                *style = merged;
+                *content = grandchildren;
+            }
+
+            stack.push(content.as_mut_slice());
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{Token, Tokens};
+    use crate::ast::{Color, Decoration};
+    use std::borrow::Cow;
 
     #[test]
     fn push_adds_token_to_end_when_no_tokens() {
         let mut tokens = Tokens::default();
-        tokens.push(Token::Content(String::from("test")));
+        tokens.push(Token::Content("test".into(), span!(0..0)));
 
         assert_eq!(
             tokens,
-            Tokens::from(vec![Token::Content(String::from("test"))])
+            Tokens::from(vec![Token::Content("test".into(), span!(0..0))])
         )
     }
 
     #[test]
     fn push_adds_token_to_end_when_tokens_present() {
-        let mut tokens = Tokens::from(vec![Token::Content(String::from("existing"))]);
-        tokens.push(Token::Content(String::from("test")));
+        let mut tokens = Tokens::from(vec![Token::Content("existing".into(), span!(0..0))]);
+        tokens.push(Token::Content("test".into(), span!(0..0)));
 
         assert_eq!(
             tokens,
             Tokens::from(vec![
-                Token::Content(String::from("existing")),
-                Token::Content(String::from("test"))
+                Token::Content("existing".into(), span!(0..0)),
+                Token::Content("test".into(), span!(0..0))
             ])
         )
     }
@@ -118,11 +291,11 @@ mod tests {
     #[test]
     fn push_str_adds_new_content_token_to_end_when_no_tokens() {
         let mut tokens = Tokens::default();
-        tokens.push_str("test");
+        tokens.push_str("test", span!(0..4));
 
         assert_eq!(
             tokens,
-            Tokens::from(vec![Token::Content(String::from("test"))])
+            Tokens::from(vec![Token::Content("test".into(), span!(0..4))])
         )
     }
 
@@ -131,17 +304,19 @@ mod tests {
         let mut tokens = Tokens::from(vec![Token::Styled {
             content: vec![],
             style: style!(),
+            span: span!(0..0),
         }]);
-        tokens.push_str("test");
+        tokens.push_str("test", span!(4..8));
 
         assert_eq!(
             tokens,
             Tokens::from(vec![
                 Token::Styled {
                     content: vec![],
-                    style: style!()
+                    style: style!(),
+                    span: span!(0..0),
                 },
-                Token::Content(String::from("test"))
+                Token::Content("test".into(), span!(4..8))
             ])
         )
     }
@@ -149,42 +324,67 @@ mod tests {
     #[test]
     fn push_str_adds_new_content_token_to_end_when_style_token_with_children_exists() {
         let mut tokens = Tokens::from(vec![Token::Styled {
-            content: vec![Token::Content(String::from("existing"))],
+            content: vec![Token::Content("existing".into(), span!(0..0))],
             style: style!(),
+            span: span!(0..0),
         }]);
-        tokens.push_str("test");
+        tokens.push_str("test", span!(4..8));
 
         assert_eq!(
             tokens,
             Tokens::from(vec![
                 Token::Styled {
-                    content: vec![Token::Content(String::from("existing"))],
+                    content: vec![Token::Content("existing".into(), span!(0..0))],
                     style: style!(),
+                    span: span!(0..0),
                 },
-                Token::Content(String::from("test"))
+                Token::Content("test".into(), span!(4..8))
             ])
         );
     }
 
     #[test]
     fn push_str_appends_to_last_non_nested_content_token() {
-        let mut tokens = Tokens::from(vec![Token::Content(String::from("existing "))]);
-        tokens.push_str("test");
+        let mut tokens = Tokens::from(vec![Token::Content("existing ".into(), span!(0..9))]);
+        tokens.push_str("test", span!(9..13));
 
         assert_eq!(
             tokens,
-            Tokens::from(vec![Token::Content(String::from("existing test"))])
+            Tokens::from(vec![Token::Content("existing test".into(), span!(0..13))])
         );
     }
 
+    #[test]
+    fn push_str_borrows_new_content() {
+        let mut tokens = Tokens::default();
+        tokens.push_str("test", span!(0..4));
+
+        assert!(matches!(
+            Vec::from(tokens).as_slice(),
+            [Token::Content(Cow::Borrowed("test"), _)]
+        ));
+    }
+
+    #[test]
+    fn push_str_allocates_when_merging() {
+        let mut tokens = Tokens::default();
+        tokens.push_str("existing ", span!(0..9));
+        tokens.push_str("test", span!(9..13));
+
+        assert!(matches!(
+            Vec::from(tokens).as_slice(),
+            [Token::Content(Cow::Owned(content), span)] if content == "existing test" && *span == span!(0..13)
+        ));
+    }
+
     #[test]
     fn push_char_adds_new_content_token_to_end_when_no_tokens() {
         let mut tokens = Tokens::default();
-        tokens.push_char('T');
+        tokens.push_char('T', span!(0..1));
 
         assert_eq!(
             tokens,
-            Tokens::from(vec![Token::Content(String::from("T"))])
+            Tokens::from(vec![Token::Content("T".into(), span!(0..1))])
         )
     }
 
@@ -193,17 +393,19 @@ mod tests {
         let mut tokens = Tokens::from(vec![Token::Styled {
             content: vec![],
             style: style!(),
+            span: span!(0..0),
         }]);
-        tokens.push_char('T');
+        tokens.push_char('T', span!(4..5));
 
         assert_eq!(
             tokens,
             Tokens::from(vec![
                 Token::Styled {
                     content: vec![],
-                    style: style!()
+                    style: style!(),
+                    span: span!(0..0),
                 },
-                Token::Content(String::from("T"))
+                Token::Content("T".into(), span!(4..5))
             ])
         )
     }
@@ -211,31 +413,33 @@ mod tests {
     #[test]
     fn push_char_adds_new_content_token_to_end_when_style_token_with_children_exists() {
         let mut tokens = Tokens::from(vec![Token::Styled {
-            content: vec![Token::Content(String::from("existing"))],
+            content: vec![Token::Content("existing".into(), span!(0..0))],
             style: style!(),
+            span: span!(0..0),
         }]);
-        tokens.push_char('T');
+        tokens.push_char('T', span!(4..5));
 
         assert_eq!(
             tokens,
             Tokens::from(vec![
                 Token::Styled {
-                    content: vec![Token::Content(String::from("existing"))],
+                    content: vec![Token::Content("existing".into(), span!(0..0))],
                     style: style!(),
+                    span: span!(0..0),
                 },
-                Token::Content(String::from("T"))
+                Token::Content("T".into(), span!(4..5))
             ])
         );
     }
 
     #[test]
     fn push_char_appends_to_last_non_nested_content_token() {
-        let mut tokens = Tokens::from(vec![Token::Content(String::from("existing "))]);
-        tokens.push_char('T');
+        let mut tokens = Tokens::from(vec![Token::Content("existing ".into(), span!(0..9))]);
+        tokens.push_char('T', span!(9..10));
 
         assert_eq!(
             tokens,
-            Tokens::from(vec![Token::Content(String::from("existing T"))])
+            Tokens::from(vec![Token::Content("existing T".into(), span!(0..10))])
         );
     }
 
@@ -249,10 +453,10 @@ mod tests {
     #[test]
     fn extend_empty_with_content_token() {
         let mut tokens = Tokens::default();
-        tokens.extend(vec![Token::Content(String::from("testing"))]);
+        tokens.extend(vec![Token::Content("testing".into(), span!(0..0))]);
         assert_eq!(
             tokens,
-            Tokens::from(vec![Token::Content(String::from("testing"))])
+            Tokens::from(vec![Token::Content("testing".into(), span!(0..0))])
         );
     }
 
@@ -261,51 +465,55 @@ mod tests {
         let mut tokens = Tokens::default();
         tokens.extend(vec![Token::Styled {
             style: style!(),
-            content: vec![Token::Content(String::from("testing"))],
+            content: vec![Token::Content("testing".into(), span!(0..0))],
+            span: span!(0..0),
         }]);
         assert_eq!(
             tokens,
             Tokens::from(vec![Token::Styled {
                 style: style!(),
-                content: vec![Token::Content(String::from("testing"))]
+                content: vec![Token::Content("testing".into(), span!(0..0))],
+                span: span!(0..0),
             }])
         );
     }
 
     #[test]
     fn extend_single_content_token_with_empty() {
-        let mut tokens = Tokens::from(vec![Token::Content(String::from("existing"))]);
+        let mut tokens = Tokens::from(vec![Token::Content("existing".into(), span!(0..0))]);
         tokens.extend(vec![]);
         assert_eq!(
             tokens,
-            Tokens::from(vec![Token::Content(String::from("existing"))])
+            Tokens::from(vec![Token::Content("existing".into(), span!(0..0))])
         );
     }
 
     #[test]
     fn extend_single_content_token_with_content_token() {
-        let mut tokens = Tokens::from(vec![Token::Content(String::from("existing"))]);
-        tokens.extend(vec![Token::Content(String::from("testing"))]);
+        let mut tokens = Tokens::from(vec![Token::Content("existing".into(), span!(0..8))]);
+        tokens.extend(vec![Token::Content("testing".into(), span!(8..15))]);
         assert_eq!(
             tokens,
-            Tokens::from(vec![Token::Content(String::from("existingtesting"))])
+            Tokens::from(vec![Token::Content("existingtesting".into(), span!(0..15))])
         );
     }
 
     #[test]
     fn extend_single_content_token_with_styled_token() {
-        let mut tokens = Tokens::from(vec![Token::Content(String::from("existing"))]);
+        let mut tokens = Tokens::from(vec![Token::Content("existing".into(), span!(0..0))]);
         tokens.extend(vec![Token::Styled {
             style: style!(),
-            content: vec![Token::Content(String::from("testing"))],
+            content: vec![Token::Content("testing".into(), span!(0..0))],
+            span: span!(0..0),
         }]);
         assert_eq!(
             tokens,
             Tokens::from(vec![
-                Token::Content(String::from("existing")),
+                Token::Content("existing".into(), span!(0..0)),
                 Token::Styled {
                     style: style!(),
-                    content: vec![Token::Content(String::from("testing"))],
+                    content: vec![Token::Content("testing".into(), span!(0..0))],
+                    span: span!(0..0),
                 }
             ])
         );
@@ -315,14 +523,16 @@ mod tests {
     fn extend_single_styled_token_with_empty() {
         let mut tokens = Tokens::from(vec![Token::Styled {
             style: style!(),
-            content: vec![Token::Content(String::from("testing"))],
+            content: vec![Token::Content("testing".into(), span!(0..0))],
+            span: span!(0..0),
         }]);
         tokens.extend(vec![]);
         assert_eq!(
             tokens,
             Tokens::from(vec![Token::Styled {
                 style: style!(),
-                content: vec![Token::Content(String::from("testing"))],
+                content: vec![Token::Content("testing".into(), span!(0..0))],
+                span: span!(0..0),
             }])
         );
     }
@@ -331,17 +541,19 @@ mod tests {
     fn extend_single_styled_token_with_content_token() {
         let mut tokens = Tokens::from(vec![Token::Styled {
             style: style!(),
-            content: vec![Token::Content(String::from("testing"))],
+            content: vec![Token::Content("testing".into(), span!(0..0))],
+            span: span!(0..0),
         }]);
-        tokens.extend(vec![Token::Content(String::from("testing"))]);
+        tokens.extend(vec![Token::Content("testing".into(), span!(0..0))]);
         assert_eq!(
             tokens,
             Tokens::from(vec![
                 Token::Styled {
                     style: style!(),
-                    content: vec![Token::Content(String::from("testing"))],
+                    content: vec![Token::Content("testing".into(), span!(0..0))],
+                    span: span!(0..0),
                 },
-                Token::Content(String::from("testing"))
+                Token::Content("testing".into(), span!(0..0))
             ])
         );
     }
@@ -350,22 +562,26 @@ mod tests {
     fn extend_single_styled_token_with_styled_token() {
         let mut tokens = Tokens::from(vec![Token::Styled {
             style: style!(),
-            content: vec![Token::Content(String::from("testing"))],
+            content: vec![Token::Content("testing".into(), span!(0..0))],
+            span: span!(0..0),
         }]);
         tokens.extend(vec![Token::Styled {
             style: style!(),
-            content: vec![Token::Content(String::from("testing"))],
+            content: vec![Token::Content("testing".into(), span!(0..0))],
+            span: span!(0..0),
         }]);
         assert_eq!(
             tokens,
             Tokens::from(vec![
                 Token::Styled {
                     style: style!(),
-                    content: vec![Token::Content(String::from("testing"))],
+                    content: vec![Token::Content("testing".into(), span!(0..0))],
+                    span: span!(0..0),
                 },
                 Token::Styled {
                     style: style!(),
-                    content: vec![Token::Content(String::from("testing"))],
+                    content: vec![Token::Content("testing".into(), span!(0..0))],
+                    span: span!(0..0),
                 }
             ])
         );
@@ -376,25 +592,29 @@ mod tests {
         let mut tokens = Tokens::from(vec![
             Token::Styled {
                 style: style!(),
-                content: vec![Token::Content(String::from("existing styled"))],
+                content: vec![Token::Content("existing styled".into(), span!(0..0))],
+                span: span!(0..0),
             },
-            Token::Content(String::from("existing content")),
+            Token::Content("existing content".into(), span!(0..0)),
         ]);
         tokens.extend(vec![Token::Styled {
             style: style!(),
-            content: vec![Token::Content(String::from("testing"))],
+            content: vec![Token::Content("testing".into(), span!(0..0))],
+            span: span!(0..0),
         }]);
         assert_eq!(
             tokens,
             Tokens::from(vec![
                 Token::Styled {
                     style: style!(),
-                    content: vec![Token::Content(String::from("existing styled"))],
+                    content: vec![Token::Content("existing styled".into(), span!(0..0))],
+                    span: span!(0..0),
                 },
-                Token::Content(String::from("existing content")),
+                Token::Content("existing content".into(), span!(0..0)),
                 Token::Styled {
                     style: style!(),
-                    content: vec![Token::Content(String::from("testing"))],
+                    content: vec![Token::Content("testing".into(), span!(0..0))],
+                    span: span!(0..0),
                 }
             ])
         );
@@ -403,27 +623,31 @@ mod tests {
     #[test]
     fn extend_content_and_styled_token_with_styled_token() {
         let mut tokens = Tokens::from(vec![
-            Token::Content(String::from("existing content")),
+            Token::Content("existing content".into(), span!(0..0)),
             Token::Styled {
                 style: style!(),
-                content: vec![Token::Content(String::from("existing styled"))],
+                content: vec![Token::Content("existing styled".into(), span!(0..0))],
+                span: span!(0..0),
             },
         ]);
         tokens.extend(vec![Token::Styled {
             style: style!(),
-            content: vec![Token::Content(String::from("testing"))],
+            content: vec![Token::Content("testing".into(), span!(0..0))],
+            span: span!(0..0),
         }]);
         assert_eq!(
             tokens,
             Tokens::from(vec![
-                Token::Content(String::from("existing content")),
+                Token::Content("existing content".into(), span!(0..0)),
                 Token::Styled {
                     style: style!(),
-                    content: vec![Token::Content(String::from("existing styled"))],
+                    content: vec![Token::Content("existing styled".into(), span!(0..0))],
+                    span: span!(0..0),
                 },
                 Token::Styled {
                     style: style!(),
-                    content: vec![Token::Content(String::from("testing"))],
+                    content: vec![Token::Content("testing".into(), span!(0..0))],
+                    span: span!(0..0),
                 }
             ])
         );
@@ -434,19 +658,21 @@ mod tests {
         let mut tokens = Tokens::from(vec![
             Token::Styled {
                 style: style!(),
-                content: vec![Token::Content(String::from("existing styled"))],
+                content: vec![Token::Content("existing styled".into(), span!(0..0))],
+                span: span!(0..0),
             },
-            Token::Content(String::from("existing content")),
+            Token::Content("existing content".into(), span!(4..20)),
         ]);
-        tokens.extend(vec![Token::Content(String::from("testing"))]);
+        tokens.extend(vec![Token::Content("testing".into(), span!(20..27))]);
         assert_eq!(
             tokens,
             Tokens::from(vec![
                 Token::Styled {
                     style: style!(),
-                    content: vec![Token::Content(String::from("existing styled"))],
+                    content: vec![Token::Content("existing styled".into(), span!(0..0))],
+                    span: span!(0..0),
                 },
-                Token::Content(String::from("existing contenttesting")),
+                Token::Content("existing contenttesting".into(), span!(4..27)),
             ])
         );
     }
@@ -454,23 +680,291 @@ mod tests {
     #[test]
     fn extend_content_and_styled_token_with_content_token() {
         let mut tokens = Tokens::from(vec![
-            Token::Content(String::from("existing content")),
+            Token::Content("existing content".into(), span!(0..0)),
             Token::Styled {
                 style: style!(),
-                content: vec![Token::Content(String::from("existing styled"))],
+                content: vec![Token::Content("existing styled".into(), span!(0..0))],
+                span: span!(0..0),
             },
         ]);
-        tokens.extend(vec![Token::Content(String::from("testing"))]);
+        tokens.extend(vec![Token::Content("testing".into(), span!(0..0))]);
         assert_eq!(
             tokens,
             Tokens::from(vec![
-                Token::Content(String::from("existing content")),
+                Token::Content("existing content".into(), span!(0..0)),
                 Token::Styled {
                     style: style!(),
-                    content: vec![Token::Content(String::from("existing styled"))],
+                    content: vec![Token::Content("existing styled".into(), span!(0..0))],
+                    span: span!(0..0),
+                },
+                Token::Content("testing".into(), span!(0..0))
+            ])
+        );
+    }
+
+    #[test]
+    fn normalize_merges_single_styled_child() {
+        let mut tokens = Tokens::from(vec![Token::Styled {
+            style: style!(fg: Red;),
+            content: vec![Token::Styled {
+                style: style!(deco: Bold;),
+                content: vec![Token::Content("inner".into(), span!(0..0))],
+                span: span!(0..0),
+            }],
+            span: span!(0..0),
+        }]);
+        tokens.normalize();
+
+        assert_eq!(
+            tokens,
+            Tokens::from(vec![Token::Styled {
+                style: style!(fg: Red; deco: Bold;),
+                content: vec![Token::Content("inner".into(), span!(0..0))],
+                span: span!(0..0),
+            }])
+        );
+    }
+
+    #[test]
+    fn normalize_child_takes_precedence() {
+        let mut tokens = Tokens::from(vec![Token::Styled {
+            style: style!(fg: Red; bg: Blue; deco: Bold;),
+            content: vec![Token::Styled {
+                style: style!(fg: Green; deco: Italic;),
+                content: vec![Token::Content("inner".into(), span!(0..0))],
+                span: span!(0..0),
+            }],
+            span: span!(0..0),
+        }]);
+        tokens.normalize();
+
+        assert_eq!(
+            tokens,
+            Tokens::from(vec![Token::Styled {
+                style: style!(fg: Green; bg: Blue; deco: Bold, Italic;),
+                content: vec![Token::Content("inner".into(), span!(0..0))],
+                span: span!(0..0),
+            }])
+        );
+    }
+
+    #[test]
+    fn normalize_merges_deeply_nested_children() {
+        let mut tokens = Tokens::from(vec![Token::Styled {
+            style: style!(fg: Red;),
+            content: vec![Token::Styled {
+                style: style!(bg: Blue;),
+                content: vec![Token::Styled {
+                    style: style!(deco: Dim;),
+                    content: vec![Token::Content("inner".into(), span!(0..0))],
+                    span: span!(0..0),
+                }],
+                span: span!(0..0),
+            }],
+            span: span!(0..0),
+        }]);
+        tokens.normalize();
+
+        assert_eq!(
+            tokens,
+            Tokens::from(vec![Token::Styled {
+                style: style!(fg: Red; bg: Blue; deco: Dim;),
+                content: vec![Token::Content("inner".into(), span!(0..0))],
+                span: span!(0..0),
+            }])
+        );
+    }
+
+    #[test]
+    fn normalize_siblings_block_merge() {
+        let original = Tokens::from(vec![Token::Styled {
+            style: style!(fg: Red;),
+            content: vec![
+                Token::Styled {
+                    style: style!(deco: Bold;),
+                    content: vec![Token::Content("inner".into(), span!(0..0))],
+                    span: span!(0..0),
+                },
+                Token::Content(" sibling".into(), span!(0..0)),
+            ],
+            span: span!(0..0),
+        }]);
+        let mut tokens = original.clone();
+        tokens.normalize();
+
+        assert_eq!(tokens, original);
+    }
+
+    #[test]
+    fn normalize_merges_inside_siblings() {
+        let mut tokens = Tokens::from(vec![
+            Token::Content("leading ".into(), span!(0..0)),
+            Token::Styled {
+                style: style!(fg: Red;),
+                content: vec![
+                    Token::Content("one ".into(), span!(0..0)),
+                    Token::Styled {
+                        style: style!(bg: Blue;),
+                        content: vec![Token::Styled {
+                            style: style!(deco: Dim;),
+                            content: vec![Token::Content("two".into(), span!(0..0))],
+                            span: span!(0..0),
+                        }],
+                        span: span!(0..0),
+                    },
+                ],
+                span: span!(0..0),
+            },
+        ]);
+        tokens.normalize();
+
+        assert_eq!(
+            tokens,
+            Tokens::from(vec![
+                Token::Content("leading ".into(), span!(0..0)),
+                Token::Styled {
+                    style: style!(fg: Red;),
+                    content: vec![
+                        Token::Content("one ".into(), span!(0..0)),
+                        Token::Styled {
+                            style: style!(bg: Blue; deco: Dim;),
+                            content: vec![Token::Content("two".into(), span!(0..0))],
+                            span: span!(0..0),
+                        },
+                    ],
+                    span: span!(0..0),
                 },
-                Token::Content(String::from("testing"))
             ])
         );
     }
+
+    fn segments(source: &str) -> Vec<(String, Color, Color, Vec<Decoration>)> {
+        let tokens = Tokens::parse(source).unwrap();
+        tokens
+            .iter()
+            .map(|(text, style)| {
+                (
+                    text.to_owned(),
+                    style.foreground(),
+                    style.background(),
+                    style.decoration().iter().copied().collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn iter_empty() {
+        assert_eq!(segments(""), vec![]);
+    }
+
+    #[test]
+    fn iter_unstyled() {
+        assert_eq!(
+            segments("plain text"),
+            vec![("plain text".into(), Color::Default, Color::Default, vec![])]
+        );
+    }
+
+    #[test]
+    fn iter_skips_empty_content_and_escape_sequences() {
+        assert_eq!(
+            segments("[fg:red]()\x1b[1m[deco:bold](a)"),
+            vec![(
+                "a".into(),
+                Color::Default,
+                Color::Default,
+                vec![Decoration::Bold]
+            )]
+        );
+    }
+
+    #[test]
+    fn iter_resolves_nested_styles() {
+        assert_eq!(
+            segments(
+                "leading [fg:red](one [bg:blue](two [deco:dim](three) [fg:default](four)) one) \
+                 trailing"
+            ),
+            vec![
+                ("leading ".into(), Color::Default, Color::Default, vec![]),
+                ("one ".into(), Color::Red, Color::Default, vec![]),
+                ("two ".into(), Color::Red, Color::Blue, vec![]),
+                (
+                    "three".into(),
+                    Color::Red,
+                    Color::Blue,
+                    vec![Decoration::Dim]
+                ),
+                (" ".into(), Color::Red, Color::Blue, vec![]),
+                ("four".into(), Color::Default, Color::Blue, vec![]),
+                (" one".into(), Color::Red, Color::Default, vec![]),
+                (" trailing".into(), Color::Default, Color::Default, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_keeps_spans() {
+        let source = "a\\[ [fg:red](b [deco:bold](c)) \x1b[1md";
+        let tokens = Vec::from(Tokens::parse(source).unwrap());
+
+        let spans = tokens
+            .iter()
+            .map(|token| &source[token.span()])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            vec!["a\\[ ", "[fg:red](b [deco:bold](c))", " ", "\x1b[1m", "d"]
+        );
+
+        let Token::Styled { content, .. } = &tokens[1] else {
+            panic!("expected a styled token");
+        };
+        let spans = content
+            .iter()
+            .map(|token| &source[token.span()])
+            .collect::<Vec<_>>();
+        assert_eq!(spans, vec!["b ", "[deco:bold](c)"]);
+    }
+
+    #[test]
+    fn parse_reports_errors() {
+        assert!(Tokens::parse("[fg:red](unclosed").is_err());
+    }
+
+    #[test]
+    fn extend_from_markup_merges_content_across_fragments() {
+        let mut tokens = Tokens::parse("hello ").unwrap();
+        tokens.extend_from_markup("world [fg:red](!)").unwrap();
+        tokens.extend_from_markup(" and").unwrap();
+        tokens.extend_from_markup(" more").unwrap();
+
+        assert_eq!(
+            tokens,
+            Tokens::from(vec![
+                Token::Content("hello world ".into(), span!(0..6)),
+                Token::Styled {
+                    content: vec![Token::Content("!".into(), span!(15..16))],
+                    style: style!(fg: Red;),
+                    span: span!(6..17),
+                },
+                Token::Content(" and more".into(), span!(0..5)),
+            ])
+        );
+    }
+
+    #[test]
+    fn extend_from_markup_into_empty_tokens() {
+        let mut tokens = Tokens::default();
+        tokens.extend_from_markup("[deco:bold](a)b").unwrap();
+        assert_eq!(tokens, Tokens::parse("[deco:bold](a)b").unwrap());
+    }
+
+    #[test]
+    fn extend_from_markup_reports_errors_without_changing_tokens() {
+        let mut tokens = Tokens::parse("existing").unwrap();
+        assert!(tokens.extend_from_markup("[fg:red](unclosed").is_err());
+        assert_eq!(tokens, Tokens::parse("existing").unwrap());
+    }
 }
@@ -0,0 +1,287 @@
+use crate::ast::Decoration;
+use indexmap::IndexSet;
+use std::{
+    ffi::OsStr,
+    io::{self, IsTerminal},
+};
+
+/// When escape codes should be emitted
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ColorChoice {
+    /// Emit escape codes based on the environment
+    ///
+    /// The environment is checked in the following order, with the first match winning:
+    ///
+    /// 1. `CLICOLOR_FORCE` is set to anything other than `0`: escape codes are emitted
+    /// 2. `NO_COLOR` is set to a non-empty value: escape codes are not emitted
+    /// 3. `CLICOLOR` is set to `0`: escape codes are not emitted
+    /// 4. escape codes are emitted only if stdout is a terminal
+    #[default]
+    Auto,
+    /// Always emit escape codes
+    Always,
+    /// Never emit escape codes
+    Never,
+}
+
+impl ColorChoice {
+    /// Determine whether escape codes should be emitted
+    pub fn should_colorize(&self) -> bool {
+        self.resolve(&Environment::detect())
+    }
+
+    /// Determine whether escape codes should be emitted given the state of the environment
+    pub(crate) fn resolve(&self, environment: &Environment) -> bool {
+        match self {
+            ColorChoice::Auto => environment.resolve(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// The state of the environment that is used to automatically decide whether to emit escape codes
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Environment {
+    /// Whether stdout is connected to a terminal
+    pub(crate) is_terminal: bool,
+    /// Whether the `NO_COLOR` environment variable is set
+    pub(crate) no_color: bool,
+    /// Whether the `CLICOLOR` environment variable disables color
+    pub(crate) clicolor_disabled: bool,
+    /// Whether the `CLICOLOR_FORCE` environment variable forces color
+    pub(crate) clicolor_force: bool,
+}
+
+impl Environment {
+    /// Detect the state of the current process
+    pub(crate) fn detect() -> Self {
+        Self {
+            is_terminal: io::stdout().is_terminal(),
+            no_color: no_color_requested(),
+            clicolor_disabled: std::env::var_os("CLICOLOR").is_some_and(|value| value == "0"),
+            clicolor_force: std::env::var_os("CLICOLOR_FORCE")
+                .is_some_and(|value| is_enabled(&value)),
+        }
+    }
+
+    /// Decide whether escape codes should be emitted
+    ///
+    /// See [`ColorChoice::Auto`] for the order of precedence.
+    fn resolve(&self) -> bool {
+        if self.clicolor_force {
+            true
+        } else if self.no_color || self.clicolor_disabled {
+            false
+        } else {
+            self.is_terminal
+        }
+    }
+}
+
+/// Whether a flag-like environment variable is turned on
+fn is_enabled(value: &OsStr) -> bool {
+    !value.is_empty() && value != "0"
+}
+
+/// Check whether the user requested that no color be output using the `NO_COLOR` environment
+/// variable
+///
+/// See <https://no-color.org> for details.
+fn no_color_requested() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// The decorations supported by terminals that are known to support every common decoration
+const FULL_SUPPORT: &[Decoration] = &[
+    Decoration::Bold,
+    Decoration::Dim,
+    Decoration::Italic,
+    Decoration::Underline,
+    Decoration::SlowBlink,
+    Decoration::Invert,
+    Decoration::Hide,
+    Decoration::StrikeThrough,
+];
+
+/// The decorations supported by terminals that are known to lack italics and strike-through
+const BASIC_SUPPORT: &[Decoration] = &[
+    Decoration::Bold,
+    Decoration::Dim,
+    Decoration::Underline,
+    Decoration::SlowBlink,
+    Decoration::Invert,
+];
+
+/// The decorations assumed to be supported by terminals that aren't known
+const FALLBACK_SUPPORT: &[Decoration] =
+    &[Decoration::Bold, Decoration::Underline, Decoration::Invert];
+
+/// Every known terminal along with the decorations it supports
+const TERMINALS: &[(&str, &[Decoration])] = &[
+    ("xterm", FULL_SUPPORT),
+    ("tmux", FULL_SUPPORT),
+    ("screen", BASIC_SUPPORT),
+    ("linux", BASIC_SUPPORT),
+    (
+        "vt100",
+        &[
+            Decoration::Bold,
+            Decoration::Underline,
+            Decoration::SlowBlink,
+            Decoration::Invert,
+        ],
+    ),
+    ("dumb", &[]),
+];
+
+/// Get the decorations a terminal is known to support, from its name in the `TERM` environment
+/// variable
+///
+/// This is a conservative, static table rather than a terminfo lookup. Variants of a terminal are
+/// matched by the part of the name before the first `-` (i.e. `xterm-256color` is treated as
+/// `xterm`). No terminal is assumed to support fast blinking, as most either ignore it or blink
+/// at the same speed. Terminals that aren't known are assumed to only support bold, underlined,
+/// and inverted text.
+pub fn supported_decorations(term: &str) -> IndexSet<Decoration> {
+    let base = term.split('-').next().unwrap_or(term);
+    let supported = TERMINALS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(base))
+        .map_or(FALLBACK_SUPPORT, |(_, supported)| supported);
+
+    supported.iter().copied().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_enabled, supported_decorations, ColorChoice, Environment};
+    use crate::ast::Decoration;
+    use std::ffi::OsStr;
+
+    /// Every possible state of the environment
+    fn environments() -> impl Iterator<Item = Environment> {
+        (0..16u8).map(|bits| Environment {
+            is_terminal: bits & 1 != 0,
+            no_color: bits & 2 != 0,
+            clicolor_disabled: bits & 4 != 0,
+            clicolor_force: bits & 8 != 0,
+        })
+    }
+
+    fn terminal() -> Environment {
+        Environment {
+            is_terminal: true,
+            ..Environment::default()
+        }
+    }
+
+    #[test]
+    fn xterm_supports_common_decorations() {
+        for term in ["xterm", "xterm-256color"] {
+            let supported = supported_decorations(term);
+            assert!(supported.contains(&Decoration::Italic), "{term}");
+            assert!(supported.contains(&Decoration::StrikeThrough), "{term}");
+            assert!(!supported.contains(&Decoration::FastBlink), "{term}");
+        }
+    }
+
+    #[test]
+    fn screen_lacks_italics() {
+        let supported = supported_decorations("screen-256color");
+        assert!(supported.contains(&Decoration::Bold));
+        assert!(!supported.contains(&Decoration::Italic));
+        assert_eq!(supported, supported_decorations("screen"));
+    }
+
+    #[test]
+    fn dumb_supports_nothing() {
+        assert!(supported_decorations("dumb").is_empty());
+    }
+
+    #[test]
+    fn unknown_terminal_falls_back() {
+        for term in ["", "made-up-terminal"] {
+            assert_eq!(
+                supported_decorations(term),
+                set![Decoration::Bold, Decoration::Underline, Decoration::Invert],
+                "{term:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn always_ignores_environment() {
+        assert!(environments().all(|environment| ColorChoice::Always.resolve(&environment)));
+    }
+
+    #[test]
+    fn never_ignores_environment() {
+        assert!(environments().all(|environment| !ColorChoice::Never.resolve(&environment)));
+    }
+
+    #[test]
+    fn auto_requires_terminal() {
+        assert!(ColorChoice::Auto.resolve(&terminal()));
+        assert!(!ColorChoice::Auto.resolve(&Environment::default()));
+    }
+
+    #[test]
+    fn auto_respects_no_color() {
+        let environment = Environment {
+            no_color: true,
+            ..terminal()
+        };
+        assert!(!ColorChoice::Auto.resolve(&environment));
+    }
+
+    #[test]
+    fn auto_respects_clicolor() {
+        let environment = Environment {
+            clicolor_disabled: true,
+            ..terminal()
+        };
+        assert!(!ColorChoice::Auto.resolve(&environment));
+    }
+
+    #[test]
+    fn auto_respects_clicolor_force() {
+        let environment = Environment {
+            clicolor_force: true,
+            ..Environment::default()
+        };
+        assert!(ColorChoice::Auto.resolve(&environment));
+    }
+
+    #[test]
+    fn auto_precedence() {
+        for environment in environments() {
+            let expected = match environment {
+                Environment {
+                    clicolor_force: true,
+                    ..
+                } => true,
+                Environment { no_color: true, .. } => false,
+                Environment {
+                    clicolor_disabled: true,
+                    ..
+                } => false,
+                Environment { is_terminal, .. } => is_terminal,
+            };
+
+            assert_eq!(
+                ColorChoice::Auto.resolve(&environment),
+                expected,
+                "{environment:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn flag_values() {
+        assert!(is_enabled(OsStr::new("1")));
+        assert!(is_enabled(OsStr::new("yes")));
+        assert!(!is_enabled(OsStr::new("0")));
+        assert!(!is_enabled(OsStr::new("")));
+    }
+}
@@ -1,31 +1,173 @@
 use crate::{
-    ast::{CurrentStyle, Token},
+    ast::{CurrentStyle, Decoration, Style, Token, Tokens},
+    choice::{ColorChoice, Environment},
+    delimiters::Delimiters,
     error::Error,
-    parser::Parser,
+    minimize::minimize_sgr,
+    parser::{Parser, DEFAULT_MAX_DEPTH},
 };
+use std::slice;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Options {
     pub supports_color: bool,
+    /// Whether to disable color when the `NO_COLOR` environment variable is set
+    pub respect_no_color: bool,
+    /// Whether to remove ANSI escape sequences that were already present in the source
+    ///
+    /// Existing escape sequences are always removed when color is disabled.
+    pub strip_ansi: bool,
+    /// How a style is undone at the end of its content
+    pub reset_mode: ResetMode,
+    /// Whether to merge consecutive escape sequences into a single sequence
+    pub collapse_redundant: bool,
+    /// Whether to remove escape codes that have no visible effect anywhere in the output
+    ///
+    /// This tracks the styles across the whole output, so it can drop codes that
+    /// `collapse_redundant` can't (i.e. a reset immediately followed by re-applying the same
+    /// style), at the cost of some extra processing. The rendered text is always the same.
+    pub minimize: bool,
+    /// Whether to drop blinking decorations from the output, as many terminals don't support them
+    ///
+    /// The markup is still parsed the same way, so `fast-blink` and `slow-blink` remain valid.
+    pub suppress_blink: bool,
+    /// Whether to drop every color from the output, keeping only the decorations
+    ///
+    /// This covers foreground, background, and underline colors.
+    pub suppress_colors: bool,
+    /// Whether to drop every decoration from the output, keeping only the colors
+    pub suppress_decorations: bool,
+    /// Whether to clear the styles before each newline within styled content, and re-apply them
+    /// on the next line
+    ///
+    /// Some pagers and line-buffered consumers reset the styles at the end of every line, which
+    /// would otherwise leave the rest of a multi-line style uncolored.
+    pub reopen_on_newline: bool,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
             supports_color: true,
+            respect_no_color: false,
+            strip_ansi: false,
+            reset_mode: ResetMode::default(),
+            collapse_redundant: true,
+            minimize: false,
+            suppress_blink: false,
+            suppress_colors: false,
+            suppress_decorations: false,
+            reopen_on_newline: false,
+        }
+    }
+}
+
+/// How a style is undone at the end of its content
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ResetMode {
+    /// Only undo the properties the style changed, i.e. `\x1b[39m` to restore the foreground
+    #[default]
+    Targeted,
+    /// Clear every property using `\x1b[0m`, then re-apply the enclosing styles
+    ///
+    /// This is more robust when the text is interleaved with escape sequences from other sources,
+    /// at the cost of longer output.
+    Full,
+}
+
+impl Options {
+    /// Whether escape codes should be emitted, only checking the environment when it matters
+    fn emit_color(&self, environment: impl FnOnce() -> Environment) -> bool {
+        self.supports_color && !(self.respect_no_color && environment().no_color)
+    }
+}
+
+/// Options for converting styled markup with [`colorize_with`]
+#[derive(Clone, Copy, Debug)]
+pub struct ColorizeOptions {
+    /// How the markup is converted to escape codes
+    pub output: Options,
+    /// The characters surrounding the style specifiers and content of the markup
+    pub delimiters: Delimiters,
+    /// How deeply styled markup can be nested within each other, defaults to 128
+    ///
+    /// Markup nested any deeper produces an error instead of overflowing the stack. Sticky styles
+    /// don't have any content of their own, so they don't count towards the depth.
+    pub max_depth: usize,
+    /// Whether to recognize Markdown-style emphasis, where `*text*` is italic and `**text**` is
+    /// bold, defaults to `false`
+    ///
+    /// When enabled, literal asterisks must be escaped as `\*`. Emphasis isn't recognized if `*` is
+    /// one of the delimiters.
+    pub markdown_emphasis: bool,
+}
+
+impl Default for ColorizeOptions {
+    fn default() -> Self {
+        Self {
+            output: Options::default(),
+            delimiters: Delimiters::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            markdown_emphasis: false,
         }
     }
 }
 
 pub fn colorize(input: &str, options: Options) -> Result<String, Vec<Error>> {
-    let (tokens, errors) = Parser::new(input).parse();
+    colorize_with(
+        input,
+        &ColorizeOptions {
+            output: options,
+            ..ColorizeOptions::default()
+        },
+    )
+}
+
+/// Convert styled markup to ANSI escape codes, allowing the markup's delimiters to be changed
+///
+/// Any errors refer to the configured delimiters, so they should be reported using
+/// [`ErrorReport::with_delimiters`](crate::ErrorReport::with_delimiters).
+pub fn colorize_with(input: &str, options: &ColorizeOptions) -> Result<String, Vec<Error>> {
+    colorize_in(input, options, Environment::detect)
+}
+
+/// Convert styled markup to ANSI escape codes, checking the given environment rather than the one
+/// the process is running in
+fn colorize_in(
+    input: &str,
+    options: &ColorizeOptions,
+    environment: impl FnOnce() -> Environment,
+) -> Result<String, Vec<Error>> {
+    let (tokens, errors) = Parser::with_delimiters(input, options.delimiters)
+        .with_max_depth(options.max_depth)
+        .with_markdown_emphasis(options.markdown_emphasis)
+        .parse();
     if !errors.is_empty() {
         return Err(errors);
     }
 
+    let mut tokens = Tokens::from(tokens);
+    tokens.normalize();
+    let mut tokens = Vec::from(tokens);
+    let output = &options.output;
+    if output.suppress_blink || output.suppress_colors || output.suppress_decorations {
+        suppress_styles(&mut tokens, output);
+    }
+
     let mut result = String::with_capacity(input.len());
-    if options.supports_color {
-        convert_tokens(&mut result, CurrentStyle::default(), &tokens);
+    if options.output.emit_color(environment) {
+        convert_tokens(
+            &mut result,
+            CurrentStyle::default(),
+            &tokens,
+            &options.output,
+        );
+        if options.output.collapse_redundant {
+            result = coalesce_sgr(&result);
+        }
+        if options.output.minimize {
+            result = minimize_sgr(&result);
+        }
     } else {
         convert_tokens_no_color(&mut result, &tokens);
     }
@@ -34,35 +176,348 @@ pub fn colorize(input: &str, options: Options) -> Result<String, Vec<Error>> {
     Ok(result)
 }
 
+/// Convert styled markup to ANSI escape codes, deciding whether to emit the codes using a
+/// [`ColorChoice`]
+///
+/// When the codes are not emitted, the markup is removed and only the text is output.
+pub fn colorize_with_choice(input: &str, choice: ColorChoice) -> Result<String, Vec<Error>> {
+    colorize_with_choice_in(input, choice, &Environment::detect())
+}
+
+/// Convert styled markup to ANSI escape codes, deciding whether to emit the codes using a
+/// [`ColorChoice`] in the given environment rather than the one the process is running in
+fn colorize_with_choice_in(
+    input: &str,
+    choice: ColorChoice,
+    environment: &Environment,
+) -> Result<String, Vec<Error>> {
+    let options = Options {
+        supports_color: choice.resolve(environment),
+        ..Options::default()
+    };
+    colorize(input, options)
+}
+
+/// Convert styled markup to ANSI escape codes when writing to a terminal
+///
+/// Escape codes are only emitted when stdout is a terminal and the `NO_COLOR` environment variable
+/// is not set. Otherwise, the markup is removed and only the text is output.
+pub fn colorize_auto(input: &str) -> Result<String, Vec<Error>> {
+    colorize_with_choice(input, ColorChoice::Auto)
+}
+
+/// Remove all styled markup, leaving only the text
+///
+/// Any ANSI escape sequences already present in the source are removed as well.
+pub fn strip(input: &str) -> Result<String, Vec<Error>> {
+    colorize_with_choice(input, ColorChoice::Never)
+}
+
+/// Check whether a piece of styled markup is valid, without converting it
+///
+/// Returns the same errors as [`colorize`], but skips building the output.
+pub fn validate(input: &str) -> Result<(), Vec<Error>> {
+    let (_, errors) = Parser::new(input).parse();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
 /// Convert the tokens into the resulting string
-fn convert_tokens(output: &mut String, parent_style: CurrentStyle, tokens: &[Token]) {
-    for token in tokens {
+///
+/// The tokens are walked with an explicit stack, so arbitrarily deep nesting can't overflow the
+/// call stack.
+fn convert_tokens(
+    output: &mut String,
+    parent_style: CurrentStyle,
+    tokens: &[Token],
+    options: &Options,
+) {
+    let mut stack = vec![Frame {
+        tokens: tokens.iter(),
+        parent_style,
+        open: None,
+    }];
+
+    while let Some(frame) = stack.last_mut() {
+        let Some(token) = frame.tokens.next() else {
+            let frame = stack.pop().expect("stack is not empty");
+            if let Some(style) = frame.open {
+                reset_style(style, &frame.parent_style, options, output);
+            }
+            continue;
+        };
+
         match token {
-            Token::Content(content) => output.push_str(content),
-            Token::Styled { content, style } => {
+            Token::Content(content, _) => {
+                if let Some(style) = frame.open.take() {
+                    reset_style(style, &frame.parent_style, options, output);
+                }
+                if options.reopen_on_newline {
+                    push_reopening_lines(content, &frame.parent_style, output);
+                } else {
+                    output.push_str(content);
+                }
+            }
+            Token::Raw(sequence, _) => {
+                if options.strip_ansi {
+                    continue;
+                }
+
+                if let Some(style) = frame.open.take() {
+                    reset_style(style, &frame.parent_style, options, output);
+                }
+                output.push_str(sequence);
+            }
+            Token::Styled { content, style, .. } => {
                 if content.is_empty() {
                     continue;
                 }
 
-                style.apply(&parent_style, output);
-                convert_tokens(output, parent_style.extend(style), content);
-                style.reset(&parent_style, output);
+                match frame.open {
+                    Some(previous) if previous == style => {}
+                    _ => {
+                        if let Some(previous) = frame.open {
+                            reset_style(previous, &frame.parent_style, options, output);
+                        }
+                        style.apply(&frame.parent_style, output);
+                    }
+                }
+
+                frame.open = Some(style);
+                let parent_style = frame.parent_style.extend(style);
+                stack.push(Frame {
+                    tokens: content.iter(),
+                    parent_style,
+                    open: None,
+                });
             }
         }
     }
 }
 
-/// Convert the tokens into the resulting string without applying styles
-fn convert_tokens_no_color(output: &mut String, tokens: &[Token]) {
-    for token in tokens {
-        match token {
-            Token::Content(content) => output.push_str(content),
-            Token::Styled { content, .. } => {
-                if content.is_empty() {
+/// The progress of converting one level of the token tree
+struct Frame<'t, 's> {
+    /// The tokens that are left at this level
+    tokens: slice::Iter<'t, Token<'s>>,
+    /// The style the tokens at this level are displayed within
+    parent_style: CurrentStyle,
+    /// The last styled token, whose reset is deferred so adjacent tokens with the same style can
+    /// continue using it rather than emitting a redundant reset and apply
+    open: Option<&'t Style>,
+}
+
+/// Write the content, clearing the current style before each line break and re-applying it after
+fn push_reopening_lines(content: &str, current: &CurrentStyle, output: &mut String) {
+    let codes = current.codes();
+    if codes.is_empty() {
+        output.push_str(content);
+        return;
+    }
+
+    for line in content.split_inclusive('\n') {
+        let Some(text) = line.strip_suffix('\n') else {
+            output.push_str(line);
+            continue;
+        };
+        let (text, newline) = match text.strip_suffix('\r') {
+            Some(text) => (text, "\r\n"),
+            None => (text, "\n"),
+        };
+
+        output.push_str(text);
+        output.push_str("\x1b[0m");
+        output.push_str(newline);
+        output.push_str("\x1b[");
+        output.push_str(&codes.join(";"));
+        output.push('m');
+    }
+}
+
+/// Remove the properties suppressed by the options from every styled token
+fn suppress_styles(tokens: &mut [Token], options: &Options) {
+    let mut stack = vec![tokens];
+    while let Some(tokens) = stack.pop() {
+        for token in tokens {
+            let Token::Styled { content, style, .. } = token else {
+                continue;
+            };
+
+            if options.suppress_colors {
+                style.foreground = None;
+                style.background = None;
+                style.underline_color = None;
+            }
+            if options.suppress_decorations {
+                style.decoration = None;
+            } else if options.suppress_blink {
+                if let Some(decoration) = &mut style.decoration {
+                    decoration
+                        .retain(|d| !matches!(d, Decoration::SlowBlink | Decoration::FastBlink));
+                }
+            }
+            stack.push(content.as_mut_slice());
+        }
+    }
+}
+
+/// Undo a style at the end of its content using the configured reset mode
+fn reset_style(style: &Style, parent: &CurrentStyle, options: &Options, output: &mut String) {
+    match options.reset_mode {
+        ResetMode::Targeted => style.reset(parent, output),
+        ResetMode::Full => style.reset_fully(parent, output),
+    }
+}
+
+/// Merge consecutive SGR escape sequences into a single sequence
+///
+/// For example, `\x1b[31m\x1b[1m` becomes `\x1b[31;1m`. The order of the codes is preserved and
+/// sequences separated by any other text are never merged. Sequences followed by one that starts
+/// with a reset are dropped, as they would have no effect, and so are colors that are replaced
+/// within the merged sequence.
+fn coalesce_sgr(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut pending = Vec::new();
+    let mut rest = input;
+
+    while let Some(index) = rest.find('\x1b') {
+        if index > 0 {
+            flush_sgr(&mut output, &mut pending);
+            output.push_str(&rest[..index]);
+            rest = &rest[index..];
+        }
+
+        match sgr_parameters(rest) {
+            Some(parameters) => {
+                // a sequence starting with a reset undoes everything before it
+                if matches!(parameters.split(';').next(), Some("" | "0")) {
+                    pending.clear();
+                }
+                pending.push(parameters);
+                rest = &rest[parameters.len() + 3..];
+            }
+            None => {
+                flush_sgr(&mut output, &mut pending);
+                output.push('\x1b');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    flush_sgr(&mut output, &mut pending);
+    output.push_str(rest);
+    output
+}
+
+/// Extract the parameters of the SGR escape sequence at the start of the input
+pub(crate) fn sgr_parameters(input: &str) -> Option<&str> {
+    let body = input.strip_prefix("\x1b[")?;
+    let end = body.find(|c: char| !(c.is_ascii_digit() || c == ';'))?;
+    (body.as_bytes()[end] == b'm').then(|| &body[..end])
+}
+
+/// Write the pending SGR parameters as a single escape sequence
+///
+/// When several sequences are merged, any color that is replaced by a later code in the same
+/// sequence is dropped, as it would have no effect.
+fn flush_sgr(output: &mut String, pending: &mut Vec<&str>) {
+    match pending.as_slice() {
+        [] => return,
+        [parameters] => {
+            output.push_str("\x1b[");
+            output.push_str(parameters);
+        }
+        all => {
+            // an empty sequence is a reset, which must be explicit once merged
+            let codes = all
+                .iter()
+                .flat_map(|parameters| match parameters.is_empty() {
+                    true => "0".split(';'),
+                    false => parameters.split(';'),
+                })
+                .collect::<Vec<_>>();
+            let groups = sgr_groups(&codes);
+
+            output.push_str("\x1b[");
+            let mut first = true;
+            for (i, (channel, group)) in groups.iter().enumerate() {
+                let replaced = channel.is_some_and(|channel| {
+                    groups[i + 1..]
+                        .iter()
+                        .any(|(later, _)| *later == Some(channel))
+                });
+                if replaced {
                     continue;
                 }
 
-                convert_tokens_no_color(output, content);
+                for code in *group {
+                    if !first {
+                        output.push(';');
+                    }
+                    output.push_str(code);
+                    first = false;
+                }
+            }
+        }
+    }
+
+    output.push('m');
+    pending.clear();
+}
+
+/// The colors that can be set by an SGR code
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ColorChannel {
+    Foreground,
+    Background,
+    Underline,
+}
+
+/// Split SGR codes into groups that each make a single change, along with the color they set
+///
+/// Extended colors (i.e. `38;5;n` and `38;2;r;g;b`) span multiple codes, so they are kept
+/// together.
+fn sgr_groups<'a, 'c>(codes: &'a [&'c str]) -> Vec<(Option<ColorChannel>, &'a [&'c str])> {
+    let mut groups = Vec::with_capacity(codes.len());
+    let mut rest = codes;
+
+    while let [code, arguments @ ..] = rest {
+        let length = match (*code, arguments.first().copied()) {
+            ("38" | "48" | "58", Some("5")) => 3,
+            ("38" | "48" | "58", Some("2")) => 5,
+            _ => 1,
+        }
+        .min(rest.len());
+
+        let channel = match code.parse::<u8>() {
+            Ok(30..=39 | 90..=97) => Some(ColorChannel::Foreground),
+            Ok(40..=49 | 100..=107) => Some(ColorChannel::Background),
+            Ok(58 | 59) => Some(ColorChannel::Underline),
+            _ => None,
+        }
+        // an extended color without its arguments can't be interpreted
+        .filter(|_| length > 1 || !matches!(*code, "38" | "48" | "58"));
+
+        let (group, remaining) = rest.split_at(length);
+        groups.push((channel, group));
+        rest = remaining;
+    }
+
+    groups
+}
+
+/// Convert the tokens into the resulting string without applying styles
+fn convert_tokens_no_color(output: &mut String, tokens: &[Token]) {
+    let mut stack = vec![tokens.iter()];
+    while let Some(tokens) = stack.last_mut() {
+        match tokens.next() {
+            Some(Token::Content(content, _)) => output.push_str(content),
+            Some(Token::Raw(..)) => {}
+            Some(Token::Styled { content, .. }) => stack.push(content.iter()),
+            None => {
+                stack.pop();
             }
         }
     }
@@ -70,12 +525,26 @@ fn convert_tokens_no_color(output: &mut String, tokens: &[Token]) {
 
 #[cfg(test)]
 mod tests {
-    use super::{colorize, Options};
-    use crate::ast::{Style, Token};
+    use super::{
+        coalesce_sgr, colorize, colorize_auto, colorize_in, colorize_with, colorize_with_choice,
+        colorize_with_choice_in, strip, validate, ColorizeOptions, Options, ResetMode,
+    };
+    use crate::{
+        ast::{Style, Token},
+        choice::{ColorChoice, Environment},
+        delimiters::Delimiters,
+        error::Reason,
+    };
+    use proptest::prelude::*;
 
     fn convert_tokens(parent_style: Option<Style>, tokens: &[Token]) -> String {
         let mut result = String::new();
-        super::convert_tokens(&mut result, parent_style.unwrap_or_default().into(), tokens);
+        super::convert_tokens(
+            &mut result,
+            parent_style.unwrap_or_default().into(),
+            tokens,
+            &Options::default(),
+        );
         result
     }
 
@@ -87,7 +556,7 @@ mod tests {
 
     #[test]
     fn convert_tokens_single_content_token() {
-        let result = convert_tokens(None, &[Token::Content(String::from("test"))]);
+        let result = convert_tokens(None, &[Token::Content("test".into(), span!(0..0))]);
         assert_eq!(result, "test");
     }
 
@@ -96,9 +565,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[
-                Token::Content(String::from("a")),
-                Token::Content(String::from("b")),
-                Token::Content(String::from("c")),
+                Token::Content("a".into(), span!(0..0)),
+                Token::Content("b".into(), span!(0..0)),
+                Token::Content("c".into(), span!(0..0)),
             ],
         );
 
@@ -112,6 +581,7 @@ mod tests {
             &[Token::Styled {
                 content: vec![],
                 style: style!(),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "");
@@ -122,8 +592,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("test"))],
+                content: vec![Token::Content("test".into(), span!(0..0))],
                 style: style!(),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "test")
@@ -135,11 +606,12 @@ mod tests {
             None,
             &[Token::Styled {
                 content: vec![
-                    Token::Content(String::from("a")),
-                    Token::Content(String::from("b")),
-                    Token::Content(String::from("c")),
+                    Token::Content("a".into(), span!(0..0)),
+                    Token::Content("b".into(), span!(0..0)),
+                    Token::Content("c".into(), span!(0..0)),
                 ],
                 style: style!(),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "abc")
@@ -150,8 +622,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(fg: Red;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[31mcontent\x1b[39m");
@@ -162,8 +635,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(bg: Blue;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[44mcontent\x1b[49m");
@@ -174,8 +648,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(deco: Bold;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[1mcontent\x1b[22m");
@@ -186,8 +661,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(deco: Bold, Italic;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[1;3mcontent\x1b[22;23m");
@@ -198,8 +674,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(fg: Red; bg: Blue;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[31;44mcontent\x1b[39;49m");
@@ -210,8 +687,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(fg: Red; deco: Bold;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[31;1mcontent\x1b[39;22m");
@@ -222,8 +700,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(fg: Red; deco: Bold, Italic;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[31;1;3mcontent\x1b[39;22;23m");
@@ -234,8 +713,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(bg: Blue; fg: Red;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[31;44mcontent\x1b[39;49m");
@@ -246,8 +726,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(bg: Blue; deco: Bold;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[44;1mcontent\x1b[49;22m");
@@ -258,8 +739,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(bg: Blue; deco: Bold, Italic;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[44;1;3mcontent\x1b[49;22;23m");
@@ -270,8 +752,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(deco: Bold; fg: Red;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[31;1mcontent\x1b[39;22m");
@@ -282,8 +765,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(deco: Bold; bg: Blue;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[44;1mcontent\x1b[49;22m");
@@ -294,8 +778,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(deco: Bold, Italic; fg: Red;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[31;1;3mcontent\x1b[39;22;23m");
@@ -306,8 +791,9 @@ mod tests {
         let result = convert_tokens(
             None,
             &[Token::Styled {
-                content: vec![Token::Content(String::from("content"))],
+                content: vec![Token::Content("content".into(), span!(0..0))],
                 style: style!(deco: Bold, Italic; bg: Blue;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[44;1;3mcontent\x1b[49;22;23m");
@@ -319,14 +805,16 @@ mod tests {
             None,
             &[Token::Styled {
                 content: vec![
-                    Token::Content(String::from("red ")),
+                    Token::Content("red ".into(), span!(0..0)),
                     Token::Styled {
-                        content: vec![Token::Content(String::from("blue"))],
+                        content: vec![Token::Content("blue".into(), span!(0..0))],
                         style: style!(bg: Blue;),
+                        span: span!(0..0),
                     },
-                    Token::Content(String::from(" red")),
+                    Token::Content(" red".into(), span!(0..0)),
                 ],
                 style: style!(fg: Red;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[31mred \x1b[44mblue\x1b[49m red\x1b[39m");
@@ -338,14 +826,16 @@ mod tests {
             None,
             &[Token::Styled {
                 content: vec![
-                    Token::Content(String::from("red ")),
+                    Token::Content("red ".into(), span!(0..0)),
                     Token::Styled {
-                        content: vec![Token::Content(String::from("blue"))],
+                        content: vec![Token::Content("blue".into(), span!(0..0))],
                         style: style!(fg: Blue;),
+                        span: span!(0..0),
                     },
-                    Token::Content(String::from(" red")),
+                    Token::Content(" red".into(), span!(0..0)),
                 ],
                 style: style!(fg: Red;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[31mred \x1b[34mblue\x1b[31m red\x1b[39m");
@@ -357,14 +847,16 @@ mod tests {
             None,
             &[Token::Styled {
                 content: vec![
-                    Token::Content(String::from("red ")),
+                    Token::Content("red ".into(), span!(0..0)),
                     Token::Styled {
-                        content: vec![Token::Content(String::from("blue"))],
+                        content: vec![Token::Content("blue".into(), span!(0..0))],
                         style: style!(bg: Blue;),
+                        span: span!(0..0),
                     },
-                    Token::Content(String::from(" red")),
+                    Token::Content(" red".into(), span!(0..0)),
                 ],
                 style: style!(bg: Red;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[41mred \x1b[44mblue\x1b[41m red\x1b[49m");
@@ -376,14 +868,16 @@ mod tests {
             None,
             &[Token::Styled {
                 content: vec![
-                    Token::Content(String::from("bold ")),
+                    Token::Content("bold ".into(), span!(0..0)),
                     Token::Styled {
-                        content: vec![Token::Content(String::from("italic"))],
+                        content: vec![Token::Content("italic".into(), span!(0..0))],
                         style: style!(deco: Italic;),
+                        span: span!(0..0),
                     },
-                    Token::Content(String::from(" bold")),
+                    Token::Content(" bold".into(), span!(0..0)),
                 ],
                 style: style!(deco: Bold;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[1mbold \x1b[3mitalic\x1b[23m bold\x1b[22m");
@@ -396,14 +890,16 @@ mod tests {
             None,
             &[Token::Styled {
                 content: vec![
-                    Token::Content(String::from("bold ")),
+                    Token::Content("bold ".into(), span!(0..0)),
                     Token::Styled {
-                        content: vec![Token::Content(String::from("italic"))],
+                        content: vec![Token::Content("italic".into(), span!(0..0))],
                         style: style!(deco: Italic;),
+                        span: span!(0..0),
                     },
-                    Token::Content(String::from(" bold")),
+                    Token::Content(" bold".into(), span!(0..0)),
                 ],
                 style: style!(deco: Bold, Italic;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[1;3mbold italic bold\x1b[22;23m");
@@ -415,14 +911,16 @@ mod tests {
             None,
             &[Token::Styled {
                 content: vec![
-                    Token::Content(String::from("red ")),
+                    Token::Content("red ".into(), span!(0..0)),
                     Token::Styled {
-                        content: vec![Token::Content(String::from("blue"))],
+                        content: vec![Token::Content("blue".into(), span!(0..0))],
                         style: style!(fg: Red; bg: Blue;),
+                        span: span!(0..0),
                     },
-                    Token::Content(String::from(" red")),
+                    Token::Content(" red".into(), span!(0..0)),
                 ],
                 style: style!(fg: Red;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[31mred \x1b[44mblue\x1b[49m red\x1b[39m");
@@ -434,14 +932,16 @@ mod tests {
             None,
             &[Token::Styled {
                 content: vec![
-                    Token::Content(String::from("red ")),
+                    Token::Content("red ".into(), span!(0..0)),
                     Token::Styled {
-                        content: vec![Token::Content(String::from("blue"))],
+                        content: vec![Token::Content("blue".into(), span!(0..0))],
                         style: style!(fg: Red; bg: Blue;),
+                        span: span!(0..0),
                     },
-                    Token::Content(String::from(" red")),
+                    Token::Content(" red".into(), span!(0..0)),
                 ],
                 style: style!(bg: Blue;),
+                span: span!(0..0),
             }],
         );
         assert_eq!(result, "\x1b[44mred \x1b[31mblue\x1b[39m red\x1b[49m");
@@ -582,7 +1082,64 @@ mod tests {
     #[test]
     fn colorize_two_spans_of_styled_text_back_to_back() {
         let result = colorize("[fg:red](first)[fg:blue](second)", Options::default()).unwrap();
-        assert_eq!(result, "\x1b[31mfirst\x1b[39m\x1b[34msecond\x1b[39m");
+        assert_eq!(result, "\x1b[31mfirst\x1b[34msecond\x1b[39m");
+    }
+
+    #[test]
+    fn colorize_back_to_back_spans_with_same_style_are_merged() {
+        let result = colorize("[fg:red](a)[fg:red](b)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[31mab\x1b[39m");
+        assert!(!result.contains("\x1b[39m\x1b[31m"));
+    }
+
+    #[test]
+    fn colorize_back_to_back_nested_spans_with_same_style_are_merged() {
+        let result = colorize(
+            "[fg:red]([deco:bold](a)[deco:bold](b)[deco:bold](c))",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "\x1b[31;1mabc\x1b[22;39m");
+    }
+
+    #[test]
+    fn colorize_empty_content_is_dropped() {
+        let result = colorize("[fg:red]()next", Options::default()).unwrap();
+        assert_eq!(result, "next");
+    }
+
+    #[test]
+    fn colorize_empty_content_does_not_style_following_text() {
+        let result = colorize("[fg:red]()[deco:bold](next)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[1mnext\x1b[22m");
+    }
+
+    #[test]
+    fn colorize_empty_content_with_full_reset_is_dropped() {
+        let options = Options {
+            reset_mode: ResetMode::Full,
+            ..Options::default()
+        };
+        let result = colorize("a [fg:red]([deco:bold]()) b", options).unwrap();
+        assert_eq!(result, "a  b");
+    }
+
+    #[test]
+    fn colorize_spans_with_same_style_separated_by_empty_span_are_merged() {
+        let result = colorize("[fg:red](a)[fg:blue]()[fg:red](b)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[31mab\x1b[39m");
+    }
+
+    #[test]
+    fn colorize_spans_with_same_style_separated_by_content_are_not_merged() {
+        let result = colorize("[fg:red](a) [fg:red](b)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[31ma\x1b[39m \x1b[31mb\x1b[39m");
+    }
+
+    #[test]
+    fn colorize_spans_with_same_style_separated_by_escape_sequence_are_not_merged() {
+        let result = colorize("[fg:red](a)\x1b[1m[fg:red](b)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[31ma\x1b[1;31mb\x1b[39m");
     }
 
     #[test]
@@ -616,27 +1173,1023 @@ mod tests {
     }
 
     #[test]
-    fn colorize_kitchen_sink() {
+    fn colorize_single_nested_child_is_merged_with_parent() {
+        let result = colorize("[fg:red]([deco:bold](content))", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[31;1mcontent\x1b[39;22m");
+    }
+
+    #[test]
+    fn colorize_deeply_nested_single_children_are_merged() {
         let result = colorize(
-            "leading [fg:red](one [bg:blue](two [deco:dim](three) two) one) trailing",
+            "[fg:red]([bg:blue]([deco:dim]([fg:green](content))))",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "\x1b[32;44;2mcontent\x1b[39;49;22m");
+    }
+
+    #[test]
+    fn colorize_default_colors_without_colored_parent() {
+        let result = colorize("a [fg:default;bg:default](b) c", Options::default()).unwrap();
+        assert_eq!(result, "a b c");
+    }
+
+    #[test]
+    fn colorize_default_foreground_after_reset() {
+        let result = colorize(
+            "[fg:red](red [reset;fg:default](default) red)",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "\x1b[31mred \x1b[0mdefault\x1b[0;31m red\x1b[39m");
+    }
+
+    #[test]
+    fn colorize_default_foreground_under_colored_parent() {
+        let result = colorize(
+            "[fg:red](red [fg:default](default) red)",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "\x1b[31mred \x1b[39mdefault\x1b[31m red\x1b[39m");
+    }
+
+    #[test]
+    fn colorize_default_foreground_nested_directly_in_colored_parent() {
+        // the only child is merged into its parent, so the text is never colored in the first place
+        let result = colorize("[fg:red]([fg:default](x))", Options::default()).unwrap();
+        assert_eq!(result, "x");
+
+        let result = colorize("[fg:red]([fg:default](x) y)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[39mx\x1b[31m y\x1b[39m");
+    }
+
+    #[test]
+    fn colorize_default_foreground_under_colored_ancestor() {
+        let result = colorize(
+            "[fg:red](a [deco:bold](b [fg:default](x) c))",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "\x1b[31ma \x1b[1mb \x1b[39mx\x1b[31m c\x1b[22;39m");
+    }
+
+    #[test]
+    fn colorize_colored_foreground_under_default_parent() {
+        let result = colorize(
+            "[fg:default](default [fg:red](red) default)",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "default \x1b[31mred\x1b[39m default");
+    }
+
+    #[test]
+    fn colorize_default_foreground_under_colored_parent_keeps_other_styles() {
+        let result = colorize(
+            "[fg:red;bg:blue;deco:bold](one [fg:default](two) one)",
             Options::default(),
         )
         .unwrap();
         assert_eq!(
             result,
-            "leading \x1b[31mone \x1b[44mtwo \x1b[2mthree\x1b[22m two\x1b[49m one\x1b[39m trailing"
+            "\x1b[31;44;1mone \x1b[39mtwo\x1b[31m one\x1b[39;49;22m"
         );
     }
 
     #[test]
-    fn colorize_kitchen_sink_color_disabled() {
+    fn colorize_default_background_under_colored_parent() {
+        let result = colorize("[bg:blue]([bg:default](x) y)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[49mx\x1b[44m y\x1b[49m");
+    }
+
+    #[test]
+    fn colorize_colored_background_under_default_parent() {
+        let result = colorize("[bg:default](x [bg:blue](y) x)", Options::default()).unwrap();
+        assert_eq!(result, "x \x1b[44my\x1b[49m x");
+    }
+
+    #[test]
+    fn colorize_default_background_between_colored_backgrounds() {
         let result = colorize(
-            "leading [fg:red](one [bg:blue](two [deco:dim](three) two) one) trailing",
-            Options {
-                supports_color: false,
-            },
+            "[bg:blue](one [bg:default](two [bg:red](three) two) one)",
+            Options::default(),
         )
         .unwrap();
-        assert_eq!(result, "leading one two three two one trailing");
+        assert_eq!(
+            result,
+            "\x1b[44mone \x1b[49mtwo \x1b[41mthree\x1b[49m two\x1b[44m one\x1b[49m"
+        );
+    }
+
+    #[test]
+    fn colorize_default_background_under_colored_parent_keeps_other_styles() {
+        let result = colorize(
+            "[fg:red;bg:blue;deco:bold](one [bg:default](two) one)",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "\x1b[31;44;1mone \x1b[49mtwo\x1b[44m one\x1b[39;49;22m"
+        );
+    }
+
+    #[test]
+    fn colorize_none_foreground_keeps_inherited_background() {
+        let result = colorize("[fg:red;bg:blue](a [fg:none](b) a)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[31;44ma \x1b[39mb\x1b[31m a\x1b[39;49m");
+    }
+
+    #[test]
+    fn colorize_reset_foreground_under_colored_parent() {
+        let result = colorize(
+            "[fg:red;bg:blue](a [fg:reset](b [fg:green](c) b) a)",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "\x1b[31;44ma \x1b[39mb \x1b[32mc\x1b[39m b\x1b[31m a\x1b[39;49m"
+        );
+    }
+
+    #[test]
+    fn colorize_reset_background_under_colored_parent() {
+        let result = colorize("[bg:blue](a [bg:reset](b) a)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[44ma \x1b[49mb\x1b[44m a\x1b[49m");
+    }
+
+    #[test]
+    fn colorize_reset_foreground_in_full_reset_mode() {
+        let options = Options {
+            reset_mode: ResetMode::Full,
+            ..Options::default()
+        };
+        let result = colorize("[fg:red;deco:bold](a [fg:reset](b) a)", options).unwrap();
+        assert_eq!(result, "\x1b[31;1ma \x1b[39mb\x1b[0;31;1m a\x1b[0m");
+    }
+
+    #[test]
+    fn colorize_unset_background_keeps_inherited_foreground() {
+        let result = colorize("[fg:red;bg:blue](a [bg:unset](b) a)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[31;44ma \x1b[49mb\x1b[44m a\x1b[39;49m");
+    }
+
+    #[test]
+    fn colorize_underline_color() {
+        let result = colorize("[ul:red;deco:underline](a)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[58;5;1;4ma\x1b[59;24m");
+
+        let result = colorize("[underline-color:bright-blue](a)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[58;5;12ma\x1b[59m");
+    }
+
+    #[test]
+    fn colorize_underline_color_under_parent() {
+        let result = colorize(
+            "[deco:underline;ul:red](a [ul:blue](b) [ul:red](c) [ul:default](d) a)",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "\x1b[58;5;1;4ma \x1b[58;5;4mb\x1b[58;5;1m c \x1b[59md\x1b[58;5;1m a\x1b[59;24m"
+        );
+    }
+
+    #[test]
+    fn colorize_underline_color_in_full_reset_mode() {
+        let options = Options {
+            reset_mode: ResetMode::Full,
+            ..Options::default()
+        };
+        let result = colorize("[ul:red;deco:underline](a [ul:blue](b) a)", options).unwrap();
+        assert_eq!(
+            result,
+            "\x1b[58;5;1;4ma \x1b[58;5;4mb\x1b[0;58;5;1;4m a\x1b[0m"
+        );
+    }
+
+    /// Render each source with both reset modes, escaping the output so it can be read
+    fn compare_reset_modes(sources: &[&str]) -> String {
+        let mut result = String::new();
+        for source in sources {
+            result.push_str(&format!("{source}\n"));
+            for reset_mode in [ResetMode::Targeted, ResetMode::Full] {
+                let options = Options {
+                    reset_mode,
+                    ..Options::default()
+                };
+                let output = colorize(source, options).unwrap();
+                result.push_str(&format!("  {reset_mode:?}: {output:?}\n"));
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn reset_modes() {
+        insta::assert_snapshot!(compare_reset_modes(&[
+            "[fg:red](a) [bg:blue](b) [deco:bold,underline](c)",
+            "[fg:red](a [bg:blue](b [deco:bold](c) b) a)",
+            "[fg:red;deco:bold](a [reset;fg:green](b) a)",
+            "[fg:red](a) [fg:red](b)",
+            "[fg:red]sticky [deco:bold]styles",
+        ]));
+    }
+
+    #[test]
+    fn full_reset_mode_ends_every_top_level_block_with_reset() {
+        let options = Options {
+            reset_mode: ResetMode::Full,
+            ..Options::default()
+        };
+        let result = colorize("[fg:red](a [deco:bold](b)) [bg:blue](c)", options).unwrap();
+        assert_eq!(result, "\x1b[31ma \x1b[1mb\x1b[0m \x1b[44mc\x1b[0m");
+    }
+
+    #[test]
+    fn colorize_minimize() {
+        let options = Options {
+            reset_mode: ResetMode::Full,
+            minimize: true,
+            ..Options::default()
+        };
+        let result = colorize("[fg:red](a [deco:bold](b) a) [fg:red](c)", options).unwrap();
+        assert_eq!(
+            result,
+            "\x1b[31ma \x1b[1mb\x1b[22m a\x1b[0m \x1b[31mc\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn colorize_kitchen_sink() {
+        let result = colorize(
+            "leading [fg:red](one [bg:blue](two [deco:dim](three) two) one) trailing",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "leading \x1b[31mone \x1b[44mtwo \x1b[2mthree\x1b[22m two\x1b[49m one\x1b[39m trailing"
+        );
+    }
+
+    #[test]
+    fn colorize_reset_clears_inherited_styles() {
+        let result = colorize(
+            "[fg:red;bg:blue;deco:bold](a [reset](b) c)",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "\x1b[31;44;1ma \x1b[0mb\x1b[0;31;44;1m c\x1b[39;49;22m"
+        );
+    }
+
+    #[test]
+    fn colorize_reset_with_styles() {
+        let result = colorize(
+            "[fg:red;deco:bold](a [reset;fg:green](b) c)",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "\x1b[31;1ma \x1b[0;32mb\x1b[0;31;1m c\x1b[39;22m");
+    }
+
+    #[test]
+    fn colorize_reset_with_nested_styles() {
+        let result = colorize("[fg:red](a [reset]([deco:bold](b)) c)", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[31ma \x1b[0;1mb\x1b[0;31m c\x1b[39m");
+    }
+
+    #[test]
+    fn colorize_sticky_style_applies_to_rest_of_text() {
+        let result = colorize("a [fg:red]b c", Options::default()).unwrap();
+        assert_eq!(result, "a \x1b[31mb c\x1b[39m");
+    }
+
+    #[test]
+    fn colorize_sticky_style_ends_with_enclosing_content() {
+        let result = colorize("[deco:bold](a [fg:red]b) c", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[1ma \x1b[31mb\x1b[39;22m c");
+    }
+
+    #[test]
+    fn colorize_sticky_style_overridden() {
+        let result = colorize("[fg:red]a [fg:blue](b) c [fg:green]d", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[31ma \x1b[34mb\x1b[31m c \x1b[32md\x1b[39m");
+    }
+
+    #[test]
+    fn colorize_full_reset_mode() {
+        let options = Options {
+            reset_mode: ResetMode::Full,
+            ..Options::default()
+        };
+        let result = colorize("a [fg:red;deco:bold](b [bg:blue](c) d) e", options).unwrap();
+        assert_eq!(result, "a \x1b[31;1mb \x1b[44mc\x1b[0;31;1m d\x1b[0m e");
+    }
+
+    #[test]
+    fn colorize_without_collapsing_redundant_sequences() {
+        let options = Options {
+            collapse_redundant: false,
+            ..Options::default()
+        };
+        let result = colorize("[fg:red](a)[deco:bold](b)", options).unwrap();
+        assert_eq!(result, "\x1b[31ma\x1b[39m\x1b[1mb\x1b[22m");
+    }
+
+    #[test]
+    fn colorize_large_input() {
+        let source = "plain text [fg:red](red [deco:bold](bold) red) ".repeat(100_000);
+        let result = colorize(&source, Options::default()).unwrap();
+        assert_eq!(
+            result,
+            "plain text \x1b[31mred \x1b[1mbold\x1b[22m red\x1b[39m ".repeat(100_000)
+        );
+    }
+
+    #[test]
+    fn colorize_kitchen_sink_color_disabled() {
+        let result = colorize(
+            "leading [fg:red](one [bg:blue](two [deco:dim](three) two) one) trailing",
+            Options {
+                supports_color: false,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result, "leading one two three two one trailing");
+    }
+
+    #[test]
+    fn colorize_no_color_environment_variable() {
+        let source = "leading [fg:red](styled) trailing";
+        let respect = ColorizeOptions {
+            output: Options {
+                respect_no_color: true,
+                ..Options::default()
+            },
+            ..ColorizeOptions::default()
+        };
+        let no_color = || Environment {
+            no_color: true,
+            ..Environment::default()
+        };
+
+        assert_eq!(
+            colorize_in(source, &respect, no_color).unwrap(),
+            "leading styled trailing"
+        );
+        assert_eq!(
+            colorize_in(source, &ColorizeOptions::default(), no_color).unwrap(),
+            "leading \x1b[31mstyled\x1b[39m trailing"
+        );
+        assert!(colorize_in("[fg:red](unclosed", &respect, no_color).is_err());
+
+        assert_eq!(
+            colorize_in(source, &respect, Environment::default).unwrap(),
+            "leading \x1b[31mstyled\x1b[39m trailing"
+        );
+    }
+
+    #[test]
+    fn colorize_with_choice_always() {
+        let result = colorize_with_choice("leading [fg:red](styled)", ColorChoice::Always).unwrap();
+        assert_eq!(result, "leading \x1b[31mstyled\x1b[39m");
+    }
+
+    #[test]
+    fn colorize_with_choice_never() {
+        let result = colorize_with_choice("leading [fg:red](styled)", ColorChoice::Never).unwrap();
+        assert_eq!(result, "leading styled");
+    }
+
+    #[test]
+    fn colorize_with_choice_auto() {
+        let source = "leading [fg:red](styled)";
+        let terminal = Environment {
+            is_terminal: true,
+            ..Environment::default()
+        };
+        let colorize =
+            |environment| colorize_with_choice_in(source, ColorChoice::Auto, &environment);
+
+        assert_eq!(
+            colorize(terminal).unwrap(),
+            "leading \x1b[31mstyled\x1b[39m"
+        );
+        assert_eq!(colorize(Environment::default()).unwrap(), "leading styled");
+        assert_eq!(
+            colorize(Environment {
+                no_color: true,
+                ..terminal
+            })
+            .unwrap(),
+            "leading styled"
+        );
+        assert_eq!(
+            colorize(Environment {
+                clicolor_force: true,
+                ..Environment::default()
+            })
+            .unwrap(),
+            "leading \x1b[31mstyled\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn colorize_auto_reports_errors() {
+        assert!(colorize_auto("[fg:red](unclosed").is_err());
+    }
+
+    #[test]
+    fn colorize_with_choice_reports_errors() {
+        for choice in [ColorChoice::Auto, ColorChoice::Always, ColorChoice::Never] {
+            assert!(colorize_with_choice("[fg:red](unclosed", choice).is_err());
+        }
+    }
+
+    #[test]
+    fn validate_accepts_valid_markup() {
+        for source in [
+            "",
+            "plain text",
+            "[fg:red](error): [deco:bold]([bg:blue](input))",
+            "\\[escaped\\] \x1b[1mraw\x1b[22m",
+        ] {
+            assert_eq!(validate(source), Ok(()), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn validate_reports_the_same_errors_as_colorize() {
+        for source in [
+            "[fg:red](unclosed",
+            "[fg:purple](text)",
+            "[deco:bold](a) ) [bg:](b",
+            "[fg:red;;](text)",
+        ] {
+            let expected = colorize(source, Options::default()).unwrap_err();
+            assert_eq!(validate(source), Err(expected), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn strip_removes_markup_and_escape_sequences() {
+        let result =
+            strip("[fg:red](error): \x1b[1mbad\x1b[22m [deco:bold]([bg:blue](input))").unwrap();
+        assert_eq!(result, "error: bad input");
+        assert!(strip("[fg:red](unclosed").is_err());
+    }
+
+    #[test]
+    fn colorize_escaped_space_keeps_trailing_whitespace() {
+        let result = colorize("[fg:red](hello\\s)\\\n", Options::default()).unwrap();
+        assert_eq!(result, "\x1b[31mhello \x1b[39m");
+    }
+
+    #[test]
+    fn colorize_existing_escape_sequences_are_preserved() {
+        let result = colorize(
+            "\x1b[1mbold\x1b[22m and [fg:red](red \x1b[4munderline\x1b[24m)",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "\x1b[1mbold\x1b[22m and \x1b[31mred \x1b[4munderline\x1b[24;39m"
+        );
+    }
+
+    #[test]
+    fn colorize_existing_escape_sequences_are_stripped() {
+        let result = colorize(
+            "\x1b[1mbold\x1b[22m and [fg:red](red \x1b[4munderline\x1b[24m)",
+            Options {
+                strip_ansi: true,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result, "bold and \x1b[31mred underline\x1b[39m");
+    }
+
+    #[test]
+    fn colorize_existing_escape_sequences_are_stripped_without_color() {
+        let result = colorize(
+            "\x1b[1mbold\x1b[22m and [fg:red](red \x1b[4munderline\x1b[24m)",
+            Options {
+                supports_color: false,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result, "bold and red underline");
+    }
+
+    #[test]
+    fn coalesce_sgr_merges_adjacent_sequences() {
+        assert_eq!(coalesce_sgr("\x1b[31m\x1b[1mabc"), "\x1b[31;1mabc");
+        assert_eq!(coalesce_sgr("\x1b[1m\x1b[2m\x1b[3m"), "\x1b[1;2;3m");
+    }
+
+    #[test]
+    fn coalesce_sgr_does_not_merge_across_text() {
+        assert_eq!(coalesce_sgr("\x1b[31ma\x1b[1m"), "\x1b[31ma\x1b[1m");
+    }
+
+    #[test]
+    fn coalesce_sgr_leaves_other_sequences_alone() {
+        assert_eq!(
+            coalesce_sgr("\x1b[1m\x1b[2K\x1b[22m"),
+            "\x1b[1m\x1b[2K\x1b[22m"
+        );
+        assert_eq!(coalesce_sgr("\x1b]0;title\x07"), "\x1b]0;title\x07");
+        assert_eq!(coalesce_sgr("trailing \x1b["), "trailing \x1b[");
+    }
+
+    #[test]
+    fn coalesce_sgr_drops_replaced_colors() {
+        assert_eq!(coalesce_sgr("\x1b[31m\x1b[39m"), "\x1b[39m");
+        assert_eq!(coalesce_sgr("\x1b[41;1m\x1b[100m"), "\x1b[1;100m");
+        assert_eq!(coalesce_sgr("\x1b[58;5;1m\x1b[59m"), "\x1b[59m");
+        assert_eq!(
+            coalesce_sgr("\x1b[38;2;1;2;3m\x1b[48;5;4;94m"),
+            "\x1b[48;5;4;94m"
+        );
+        // the arguments of an extended color aren't colors themselves
+        assert_eq!(coalesce_sgr("\x1b[31m\x1b[48;5;31m"), "\x1b[31;48;5;31m");
+        // colors are only dropped when sequences are merged
+        assert_eq!(coalesce_sgr("\x1b[31;39m"), "\x1b[31;39m");
+    }
+
+    #[test]
+    fn coalesce_sgr_merged_reset_is_explicit() {
+        assert_eq!(coalesce_sgr("\x1b[m\x1b[31m"), "\x1b[0;31m");
+        assert_eq!(coalesce_sgr("\x1b[m"), "\x1b[m");
+    }
+
+    #[test]
+    fn coalesce_sgr_drops_codes_before_reset() {
+        assert_eq!(coalesce_sgr("\x1b[31m\x1b[0m"), "\x1b[0m");
+        assert_eq!(coalesce_sgr("\x1b[1m\x1b[0;31;44m"), "\x1b[0;31;44m");
+        assert_eq!(coalesce_sgr("\x1b[1m\x1b[m\x1b[31m"), "\x1b[0;31m");
+        // a zero that isn't the first code may belong to an extended color
+        assert_eq!(coalesce_sgr("\x1b[1m\x1b[38;5;0m"), "\x1b[1;38;5;0m");
+    }
+
+    #[test]
+    fn colorize_nested_markup_emits_no_adjacent_sequences() {
+        let result = colorize(
+            "[fg:red]([bg:blue]([deco:bold](a)[deco:dim](b)))",
+            Options::default(),
+        )
+        .unwrap();
+        assert!(!result.contains("m\x1b["), "{result:?}");
+    }
+
+    #[test]
+    fn colorize_with_default_delimiters_matches_colorize() {
+        let source = "leading [fg:red](one [bg:blue](two) \\[one\\]) trailing";
+        assert_eq!(
+            colorize_with(source, &ColorizeOptions::default()),
+            colorize(source, Options::default())
+        );
+    }
+
+    #[test]
+    fn colorize_with_custom_delimiters() {
+        let options = ColorizeOptions {
+            delimiters: Delimiters::BRACES_ANGLE_BRACKETS,
+            ..ColorizeOptions::default()
+        };
+        let result = colorize_with("[x] {fg:red}<(a) {deco:bold}<b>> \\{\\>", &options).unwrap();
+        assert_eq!(result, "[x] \x1b[31m(a) \x1b[1mb\x1b[22;39m {>");
+    }
+
+    #[test]
+    fn colorize_with_custom_delimiters_rejects_default_escapes() {
+        let options = ColorizeOptions {
+            delimiters: Delimiters::BRACES_ANGLE_BRACKETS,
+            ..ColorizeOptions::default()
+        };
+        let errors = colorize_with("\\[", &options).unwrap_err();
+        assert_eq!(errors[0].reason, Reason::UnknownEscapeSequence('['));
+    }
+
+    #[test]
+    fn colorize_with_custom_delimiters_reports_unescaped_delimiters() {
+        let options = ColorizeOptions {
+            delimiters: Delimiters::BRACES_ANGLE_BRACKETS,
+            ..ColorizeOptions::default()
+        };
+        let errors = colorize_with("a > b", &options).unwrap_err();
+        assert_eq!(errors[0].reason, Reason::UnescapedControlCharacter('>'));
+    }
+
+    #[test]
+    fn colorize_with_custom_delimiters_without_color() {
+        let options = ColorizeOptions {
+            output: Options {
+                supports_color: false,
+                ..Options::default()
+            },
+            delimiters: Delimiters::BRACES_ANGLE_BRACKETS,
+            ..ColorizeOptions::default()
+        };
+        let result = colorize_with("{fg:red}<[a]>", &options).unwrap();
+        assert_eq!(result, "[a]");
+    }
+
+    #[test]
+    fn colorize_many_sticky_styles() {
+        let source = "[fg:red]a [fg:blue]b ".repeat(100);
+        let result = colorize(&source, Options::default()).unwrap();
+        assert!(
+            result.starts_with("\x1b[31ma \x1b[34mb \x1b[31ma "),
+            "{result:?}"
+        );
+        assert!(result.ends_with("\x1b[34mb \x1b[39m"), "{result:?}");
+    }
+
+    #[test]
+    fn colorize_with_max_depth() {
+        let options = ColorizeOptions {
+            max_depth: 1,
+            ..ColorizeOptions::default()
+        };
+        assert_eq!(
+            colorize_with("[fg:red](a) [bg:red](b)", &options).unwrap(),
+            "\x1b[31ma\x1b[39m \x1b[41mb\x1b[49m"
+        );
+
+        let errors = colorize_with("[fg:red](a [bg:red](b))", &options).unwrap_err();
+        assert_eq!(errors[0].reason, Reason::NestingTooDeep(1));
+    }
+
+    #[test]
+    fn colorize_with_unlimited_depth_handles_deep_nesting() {
+        const DEPTH: usize = 20_000;
+        let options = ColorizeOptions {
+            max_depth: usize::MAX,
+            ..ColorizeOptions::default()
+        };
+
+        // each level has its own content, so the levels can't be merged together
+        let source = format!("{}{}", "[fg:red](a".repeat(DEPTH), ")".repeat(DEPTH));
+        let result = colorize_with(&source, &options).unwrap();
+        assert!(result.starts_with("\x1b[31maaa"), "{:?}", &result[..20]);
+        assert!(result.ends_with("a\x1b[39m"));
+
+        let source = format!("{}a{}", "[fg:red](".repeat(DEPTH), ")".repeat(DEPTH));
+        assert_eq!(
+            colorize_with(&source, &options).unwrap(),
+            "\x1b[31ma\x1b[39m"
+        );
+
+        let without_color = ColorizeOptions {
+            output: Options {
+                supports_color: false,
+                suppress_colors: true,
+                ..Options::default()
+            },
+            ..options
+        };
+        assert_eq!(
+            colorize_with(&"[fg:red](a".repeat(DEPTH), &without_color).map_err(|e| e.len()),
+            Err(1)
+        );
+        let source = format!("{}{}", "[fg:red](a".repeat(DEPTH), ")".repeat(DEPTH));
+        assert_eq!(
+            colorize_with(&source, &without_color).unwrap(),
+            "a".repeat(DEPTH)
+        );
+    }
+
+    #[test]
+    fn colorize_with_markdown_emphasis() {
+        let options = ColorizeOptions {
+            markdown_emphasis: true,
+            ..ColorizeOptions::default()
+        };
+        assert_eq!(
+            colorize_with("a *b* **c *d* ** [fg:red](*e*)", &options).unwrap(),
+            "a \x1b[3mb\x1b[23m \x1b[1mc \x1b[3md\x1b[23m \x1b[22m \x1b[31;3me\x1b[39;23m"
+        );
+    }
+
+    #[test]
+    fn colorize_with_markdown_emphasis_escaped_asterisks() {
+        let options = ColorizeOptions {
+            markdown_emphasis: true,
+            ..ColorizeOptions::default()
+        };
+        assert_eq!(colorize_with("2 \\* 3 = 6", &options).unwrap(), "2 * 3 = 6");
+
+        let errors = colorize_with("2 * 3 = 6", &options).unwrap_err();
+        assert_eq!(errors[0].reason, Reason::UnterminatedEmphasis);
+    }
+
+    #[test]
+    fn colorize_without_markdown_emphasis_keeps_asterisks() {
+        assert_eq!(
+            colorize("*a* **b** [fg:red](*c*)", Options::default()).unwrap(),
+            "*a* **b** \x1b[31m*c*\x1b[39m"
+        );
+
+        let errors = colorize("\\*", Options::default()).unwrap_err();
+        assert_eq!(errors[0].reason, Reason::UnknownEscapeSequence('*'));
+    }
+
+    #[test]
+    fn colorize_with_markdown_emphasis_and_asterisk_delimiter() {
+        let options = ColorizeOptions {
+            delimiters: Delimiters::new('*', '#', '<', '>').unwrap(),
+            markdown_emphasis: true,
+            ..ColorizeOptions::default()
+        };
+        assert_eq!(
+            colorize_with("*fg:red#<a>", &options).unwrap(),
+            "\x1b[31ma\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn colorize_raw() {
+        assert_eq!(
+            colorize(
+                "[raw](literal [fg:red] stays) [fg:red;raw]([deco:bold](\\x41)) [raw](\\))",
+                Options::default()
+            )
+            .unwrap(),
+            "literal [fg:red] stays \x1b[31m[deco:bold](\\x41)\x1b[39m )"
+        );
+    }
+
+    #[test]
+    fn colorize_raw_with_custom_delimiters() {
+        let options = ColorizeOptions {
+            delimiters: Delimiters::BRACES_ANGLE_BRACKETS,
+            ..ColorizeOptions::default()
+        };
+        assert_eq!(
+            colorize_with("{raw}<[fg:red](a) {b} <c> \\>>", &options).unwrap(),
+            "[fg:red](a) {b} <c> >"
+        );
+    }
+
+    #[test]
+    fn colorize_suppress_blink() {
+        let options = Options {
+            suppress_blink: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            colorize(
+                "[deco:slow-blink,bold](a [deco:fast-blink;fg:red](b)) [deco:slow-blink](c)",
+                options
+            )
+            .unwrap(),
+            "\x1b[1ma \x1b[31mb\x1b[39;22m c"
+        );
+    }
+
+    #[test]
+    fn colorize_suppress_blink_in_full_reset_mode() {
+        let options = Options {
+            suppress_blink: true,
+            reset_mode: ResetMode::Full,
+            ..Options::default()
+        };
+        assert_eq!(
+            colorize("[deco:italic,fast-blink](a [fg:red](b)) c", options).unwrap(),
+            "\x1b[3ma \x1b[31mb\x1b[0m c"
+        );
+    }
+
+    #[test]
+    fn colorize_keeps_blink_by_default() {
+        assert_eq!(
+            colorize("[deco:slow-blink](a)", Options::default()).unwrap(),
+            "\x1b[5ma\x1b[25m"
+        );
+    }
+
+    #[test]
+    fn colorize_suppress_decorations() {
+        let options = Options {
+            suppress_decorations: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            colorize("[fg:red;deco:slow-blink](x)", options).unwrap(),
+            "\x1b[31mx\x1b[39m"
+        );
+        assert_eq!(
+            colorize("[deco:bold](a [bg:blue;deco:italic](b))", options).unwrap(),
+            "a \x1b[44mb\x1b[49m"
+        );
+    }
+
+    #[test]
+    fn colorize_suppress_colors() {
+        let options = Options {
+            suppress_colors: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            colorize("[fg:red;deco:slow-blink](x)", options).unwrap(),
+            "\x1b[5mx\x1b[25m"
+        );
+        assert_eq!(
+            colorize("[fg:red;bg:blue;ul:green](a [deco:underline](b))", options).unwrap(),
+            "a \x1b[4mb\x1b[24m"
+        );
+    }
+
+    #[test]
+    fn colorize_suppress_colors_and_decorations_keeps_resets() {
+        let options = Options {
+            suppress_colors: true,
+            suppress_decorations: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            colorize("[fg:red;deco:bold](a [reset](b))", options).unwrap(),
+            "a \x1b[0mb\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn colorize_reopen_on_newline() {
+        let options = Options {
+            reopen_on_newline: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            colorize("[fg:red;deco:bold](one\ntwo\nthree) four\nfive", options).unwrap(),
+            "\x1b[31;1mone\x1b[0m\n\x1b[31;1mtwo\x1b[0m\n\x1b[31;1mthree\x1b[39;22m four\nfive"
+        );
+    }
+
+    #[test]
+    fn colorize_reopen_on_newline_nested() {
+        let options = Options {
+            reopen_on_newline: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            colorize("[bg:blue](a [fg:red](b\nc)\nd)", options).unwrap(),
+            "\x1b[44ma \x1b[31mb\x1b[0m\n\x1b[31;44mc\x1b[0m\n\x1b[44md\x1b[49m"
+        );
+    }
+
+    #[test]
+    fn colorize_reopen_on_newline_keeps_carriage_returns_together() {
+        let options = Options {
+            reopen_on_newline: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            colorize("[fg:red](a\r\nb)", options).unwrap(),
+            "\x1b[31ma\x1b[0m\r\n\x1b[31mb\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn colorize_newlines_not_reopened_by_default() {
+        assert_eq!(
+            colorize("[fg:red](a\nb)", Options::default()).unwrap(),
+            "\x1b[31ma\nb\x1b[39m"
+        );
+    }
+
+    /// Markup fragments that are combined at random to produce adversarial input
+    const FRAGMENTS: &[&str] = &[
+        "[",
+        "]",
+        "(",
+        ")",
+        ":",
+        ";",
+        ",",
+        " ",
+        "\n",
+        "\\",
+        "\\x",
+        "\\u{",
+        "}",
+        "#",
+        "fg",
+        "bg",
+        "ul",
+        "deco",
+        "reset",
+        "raw",
+        "red",
+        "bright-",
+        "bright_",
+        "gray",
+        "default",
+        "none",
+        "bold",
+        "dim",
+        "blink-slow",
+        "strike-through",
+        "text",
+        "é",
+        "\\x1b",
+        "\x1b[31m",
+    ];
+
+    /// Markup made of random fragments, which is much more likely to contain markup than arbitrary
+    /// strings are
+    fn markup() -> impl Strategy<Value = String> {
+        prop::collection::vec(prop::sample::select(FRAGMENTS), 0..24)
+            .prop_map(|fragments| fragments.concat())
+    }
+
+    /// Check that every property set by an SGR sequence in the output is undone by the end
+    fn assert_balanced(source: &str, output: &str) {
+        // foreground, background, underline color, then the reset code of each decoration
+        let mut active = std::collections::HashMap::<u8, u8>::new();
+
+        for sequence in output.split('\x1b').skip(1) {
+            let codes = sequence
+                .strip_prefix('[')
+                .and_then(|s| s.split_once('m'))
+                .map(|(codes, _)| codes)
+                .unwrap_or_else(|| panic!("{source:?} produced malformed {output:?}"));
+
+            let mut codes = codes.split(';').map(|code| code.parse::<u8>().unwrap());
+            while let Some(code) = codes.next() {
+                match code {
+                    0 => active.clear(),
+                    30..=37 | 90..=97 => drop(active.insert(39, code)),
+                    40..=47 | 100..=107 => drop(active.insert(49, code)),
+                    // underline colors are followed by the palette and the index of the color
+                    58 => drop(active.insert(59, codes.nth(1).unwrap())),
+                    1 | 2 => drop(active.insert(code + 100, 22)),
+                    3..=9 => {
+                        let reset = if code == 6 { 25 } else { code + 20 };
+                        active.insert(code + 100, reset);
+                    }
+                    39 | 49 | 59 => drop(active.remove(&code)),
+                    22..=29 => {
+                        active.retain(|key, reset| matches!(*key, 39 | 49 | 59) || *reset != code)
+                    }
+                    _ => panic!("{source:?} produced unexpected code {code} in {output:?}"),
+                }
+            }
+        }
+
+        assert!(
+            active.is_empty(),
+            "{source:?} left {active:?} active in {output:?}"
+        );
+    }
+
+    /// Colorize the source using each reset mode, checking that it never panics and that the
+    /// output always balances
+    fn check_colorize(source: &str) {
+        for (reset_mode, minimize) in [
+            (ResetMode::Targeted, false),
+            (ResetMode::Full, false),
+            (ResetMode::Full, true),
+        ] {
+            let options = Options {
+                reset_mode,
+                minimize,
+                ..Options::default()
+            };
+
+            // escape characters in the source, whether written directly or escaped, are passed
+            // through as-is, so only markup that doesn't contain any can be expected to balance
+            if let Ok(output) = colorize(source, options) {
+                let escaped = strip(source).is_ok_and(|text| text.contains('\x1b'));
+                if !source.contains('\x1b') && !escaped {
+                    assert_balanced(source, &output);
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(5_000))]
+
+        #[test]
+        fn colorize_markup_never_panics_and_balances(source in markup()) {
+            check_colorize(&source);
+        }
+
+        #[test]
+        fn colorize_arbitrary_input_never_panics_and_balances(source in any::<String>()) {
+            check_colorize(&source);
+        }
     }
 }
@@ -0,0 +1,311 @@
+// the attribute mapping is platform independent so it can be tested anywhere, but is only used by
+// the writer on Windows
+#![cfg_attr(not(windows), allow(dead_code))]
+
+use crate::ast::{Color, CurrentStyle, Decoration};
+
+const FOREGROUND_BLUE: u16 = 0x0001;
+const FOREGROUND_GREEN: u16 = 0x0002;
+const FOREGROUND_RED: u16 = 0x0004;
+const FOREGROUND_INTENSITY: u16 = 0x0008;
+const FOREGROUND_MASK: u16 = 0x000f;
+const BACKGROUND_MASK: u16 = 0x00f0;
+const COMMON_LVB_REVERSE_VIDEO: u16 = 0x4000;
+const COMMON_LVB_UNDERSCORE: u16 = 0x8000;
+
+/// The attributes used when the console's attributes can't be determined (light gray on black)
+const DEFAULT_ATTRIBUTES: u16 = FOREGROUND_RED | FOREGROUND_GREEN | FOREGROUND_BLUE;
+
+/// Convert a color to its foreground attribute bits
+///
+/// Returns `None` for [`Color::Default`], which keeps the console's existing color.
+fn color_bits(color: Color) -> Option<u16> {
+    let bits = match color {
+        Color::Default => return None,
+        Color::Black => 0,
+        Color::Red => FOREGROUND_RED,
+        Color::Green => FOREGROUND_GREEN,
+        Color::Yellow => FOREGROUND_RED | FOREGROUND_GREEN,
+        Color::Blue => FOREGROUND_BLUE,
+        Color::Magenta => FOREGROUND_RED | FOREGROUND_BLUE,
+        Color::Cyan => FOREGROUND_GREEN | FOREGROUND_BLUE,
+        Color::White => FOREGROUND_RED | FOREGROUND_GREEN | FOREGROUND_BLUE,
+        Color::BrightBlack => FOREGROUND_INTENSITY,
+        Color::BrightRed => FOREGROUND_RED | FOREGROUND_INTENSITY,
+        Color::BrightGreen => FOREGROUND_GREEN | FOREGROUND_INTENSITY,
+        Color::BrightYellow => FOREGROUND_RED | FOREGROUND_GREEN | FOREGROUND_INTENSITY,
+        Color::BrightBlue => FOREGROUND_BLUE | FOREGROUND_INTENSITY,
+        Color::BrightMagenta => FOREGROUND_RED | FOREGROUND_BLUE | FOREGROUND_INTENSITY,
+        Color::BrightCyan => FOREGROUND_GREEN | FOREGROUND_BLUE | FOREGROUND_INTENSITY,
+        Color::BrightWhite => {
+            FOREGROUND_RED | FOREGROUND_GREEN | FOREGROUND_BLUE | FOREGROUND_INTENSITY
+        }
+    };
+    Some(bits)
+}
+
+/// Convert a style to console attributes
+///
+/// The `default` attributes provide the colors used by [`Color::Default`]. The console API supports
+/// far fewer decorations than ANSI escape codes, so some are approximated:
+///
+/// - bold brightens the foreground color
+/// - underline and invert map to their equivalent attributes
+/// - hide sets the foreground color to the background color
+/// - dim, italic, blinking, and strike-through are ignored
+fn attributes(style: &CurrentStyle, default: u16) -> u16 {
+    let mut foreground = color_bits(style.foreground()).unwrap_or(default & FOREGROUND_MASK);
+    let background = color_bits(style.background())
+        .map(|bits| bits << 4)
+        .unwrap_or(default & BACKGROUND_MASK);
+    let mut extra = 0;
+
+    for decoration in style.decoration() {
+        match decoration {
+            Decoration::Bold => foreground |= FOREGROUND_INTENSITY,
+            Decoration::Underline => extra |= COMMON_LVB_UNDERSCORE,
+            Decoration::Invert => extra |= COMMON_LVB_REVERSE_VIDEO,
+            Decoration::Hide
+            | Decoration::Dim
+            | Decoration::Italic
+            | Decoration::SlowBlink
+            | Decoration::FastBlink
+            | Decoration::StrikeThrough => {}
+        }
+    }
+
+    if style.decoration().contains(&Decoration::Hide) {
+        foreground = background >> 4;
+    }
+
+    foreground | background | extra
+}
+
+#[cfg(windows)]
+mod writer {
+    use super::{attributes, DEFAULT_ATTRIBUTES};
+    use crate::{
+        ast::{CurrentStyle, Token},
+        error::Error,
+        parser::Parser,
+    };
+    use std::{
+        io::{self, Write},
+        mem,
+    };
+    use windows_sys::Win32::System::Console::{
+        GetConsoleScreenBufferInfo, GetStdHandle, SetConsoleTextAttribute,
+        CONSOLE_SCREEN_BUFFER_INFO, STD_OUTPUT_HANDLE,
+    };
+
+    /// Write styled markup to stdout using the Windows console API instead of ANSI escape codes
+    ///
+    /// This supports legacy consoles that don't interpret escape codes. See the console attribute
+    /// mapping for how styles are approximated. Any ANSI escape sequences present in the source are
+    /// dropped. If stdout is not a console, the text is written without styling. The console's
+    /// attributes are restored once all the text is written.
+    ///
+    /// # Panics
+    ///
+    /// Like [`print!`], this panics if writing to stdout fails.
+    pub fn write_colorized_console(source: &str) -> Result<(), Vec<Error>> {
+        let (tokens, errors) = Parser::new(source).parse();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let default = console_attributes();
+
+        let mut stdout = io::stdout().lock();
+        convert_tokens(&mut stdout, default, &tokens);
+        stdout.flush().expect("failed to write to stdout");
+        set_raw_attributes(default);
+
+        Ok(())
+    }
+
+    /// Write the tokens to the console, changing the attributes for each styled piece of text
+    ///
+    /// The tokens are walked with an explicit stack, so arbitrarily deep nesting can't overflow the
+    /// call stack. Each level tracks the style its text is written with, so the parent's attributes
+    /// can be restored once it is finished.
+    fn convert_tokens(stdout: &mut io::StdoutLock, default: u16, tokens: &[Token]) {
+        let mut stack = vec![(tokens.iter(), CurrentStyle::default())];
+        while let Some((tokens, parent_style)) = stack.last_mut() {
+            match tokens.next() {
+                Some(Token::Content(content, _)) => stdout
+                    .write_all(content.as_bytes())
+                    .expect("failed to write to stdout"),
+                Some(Token::Raw(..)) => {}
+                Some(Token::Styled { content, style, .. }) => {
+                    if content.is_empty() {
+                        continue;
+                    }
+
+                    let current = parent_style.extend(style);
+                    set_attributes(stdout, default, &current);
+                    stack.push((content.iter(), current));
+                }
+                None => {
+                    stack.pop();
+                    if let Some((_, parent_style)) = stack.last() {
+                        set_attributes(stdout, default, parent_style);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Change the attributes of any text written after this point
+    fn set_attributes(stdout: &mut io::StdoutLock, default: u16, style: &CurrentStyle) {
+        // any buffered text must be written using the previous attributes
+        stdout.flush().expect("failed to write to stdout");
+        set_raw_attributes(attributes(style, default));
+    }
+
+    /// Replace the console's attributes
+    fn set_raw_attributes(attributes: u16) {
+        // SAFETY: the handle is only used for the duration of the call and failures are ignored
+        unsafe {
+            let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+            SetConsoleTextAttribute(handle, attributes);
+        }
+    }
+
+    /// Get the console's current attributes, falling back to the defaults if stdout is not a console
+    fn console_attributes() -> u16 {
+        // SAFETY: the buffer info is plain data that is only read if the call succeeds
+        unsafe {
+            let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+            let mut info: CONSOLE_SCREEN_BUFFER_INFO = mem::zeroed();
+            if GetConsoleScreenBufferInfo(handle, &mut info) != 0 {
+                info.wAttributes
+            } else {
+                DEFAULT_ATTRIBUTES
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+pub use writer::write_colorized_console;
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        attributes, color_bits, COMMON_LVB_REVERSE_VIDEO, COMMON_LVB_UNDERSCORE,
+        DEFAULT_ATTRIBUTES, FOREGROUND_BLUE, FOREGROUND_GREEN, FOREGROUND_INTENSITY,
+        FOREGROUND_RED,
+    };
+    use crate::ast::{Color, CurrentStyle};
+
+    fn style_attributes(style: crate::ast::Style) -> u16 {
+        attributes(&CurrentStyle::from(style), DEFAULT_ATTRIBUTES)
+    }
+
+    #[test]
+    fn default_color_has_no_bits() {
+        assert_eq!(color_bits(Color::Default), None);
+    }
+
+    #[test]
+    fn standard_colors() {
+        assert_eq!(color_bits(Color::Black), Some(0));
+        assert_eq!(color_bits(Color::Red), Some(FOREGROUND_RED));
+        assert_eq!(
+            color_bits(Color::Cyan),
+            Some(FOREGROUND_GREEN | FOREGROUND_BLUE)
+        );
+        assert_eq!(
+            color_bits(Color::White),
+            Some(FOREGROUND_RED | FOREGROUND_GREEN | FOREGROUND_BLUE)
+        );
+    }
+
+    #[test]
+    fn bright_colors_are_intense() {
+        assert_eq!(color_bits(Color::BrightBlack), Some(FOREGROUND_INTENSITY));
+        assert_eq!(
+            color_bits(Color::BrightYellow),
+            Some(FOREGROUND_RED | FOREGROUND_GREEN | FOREGROUND_INTENSITY)
+        );
+    }
+
+    #[test]
+    fn named_colors_are_distinct() {
+        let mut bits = Color::ALL
+            .iter()
+            .filter_map(|&color| color_bits(color))
+            .collect::<Vec<_>>();
+        bits.sort_unstable();
+        bits.dedup();
+        assert_eq!(bits, (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unstyled_uses_default() {
+        assert_eq!(style_attributes(style!()), DEFAULT_ATTRIBUTES);
+    }
+
+    #[test]
+    fn foreground() {
+        assert_eq!(style_attributes(style!(fg: Red;)), FOREGROUND_RED);
+    }
+
+    #[test]
+    fn background() {
+        assert_eq!(
+            style_attributes(style!(bg: Blue;)),
+            DEFAULT_ATTRIBUTES | FOREGROUND_BLUE << 4
+        );
+    }
+
+    #[test]
+    fn foreground_and_background() {
+        assert_eq!(
+            style_attributes(style!(fg: BrightGreen; bg: Red;)),
+            FOREGROUND_GREEN | FOREGROUND_INTENSITY | FOREGROUND_RED << 4
+        );
+    }
+
+    #[test]
+    fn default_colors_use_existing_attributes() {
+        let existing = FOREGROUND_GREEN | FOREGROUND_BLUE << 4;
+        let style = CurrentStyle::from(style!(fg: Default; bg: Default;));
+        assert_eq!(attributes(&style, existing), existing);
+    }
+
+    #[test]
+    fn bold_brightens_foreground() {
+        assert_eq!(
+            style_attributes(style!(fg: Red; deco: Bold;)),
+            FOREGROUND_RED | FOREGROUND_INTENSITY
+        );
+    }
+
+    #[test]
+    fn underline_and_invert() {
+        assert_eq!(
+            style_attributes(style!(fg: Red; deco: Underline, Invert;)),
+            FOREGROUND_RED | COMMON_LVB_UNDERSCORE | COMMON_LVB_REVERSE_VIDEO
+        );
+    }
+
+    #[test]
+    fn hide_matches_background() {
+        assert_eq!(
+            style_attributes(style!(fg: Red; bg: Blue; deco: Hide;)),
+            FOREGROUND_BLUE | FOREGROUND_BLUE << 4
+        );
+    }
+
+    #[test]
+    fn unsupported_decorations_are_ignored() {
+        assert_eq!(
+            style_attributes(
+                style!(fg: Red; deco: Dim, Italic, SlowBlink, FastBlink, StrikeThrough;)
+            ),
+            FOREGROUND_RED
+        );
+    }
+}
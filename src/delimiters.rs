@@ -0,0 +1,184 @@
+use crate::lexer::SyntaxKind;
+use std::borrow::Cow;
+
+/// The characters that surround the style specifiers and content of styled markup
+///
+/// By default, style specifiers are wrapped in square brackets and content is wrapped in
+/// parentheses (i.e. `[fg:red](content)`). Only the configured delimiters and the backslash are
+/// control characters, so only they need to be (or can be) escaped. For example, with
+/// [`Delimiters::BRACES_ANGLE_BRACKETS`], `{fg:red}<a [literal] (text)>` is valid markup, while
+/// `\{`, `\}`, `\<`, and `\>` escape the delimiters.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Delimiters {
+    specifier_open: char,
+    specifier_close: char,
+    content_open: char,
+    content_close: char,
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Self::SQUARE_BRACKETS_PARENTHESES
+    }
+}
+
+impl Delimiters {
+    /// Style specifiers in `[ ]` and content in `( )`, the default
+    pub const SQUARE_BRACKETS_PARENTHESES: Self = Self {
+        specifier_open: '[',
+        specifier_close: ']',
+        content_open: '(',
+        content_close: ')',
+    };
+
+    /// Style specifiers in `{ }` and content in `< >`
+    pub const BRACES_ANGLE_BRACKETS: Self = Self {
+        specifier_open: '{',
+        specifier_close: '}',
+        content_open: '<',
+        content_close: '>',
+    };
+
+    /// Create a custom set of delimiters
+    ///
+    /// Each delimiter must be a distinct ASCII punctuation character, excluding those already used
+    /// by the markup syntax (`\`, `:`, `;`, `,`, and `-`). Returns `None` if the delimiters are
+    /// invalid.
+    pub fn new(
+        specifier_open: char,
+        specifier_close: char,
+        content_open: char,
+        content_close: char,
+    ) -> Option<Self> {
+        let all = [specifier_open, specifier_close, content_open, content_close];
+        let valid =
+            |c: &char| c.is_ascii_punctuation() && !matches!(c, '\\' | ':' | ';' | ',' | '-');
+
+        let distinct = all
+            .iter()
+            .enumerate()
+            .all(|(i, c)| !all[i + 1..].contains(c));
+
+        (distinct && all.iter().all(valid)).then_some(Self {
+            specifier_open,
+            specifier_close,
+            content_open,
+            content_close,
+        })
+    }
+
+    /// The characters that open and close a style specifier
+    pub fn specifier(&self) -> (char, char) {
+        (self.specifier_open, self.specifier_close)
+    }
+
+    /// The characters that open and close the content
+    pub fn content(&self) -> (char, char) {
+        (self.content_open, self.content_close)
+    }
+
+    /// Get the syntax item a character represents, if it is a delimiter
+    pub(crate) fn kind(&self, character: char) -> Option<SyntaxKind> {
+        match character {
+            c if c == self.specifier_open => Some(SyntaxKind::SquareBracketOpen),
+            c if c == self.specifier_close => Some(SyntaxKind::SquareBracketClose),
+            c if c == self.content_open => Some(SyntaxKind::ParenthesisOpen),
+            c if c == self.content_close => Some(SyntaxKind::ParenthesisClose),
+            _ => None,
+        }
+    }
+
+    /// Get the character for a delimiter syntax item
+    pub(crate) fn character(&self, kind: SyntaxKind) -> Option<char> {
+        match kind {
+            SyntaxKind::SquareBracketOpen => Some(self.specifier_open),
+            SyntaxKind::SquareBracketClose => Some(self.specifier_close),
+            SyntaxKind::ParenthesisOpen => Some(self.content_open),
+            SyntaxKind::ParenthesisClose => Some(self.content_close),
+            _ => None,
+        }
+    }
+
+    /// Get the name of a syntax item, using the delimiter characters where applicable
+    pub(crate) fn name(&self, kind: SyntaxKind) -> Cow<'static, str> {
+        match self.character(kind) {
+            Some(character) => Cow::Owned(character.to_string()),
+            None => Cow::Borrowed(kind.name()),
+        }
+    }
+
+    /// Whether the character can follow a backslash in an escape sequence
+    pub(crate) fn is_escapable(&self, character: char) -> bool {
+        character == '\\' || self.kind(character).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Delimiters;
+    use crate::lexer::SyntaxKind;
+
+    #[test]
+    fn default_is_square_brackets_and_parentheses() {
+        let delimiters = Delimiters::default();
+        assert_eq!(delimiters.specifier(), ('[', ']'));
+        assert_eq!(delimiters.content(), ('(', ')'));
+    }
+
+    #[test]
+    fn new_matches_constants() {
+        assert_eq!(
+            Delimiters::new('[', ']', '(', ')'),
+            Some(Delimiters::SQUARE_BRACKETS_PARENTHESES)
+        );
+        assert_eq!(
+            Delimiters::new('{', '}', '<', '>'),
+            Some(Delimiters::BRACES_ANGLE_BRACKETS)
+        );
+    }
+
+    #[test]
+    fn new_rejects_repeated_characters() {
+        assert_eq!(Delimiters::new('|', '|', '<', '>'), None);
+        assert_eq!(Delimiters::new('{', '}', '{', '>'), None);
+    }
+
+    #[test]
+    fn new_rejects_syntax_characters() {
+        for character in ['\\', ':', ';', ',', '-', 'a', '1', ' ', '\x1b', '«'] {
+            assert_eq!(
+                Delimiters::new(character, '}', '<', '>'),
+                None,
+                "{character:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn kind_and_character_round_trip() {
+        let delimiters = Delimiters::BRACES_ANGLE_BRACKETS;
+        for character in ['{', '}', '<', '>'] {
+            let kind = delimiters.kind(character).unwrap();
+            assert_eq!(delimiters.character(kind), Some(character));
+        }
+
+        assert_eq!(delimiters.kind('['), None);
+        assert_eq!(delimiters.character(SyntaxKind::Text), None);
+    }
+
+    #[test]
+    fn names_use_delimiter_characters() {
+        let delimiters = Delimiters::BRACES_ANGLE_BRACKETS;
+        assert_eq!(delimiters.name(SyntaxKind::ParenthesisClose), ">");
+        assert_eq!(delimiters.name(SyntaxKind::Color), "color");
+    }
+
+    #[test]
+    fn escapable_characters() {
+        let delimiters = Delimiters::BRACES_ANGLE_BRACKETS;
+        assert!(delimiters.is_escapable('\\'));
+        assert!(delimiters.is_escapable('<'));
+        assert!(!delimiters.is_escapable('('));
+        assert!(!delimiters.is_escapable('n'));
+    }
+}
@@ -1,31 +1,59 @@
-use crate::lexer::SyntaxKind;
+use crate::{
+    ast::{Color, Decoration},
+    delimiters::Delimiters,
+    lexer::SyntaxKind,
+};
 use codespan_reporting::{
     diagnostic::{Diagnostic, Label},
     files::{Error as CodespanError, SimpleFile},
     term::Config,
 };
-use std::io;
+use std::{
+    fmt::{self, Display, Write},
+    io,
+    ops::Range,
+};
 use termcolor::{Buffer, WriteColor};
 use text_size::{TextLen, TextRange};
 
 /// A report of all the issues found with a piece of text
 #[derive(Clone, Debug)]
-pub struct ErrorReport(Vec<Error>);
+pub struct ErrorReport {
+    errors: Vec<Error>,
+    delimiters: Delimiters,
+}
 
 impl From<Vec<Error>> for ErrorReport {
     fn from(errors: Vec<Error>) -> Self {
-        Self(errors)
+        Self {
+            errors,
+            delimiters: Delimiters::default(),
+        }
     }
 }
 
 impl ErrorReport {
+    /// Describe the errors using the delimiters the text was parsed with
+    pub fn with_delimiters(mut self, delimiters: Delimiters) -> Self {
+        self.delimiters = delimiters;
+        self
+    }
+
     /// Get the contained errors
     pub fn errors(&self) -> &[Error] {
-        self.0.as_slice()
+        self.errors.as_slice()
     }
 
     /// Emit the report to a string
-    pub fn emit(&self, file: &str, source: &str, supports_color: bool) -> io::Result<String> {
+    ///
+    /// Without a file name, the source is referred to as `<string>`, like Python does for code that
+    /// didn't come from a file.
+    pub fn emit(
+        &self,
+        file: Option<&str>,
+        source: &str,
+        supports_color: bool,
+    ) -> io::Result<String> {
         let mut buffer = if supports_color {
             Buffer::ansi()
         } else {
@@ -38,8 +66,13 @@ impl ErrorReport {
     }
 
     /// Emit the report to the specified output
-    pub fn emit_to(&self, file: &str, source: &str, output: &mut dyn WriteColor) -> io::Result<()> {
-        let file = SimpleFile::new(file, source);
+    pub fn emit_to(
+        &self,
+        file: Option<&str>,
+        source: &str,
+        output: &mut dyn WriteColor,
+    ) -> io::Result<()> {
+        let file = SimpleFile::new(FileName(file), source);
         let config = Config::default();
 
         let eof = {
@@ -48,12 +81,10 @@ impl ErrorReport {
         };
 
         for error in self.errors() {
-            if let Err(err) = codespan_reporting::term::emit(
-                output,
-                &config,
-                &file,
-                &error.to_diagnostic((), eof),
-            ) {
+            let mut diagnostic = error.to_diagnostic_with((), eof, &self.delimiters);
+            diagnostic.notes.extend(error.suggestion(source));
+
+            if let Err(err) = codespan_reporting::term::emit(output, &config, &file, &diagnostic) {
                 match err {
                     CodespanError::Io(e) => return Err(e),
                     _ => panic!("reporting failed: {err:?}"),
@@ -63,12 +94,108 @@ impl ErrorReport {
 
         Ok(())
     }
+
+    /// Emit the report as a JSON array for use by other tools
+    ///
+    /// Each error is an object containing a human-readable `message`, a stable `code` identifying
+    /// the kind of error, the `file`, the byte `span` (with `start` and `end`), the 1-based `line`
+    /// and `column` where the error starts, and the kind of syntax item the parser was `at`. The
+    /// `file` is `null` when no file name is given, and the `line` and `column` are `null` when the
+    /// span is not within the source.
+    pub fn emit_json(&self, file: Option<&str>, source: &str) -> String {
+        let eof = {
+            let length = source.text_len();
+            TextRange::new(length, length)
+        };
+
+        let mut output = String::from("[");
+        for (i, error) in self.errors().iter().enumerate() {
+            if i > 0 {
+                output.push(',');
+            }
+
+            let message = self.message(error, eof);
+
+            let span = error.span.unwrap_or(eof);
+            // the location can't be determined when the error came from a different source
+            let (line, column) = match error.line_col(source) {
+                Some((line, column)) => (line.to_string(), column.to_string()),
+                None => (String::from("null"), String::from("null")),
+            };
+
+            output.push_str("{\"message\":");
+            push_json_string(&mut output, &message);
+            output.push_str(",\"code\":");
+            push_json_string(&mut output, error.reason.code());
+            output.push_str(",\"file\":");
+            match file {
+                Some(file) => push_json_string(&mut output, file),
+                None => output.push_str("null"),
+            }
+            write!(
+                output,
+                ",\"span\":{{\"start\":{},\"end\":{}}},\"line\":{},\"column\":{},\"at\":",
+                u32::from(span.start()),
+                u32::from(span.end()),
+                line,
+                column,
+            )
+            .expect("writing to a string cannot fail");
+            push_json_string(&mut output, error.at.name());
+            output.push('}');
+        }
+
+        output.push(']');
+        output
+    }
+
+    /// Describe an error on a single line, combining the diagnostic's message with its labels
+    pub(crate) fn message(&self, error: &Error, eof: TextRange) -> String {
+        let diagnostic = error.to_diagnostic_with((), eof, &self.delimiters);
+        let labels = diagnostic
+            .labels
+            .iter()
+            .map(|label| label.message.as_str())
+            .collect::<Vec<_>>();
+        format!("{}: {}", diagnostic.message, labels.join(", "))
+    }
+}
+
+/// The name of the file a report is about, if it has one
+#[derive(Clone, Copy, Debug)]
+struct FileName<'a>(Option<&'a str>);
+
+impl Display for FileName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.unwrap_or("<string>"))
+    }
+}
+
+/// Append a string to the output as a quoted and escaped JSON string
+fn push_json_string(output: &mut String, value: &str) {
+    output.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => {
+                write!(output, "\\u{:04x}", c as u32).expect("writing to a string cannot fail")
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
 }
 
 /// An error that occurred while parsing
 #[derive(Clone, Debug)]
 #[cfg_attr(test, derive(Eq, PartialEq))]
 pub struct Error {
+    /// Where the error occurred, which is an empty range at the end of the source for errors at the
+    /// end of the input
     pub span: Option<TextRange>,
     pub at: SyntaxKind,
     pub reason: Reason,
@@ -77,6 +204,19 @@ pub struct Error {
 impl Error {
     /// Convert the error into a user-friendly diagnostic
     pub fn to_diagnostic<FileId>(&self, file: FileId, eof: TextRange) -> Diagnostic<FileId>
+    where
+        FileId: Copy,
+    {
+        self.to_diagnostic_with(file, eof, &Delimiters::default())
+    }
+
+    /// Convert the error into a user-friendly diagnostic for text parsed with custom delimiters
+    pub fn to_diagnostic_with<FileId>(
+        &self,
+        file: FileId,
+        eof: TextRange,
+        delimiters: &Delimiters,
+    ) -> Diagnostic<FileId>
     where
         FileId: Copy,
     {
@@ -85,19 +225,21 @@ impl Error {
             Reason::Expected(tokens) => Diagnostic::error()
                 .with_message("unexpected token encountered")
                 .with_labels(vec![
-                    Label::primary(file, span).with_message(format!("found {} token", self.at)),
+                    Label::primary(file, span)
+                        .with_message(format!("found {} token", delimiters.name(self.at))),
                     Label::secondary(file, span).with_message({
-                        let comma_separated = tokens.iter().map(SyntaxKind::name).enumerate().fold(
-                            String::new(),
-                            |mut acc, (i, name)| {
+                        let comma_separated = tokens
+                            .iter()
+                            .map(|&kind| delimiters.name(kind))
+                            .enumerate()
+                            .fold(String::new(), |mut acc, (i, name)| {
                                 if i > 0 {
                                     acc.push_str(", ");
                                 }
 
-                                acc.push_str(name);
+                                acc.push_str(&name);
                                 acc
-                            },
-                        );
+                            });
                         format!("expected one of: {comma_separated}")
                     }),
                 ]),
@@ -105,8 +247,27 @@ impl Error {
                 .with_message("unknown escape sequence")
                 .with_labels(vec![Label::primary(file, span)
                     .with_message(format!("unknown escaped character `{character}`"))])
+                .with_notes(vec![{
+                    let (specifier_open, specifier_close) = delimiters.specifier();
+                    let (content_open, content_close) = delimiters.content();
+                    format!(
+                        "valid escape sequences are: `\\\\`, `\\{specifier_open}`, \
+                         `\\{specifier_close}`, `\\{content_open}`, `\\{content_close}`, `\\s`, `\\t`, `\\xNN`, `\\u{{...}}`"
+                    )
+                }]),
+            Reason::InvalidHexEscape => Diagnostic::error()
+                .with_message("invalid hex escape sequence")
+                .with_labels(vec![Label::primary(file, span)
+                    .with_message("expected two hexadecimal digits from `00` to `7f`")])
                 .with_notes(vec![String::from(
-                    "valid escape sequences are: `\\\\`, `\\[`, `\\]`, `\\(`, `\\)`",
+                    "hex escapes are written as `\\x` followed by two digits, i.e. `\\x41` for `A`",
+                )]),
+            Reason::InvalidUnicodeEscape => Diagnostic::error()
+                .with_message("invalid unicode escape sequence")
+                .with_labels(vec![Label::primary(file, span)
+                    .with_message("expected a unicode code point up to `10FFFF`, excluding surrogates")])
+                .with_notes(vec![String::from(
+                    "unicode escapes are written as `\\u{...}` with up to six hexadecimal digits, i.e. `\\u{1F600}`",
                 )]),
             Reason::UnescapedControlCharacter(character) => Diagnostic::error()
                 .with_message("unescaped control character")
@@ -117,8 +278,162 @@ impl Error {
                     Label::secondary(file, span)
                         .with_message(format!("use `\\{character}` to escape it")),
                 ]),
+            Reason::NestingTooDeep(max_depth) => Diagnostic::error()
+                .with_message("markup is nested too deeply")
+                .with_labels(vec![Label::primary(file, span).with_message(format!(
+                    "exceeds the maximum nesting depth of {max_depth}"
+                ))]),
+            Reason::UnknownSpecifier(tag) => Diagnostic::error()
+                .with_message("unknown style specifier")
+                .with_labels(vec![Label::primary(file, span)
+                    .with_message(format!("`{tag}` is not a valid style specifier"))])
+                .with_notes(vec![String::from(
+                    "valid style specifiers are: `fg`, `bg`, `ul`, `deco`, `reset`, `raw`",
+                )]),
+            Reason::UnterminatedSpecifier => {
+                let (open, close) = delimiters.specifier();
+                unterminated(file, span, eof, "style specifier", open, close)
+            }
+            Reason::UnterminatedContent => {
+                let (open, close) = delimiters.content();
+                unterminated(file, span, eof, "styled content", open, close)
+            }
+            Reason::UnterminatedEmphasis => {
+                // the marker is `*` for italic and `**` for bold
+                let marker = "*".repeat(usize::from(span.len()));
+                Diagnostic::error()
+                    .with_message("unclosed emphasis")
+                    .with_labels(vec![Label::primary(file, span)
+                        .with_message(format!("unclosed `{marker}` opened here"))])
+                    .with_notes(vec![format!(
+                        "emphasis must be closed with `{marker}` before the end of the enclosing content, \
+                         or written as `\\*` to use a literal asterisk"
+                    )])
+            }
+        }
+    }
+}
+
+/// Describe a group that was opened at the span but never closed before the end of the input
+fn unterminated<FileId>(
+    file: FileId,
+    span: TextRange,
+    eof: TextRange,
+    group: &str,
+    open: char,
+    close: char,
+) -> Diagnostic<FileId>
+where
+    FileId: Copy,
+{
+    Diagnostic::error()
+        .with_message(format!("unclosed {group}"))
+        .with_labels(vec![
+            Label::primary(file, span).with_message(format!("unclosed `{open}` opened here")),
+            Label::secondary(file, eof)
+                .with_message(format!("expected `{close}` before the end of the input")),
+        ])
+}
+
+impl Error {
+    /// Get the 1-based line and column where the error starts
+    ///
+    /// Columns are counted in characters. Errors without a span are located at the end of the
+    /// source. Returns `None` if the span is not within the source, i.e. if the error came from
+    /// parsing a different piece of text.
+    pub fn line_col(&self, source: &str) -> Option<(usize, usize)> {
+        let offset = match self.span {
+            Some(span) => usize::from(span.start()),
+            None => source.len(),
+        };
+
+        let before = source.get(..offset)?;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        let line = before.matches('\n').count() + 1;
+        let column = before[line_start..].chars().count() + 1;
+        Some((line, column))
+    }
+
+    /// Suggest the closest valid name when a specifier, color, or decoration was misspelled
+    ///
+    /// Aliases are matched as well, but the canonical name is always the one suggested.
+    fn suggestion(&self, source: &str) -> Option<String> {
+        // every name that could have been meant, along with the name to suggest for it
+        let (found, names): (String, Vec<(&str, &str)>) = match &self.reason {
+            Reason::UnknownSpecifier(found) => {
+                (found.to_ascii_lowercase(), SPECIFIER_NAMES.to_vec())
+            }
+            Reason::Expected(expected) => {
+                let names = if expected.contains(&SyntaxKind::Color) {
+                    Color::NAMES
+                        .iter()
+                        .map(|(name, color)| (*name, color.name()))
+                        .collect()
+                } else if expected.contains(&SyntaxKind::Decoration) {
+                    Decoration::NAMES
+                        .iter()
+                        .map(|(name, decoration)| (*name, decoration.name()))
+                        .collect()
+                } else {
+                    return None;
+                };
+
+                let found = source.get(Range::from(self.span?))?.to_ascii_lowercase();
+                (found, names)
+            }
+            _ => return None,
+        };
+
+        let candidates = names.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        let closest = closest(&found, &candidates)?;
+        let (_, suggested) = names.iter().find(|(name, _)| *name == closest)?;
+        Some(format!("did you mean `{suggested}`?"))
+    }
+}
+
+/// Names that a specifier's tag could be mistaken for, along with the tag they refer to
+const SPECIFIER_NAMES: [(&str, &str); 10] = [
+    ("fg", "fg"),
+    ("foreground", "fg"),
+    ("color", "fg"),
+    ("colour", "fg"),
+    ("bg", "bg"),
+    ("background", "bg"),
+    ("ul", "ul"),
+    ("underline", "ul"),
+    ("deco", "deco"),
+    ("decoration", "deco"),
+];
+
+/// Find the name that is most similar to the found text, if any are close enough
+fn closest<'n>(found: &str, names: &[&'n str]) -> Option<&'n str> {
+    // allow roughly one mistake for every three characters, but always allow a couple
+    let max_distance = (found.chars().count() / 3).max(2);
+
+    names
+        .iter()
+        .map(|name| (edit_distance(found, name), *name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// The Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
         }
+        previous = current;
     }
+
+    previous[b.len()]
 }
 
 /// The reason for the parsing failure
@@ -129,6 +444,293 @@ pub enum Reason {
     Expected(Vec<SyntaxKind>),
     /// Encountered an escape sequence that is not valid
     UnknownEscapeSequence(char),
+    /// Encountered a hex escape without two hexadecimal digits, or for a non-ASCII character
+    InvalidHexEscape,
+    /// Encountered a unicode escape that is malformed or isn't a valid code point
+    InvalidUnicodeEscape,
     /// Encountered an unescaped control character
     UnescapedControlCharacter(char),
+    /// Styled markup was nested deeper than the maximum depth
+    NestingTooDeep(usize),
+    /// Encountered a style specifier with a tag that does not exist
+    UnknownSpecifier(String),
+    /// The input ended before the style specifier starting at the span was closed
+    UnterminatedSpecifier,
+    /// The input ended before the styled content starting at the span was closed
+    UnterminatedContent,
+    /// The enclosing content or the input ended before the emphasis starting at the span was
+    /// closed
+    UnterminatedEmphasis,
+}
+
+impl Reason {
+    /// A stable identifier for the kind of error
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Expected(_) => "expected-token",
+            Self::UnknownEscapeSequence(_) => "unknown-escape-sequence",
+            Self::InvalidHexEscape => "invalid-hex-escape",
+            Self::InvalidUnicodeEscape => "invalid-unicode-escape",
+            Self::UnescapedControlCharacter(_) => "unescaped-control-character",
+            Self::NestingTooDeep(_) => "nesting-too-deep",
+            Self::UnknownSpecifier(_) => "unknown-specifier",
+            Self::UnterminatedSpecifier => "unterminated-specifier",
+            Self::UnterminatedContent => "unterminated-content",
+            Self::UnterminatedEmphasis => "unterminated-emphasis",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest, edit_distance, push_json_string, ErrorReport};
+    use crate::{ast::Decoration, parser::Parser};
+    use std::{ops::Range, str::FromStr};
+    use text_size::{TextLen, TextRange};
+
+    fn report(source: &str) -> String {
+        let (_, errors) = Parser::new(source).parse();
+        ErrorReport::from(errors)
+            .emit(Some("test"), source, false)
+            .unwrap()
+    }
+
+    #[test]
+    fn edit_distance_between_names() {
+        assert_eq!(edit_distance("red", "red"), 0);
+        assert_eq!(edit_distance("rde", "red"), 2);
+        assert_eq!(edit_distance("bleu", "blue"), 2);
+        assert_eq!(edit_distance("", "cyan"), 4);
+        assert_eq!(edit_distance("magneta", "magenta"), 2);
+    }
+
+    #[test]
+    fn closest_picks_nearest_name() {
+        assert_eq!(closest("gren", &["green", "red", "cyan"]), Some("green"));
+        assert_eq!(closest("prple", &["green", "red", "cyan"]), None);
+    }
+
+    #[test]
+    fn suggests_misspelled_color() {
+        let report = report("[fg:yelow](text)");
+        assert!(report.contains("did you mean `yellow`?"), "{report}");
+    }
+
+    #[test]
+    fn suggests_misspelled_bright_color() {
+        let report = report("[bg:bright-magneta](text)");
+        assert!(
+            report.contains("did you mean `bright-magenta`?"),
+            "{report}"
+        );
+    }
+
+    #[test]
+    fn suggests_misspelled_decoration() {
+        let report = report("[deco:bold,undrline](text)");
+        assert!(report.contains("did you mean `underline`?"), "{report}");
+    }
+
+    #[test]
+    fn suggests_canonical_decoration_for_typos() {
+        for (typo, expected) in [
+            ("itallic", "italic"),
+            ("strikethru", "strike-through"),
+            ("blod", "bold"),
+            ("slowblink", "slow-blink"),
+            ("reverce", "invert"),
+            ("concael", "hide"),
+        ] {
+            let report = report(&format!("[deco:{typo}](text)"));
+            assert!(
+                report.contains(&format!("did you mean `{expected}`?")),
+                "{report}"
+            );
+            assert!(Decoration::from_str(expected).is_ok(), "{expected}");
+        }
+    }
+
+    #[test]
+    fn suggests_foreground_for_unknown_specifier() {
+        for source in [
+            "[colour:red](text)",
+            "[foreground:red](text)",
+            "[fgg:red](text)",
+        ] {
+            let report = report(source);
+            assert!(report.contains("did you mean `fg`?"), "{report}");
+        }
+    }
+
+    #[test]
+    fn suggests_decoration_for_unknown_specifier() {
+        for source in ["[decoraton:bold](text)", "[dceo:bold](text)"] {
+            let report = report(source);
+            assert!(report.contains("did you mean `deco`?"), "{report}");
+        }
+    }
+
+    #[test]
+    fn unknown_specifier_span_covers_tag() {
+        let source = "[fg:red;colour:blue](text)";
+        let (_, errors) = Parser::new(source).parse();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(&source[Range::from(errors[0].span.unwrap())], "colour");
+    }
+
+    #[test]
+    fn unknown_specifier() {
+        insta::assert_snapshot!(report("[fg:red;colour:blue](text)"));
+    }
+
+    #[test]
+    fn no_suggestion_for_unrelated_names() {
+        let report = report("[fg:prple](text)");
+        assert!(!report.contains("did you mean"), "{report}");
+    }
+
+    #[test]
+    fn unclosed_specifier() {
+        insta::assert_snapshot!(report("some text [fg:red;deco:bold"));
+    }
+
+    #[test]
+    fn unclosed_content() {
+        insta::assert_snapshot!(report("some text [fg:red](copied\nacross lines"));
+    }
+
+    #[test]
+    fn unclosed_nested_content() {
+        insta::assert_snapshot!(report("[fg:red](outer [bg:blue](inner) still open"));
+    }
+
+    #[test]
+    fn unclosed_emphasis() {
+        let source = "some **bold text";
+        let (_, errors) = Parser::new(source).with_markdown_emphasis(true).parse();
+        let report = ErrorReport::from(errors)
+            .emit(Some("test"), source, false)
+            .unwrap();
+        insta::assert_snapshot!(report);
+    }
+
+    #[test]
+    fn error_at_end_of_second_line() {
+        let source = "[fg:red](first line)\n[deco:bold;fg:";
+        let (_, errors) = Parser::new(source).parse();
+        assert_eq!(errors[0].span, Some(TextRange::empty(source.text_len())));
+        insta::assert_snapshot!(report(source));
+    }
+
+    #[test]
+    fn report_without_file_name() {
+        let source = "[fg:red](unclosed";
+        let (_, errors) = Parser::new(source).parse();
+        let report = ErrorReport::from(errors);
+
+        let named = report.emit(Some("inline"), source, false).unwrap();
+        assert!(named.contains("┌─ inline:1:9"), "{named}");
+
+        let unnamed = report.emit(None, source, false).unwrap();
+        assert!(!unnamed.contains("inline:"), "{unnamed}");
+        assert!(unnamed.contains("┌─ <string>:1:9"), "{unnamed}");
+        assert!(unnamed.contains("unclosed `(` opened here"), "{unnamed}");
+    }
+
+    #[test]
+    fn no_suggestion_for_other_errors() {
+        let report = report("[fg:red](unclosed");
+        assert!(!report.contains("did you mean"), "{report}");
+    }
+
+    fn json(source: &str) -> String {
+        let (_, errors) = Parser::new(source).parse();
+        ErrorReport::from(errors).emit_json(Some("test.txt"), source)
+    }
+
+    #[test]
+    fn json_without_errors() {
+        assert_eq!(json("[fg:red](fine)"), "[]");
+    }
+
+    #[test]
+    fn json_is_valid() {
+        let output = json("[fg:pink](a)\nb \\q [fg:red](unclosed");
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(parsed.as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn json_errors() {
+        insta::assert_snapshot!(json("[fg:pink](a)\nb \\q ) [fg:red](unclosed"));
+    }
+
+    #[test]
+    fn json_without_file_name() {
+        let source = "[fg:red](unclosed";
+        let (_, errors) = Parser::new(source).parse();
+        let output = ErrorReport::from(errors).emit_json(None, source);
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert!(parsed[0]["file"].is_null());
+    }
+
+    #[test]
+    fn json_with_mismatched_source() {
+        let (_, errors) = Parser::new("a long piece of text )").parse();
+        let output = ErrorReport::from(errors).emit_json(None, "short");
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert!(parsed[0]["line"].is_null());
+        assert!(parsed[0]["column"].is_null());
+        assert_eq!(parsed[0]["span"]["start"], 21);
+    }
+
+    #[test]
+    fn json_string_escaping() {
+        let mut output = String::new();
+        push_json_string(&mut output, "a \"quote\" \\ \n\t\x1b");
+        assert_eq!(output, r#""a \"quote\" \\ \n\t\u001b""#);
+    }
+
+    fn line_col(source: &str) -> Option<(usize, usize)> {
+        let (_, errors) = Parser::new(source).parse();
+        errors[0].line_col(source)
+    }
+
+    #[test]
+    fn line_col_first_line() {
+        assert_eq!(line_col("[fg:pink](x)"), Some((1, 5)));
+    }
+
+    #[test]
+    fn line_col_later_lines() {
+        assert_eq!(line_col("first\nsecond\n  ) third"), Some((3, 3)));
+        assert_eq!(line_col("first\r\nsecond )"), Some((2, 8)));
+    }
+
+    #[test]
+    fn line_col_after_escaped_newlines() {
+        assert_eq!(line_col("one \\\n two \\\n\tthree )"), Some((3, 8)));
+        assert_eq!(line_col("[fg:red;\\\n bg:pink](x)"), Some((2, 5)));
+    }
+
+    #[test]
+    fn line_col_counts_characters() {
+        assert_eq!(line_col("héllo wörld )"), Some((1, 13)));
+    }
+
+    #[test]
+    fn line_col_at_end_of_source() {
+        assert_eq!(line_col("a\n[fg:"), Some((2, 5)));
+    }
+
+    #[test]
+    fn line_col_of_unclosed_group() {
+        assert_eq!(line_col("a\n[fg:red](unclosed"), Some((2, 9)));
+    }
+
+    #[test]
+    fn line_col_outside_source() {
+        let (_, errors) = Parser::new("a long piece of text )").parse();
+        assert_eq!(errors[0].line_col("short"), None);
+    }
 }
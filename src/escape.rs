@@ -5,11 +5,24 @@ pub fn escape(source: &str) -> String {
     let mut result = String::with_capacity(source.len());
 
     for lexeme in lexer {
+        // a comment covers the whole of `[#: ...]`, so each control character within it is escaped
+        if lexeme.kind == SyntaxKind::Comment {
+            for ch in lexeme.text.chars() {
+                if matches!(ch, '[' | ']' | '(' | ')' | '\\') {
+                    result.push('\\');
+                }
+                result.push(ch);
+            }
+            continue;
+        }
+
         if let SyntaxKind::ParenthesisOpen
         | SyntaxKind::ParenthesisClose
         | SyntaxKind::SquareBracketOpen
         | SyntaxKind::SquareBracketClose
         | SyntaxKind::EscapeCharacter
+        | SyntaxKind::EscapeHex
+        | SyntaxKind::EscapeUnicode
         | SyntaxKind::EscapeWhitespace = lexeme.kind
         {
             result.push('\\');
@@ -24,6 +37,7 @@ pub fn escape(source: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::escape;
+    use crate::strip;
 
     #[test]
     fn lowercase_alphabetic() {
@@ -80,6 +94,16 @@ mod tests {
         assert_eq!(escape("\\ \n\t"), "\\\\ \n\t");
     }
 
+    #[test]
+    fn escaped_hex() {
+        assert_eq!(escape("\\x41"), "\\\\x41");
+    }
+
+    #[test]
+    fn escaped_unicode() {
+        assert_eq!(escape("\\u{1F600}"), "\\\\u{1F600}");
+    }
+
     #[test]
     fn empty_token() {
         assert_eq!(escape("[fg:red]()"), "\\[fg:red\\]\\(\\)");
@@ -208,6 +232,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn comment() {
+        assert_eq!(escape("[#: note]"), "\\[#: note\\]");
+        assert_eq!(
+            escape("a [#: (x) \\] y] b"),
+            "a \\[#: \\(x\\) \\\\\\] y\\] b"
+        );
+    }
+
+    #[test]
+    fn comment_round_trips() {
+        for source in [
+            "[#: note]",
+            "before [#: a (note)] after",
+            "[#: \\] escaped]",
+        ] {
+            assert_eq!(strip(&escape(source)).unwrap(), source);
+        }
+    }
+
     #[test]
     fn parse_bad_escape_character() {
         assert_eq!(escape("before \\a after"), "before \\\\a after");
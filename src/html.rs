@@ -0,0 +1,475 @@
+use crate::{
+    ast::{Color, Decoration, Style, Token},
+    error::Error,
+    parser::Parser,
+};
+
+/// Convert styled markup to HTML, wrapping each styled piece of text in a `<span>` with inline
+/// styles
+///
+/// Colors are mapped to the conventional xterm values, with underline colors becoming
+/// `text-decoration-color`. Decorations without a CSS equivalent (i.e. blinking and inverting) are
+/// dropped, as are any ANSI escape sequences present in the source.
+pub fn to_html(source: &str) -> Result<String, Vec<Error>> {
+    render(source, &style_attribute)
+}
+
+/// Convert styled markup to HTML, wrapping each styled piece of text in a `<span>` with classes
+/// describing its style
+///
+/// Each class is namespaced by the prefix, i.e. `[fg:red;deco:bold](text)` with the prefix `antsi`
+/// becomes `<span class="antsi-fg-red antsi-bold">text</span>`. Colors use `fg-`, `bg-`, and `ul-`
+/// with the color name, while decorations use their name directly. The actual styling is left to a
+/// stylesheet. Any ANSI escape sequences present in the source are dropped.
+pub fn to_html_classes(source: &str, prefix: &str) -> Result<String, Vec<Error>> {
+    render(source, &|style| class_attribute(style, prefix))
+}
+
+/// Generate a stylesheet for the classes emitted by [`to_html_classes`] with the given prefix
+///
+/// Colors use the same values as [`to_html`]. Blinking is implemented using animations and
+/// inverting using filters, as CSS has no direct equivalent.
+pub fn default_css(prefix: &str) -> String {
+    // the reset must come first so any styles applied alongside it take precedence
+    let mut css = format!(".{prefix}-reset {{");
+    for (property, value) in RESET_DECLARATIONS {
+        css.push_str(&format!(" {property}: {value};"));
+    }
+    css.push_str(" }\n");
+
+    for &color in Color::ALL {
+        let name = color.name();
+        let value = css_color(color);
+        css.push_str(&format!(".{prefix}-fg-{name} {{ color: {value}; }}\n"));
+        css.push_str(&format!(
+            ".{prefix}-bg-{name} {{ background-color: {value}; }}\n"
+        ));
+        css.push_str(&format!(
+            ".{prefix}-ul-{name} {{ text-decoration-color: {value}; }}\n"
+        ));
+    }
+
+    for &decoration in Decoration::ALL {
+        let name = decoration.name();
+        let declarations = match decoration {
+            Decoration::Bold => String::from("font-weight: bold;"),
+            Decoration::Dim => String::from("opacity: 0.5;"),
+            Decoration::Italic => String::from("font-style: italic;"),
+            Decoration::Underline => String::from("text-decoration: underline;"),
+            Decoration::SlowBlink => format!("animation: {prefix}-blink 1s step-end infinite;"),
+            Decoration::FastBlink => format!("animation: {prefix}-blink 0.3s step-end infinite;"),
+            Decoration::Invert => String::from("filter: invert(100%);"),
+            Decoration::Hide => String::from("visibility: hidden;"),
+            Decoration::StrikeThrough => String::from("text-decoration: line-through;"),
+        };
+        css.push_str(&format!(".{prefix}-{name} {{ {declarations} }}\n"));
+    }
+
+    // both decorations use the same property, so they must be combined explicitly
+    css.push_str(&format!(
+        ".{prefix}-underline.{prefix}-strike-through {{ text-decoration: underline line-through; }}\n"
+    ));
+    css.push_str(&format!(
+        "@keyframes {prefix}-blink {{ 50% {{ opacity: 0; }} }}\n"
+    ));
+
+    css
+}
+
+/// The CSS properties and values that undo every style inherited from the enclosing elements
+///
+/// Decorations drawn by an ancestor's `text-decoration` can't be removed by its descendants, so
+/// underlines and strike-throughs may still show through.
+const RESET_DECLARATIONS: &[(&str, &str)] = &[
+    ("color", "initial"),
+    ("background-color", "initial"),
+    ("font-weight", "normal"),
+    ("font-style", "normal"),
+    ("opacity", "1"),
+    ("text-decoration", "none"),
+    ("animation", "none"),
+    ("filter", "none"),
+    ("visibility", "visible"),
+];
+
+/// Parse the source and convert it to HTML, using `attribute` to generate the attributes of each
+/// `<span>`
+///
+/// Pango markup uses the same structure, so this is shared with [`to_pango`](crate::to_pango).
+pub(crate) fn render(
+    source: &str,
+    attribute: &dyn Fn(&Style) -> Option<String>,
+) -> Result<String, Vec<Error>> {
+    let (tokens, errors) = Parser::new(source).parse();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut result = String::with_capacity(source.len());
+    convert_tokens(&mut result, &tokens, attribute);
+
+    result.shrink_to_fit();
+    Ok(result)
+}
+
+/// Convert the tokens into the resulting HTML
+///
+/// The tokens are walked with an explicit stack, so arbitrarily deep nesting can't overflow the
+/// call stack. Each level tracks whether it is wrapped in a `<span>` that must be closed.
+fn convert_tokens(
+    output: &mut String,
+    tokens: &[Token],
+    attribute: &dyn Fn(&Style) -> Option<String>,
+) {
+    let mut stack = vec![(tokens.iter(), false)];
+    while let Some((tokens, _)) = stack.last_mut() {
+        match tokens.next() {
+            Some(Token::Content(content, _)) => push_escaped(output, content),
+            Some(Token::Raw(..)) => {}
+            Some(Token::Styled { content, style, .. }) => {
+                if content.is_empty() {
+                    continue;
+                }
+
+                let attributes = attribute(style);
+                if let Some(attributes) = &attributes {
+                    output.push_str("<span ");
+                    output.push_str(attributes);
+                    output.push('>');
+                }
+                stack.push((content.iter(), attributes.is_some()));
+            }
+            None => {
+                if let Some((_, true)) = stack.pop() {
+                    output.push_str("</span>");
+                }
+            }
+        }
+    }
+}
+
+/// Generate the `style` attribute for a style
+///
+/// Returns `None` if the style has no CSS equivalent.
+fn style_attribute(style: &Style) -> Option<String> {
+    let mut declarations = Vec::new();
+
+    if style.reset {
+        for (property, value) in RESET_DECLARATIONS {
+            declarations.push(format!("{property}:{value}"));
+        }
+    }
+
+    if let Some(foreground) = style.foreground {
+        declarations.push(format!("color:{}", css_color(foreground)));
+    }
+    if let Some(background) = style.background {
+        declarations.push(format!("background-color:{}", css_color(background)));
+    }
+    if let Some(underline_color) = style.underline_color {
+        declarations.push(format!(
+            "text-decoration-color:{}",
+            css_color(underline_color)
+        ));
+    }
+
+    let mut text_decorations = Vec::new();
+    for decoration in style.decoration.iter().flatten() {
+        match decoration {
+            Decoration::Bold => declarations.push(String::from("font-weight:bold")),
+            Decoration::Dim => declarations.push(String::from("opacity:0.5")),
+            Decoration::Italic => declarations.push(String::from("font-style:italic")),
+            Decoration::Underline => text_decorations.push("underline"),
+            Decoration::StrikeThrough => text_decorations.push("line-through"),
+            Decoration::Hide => declarations.push(String::from("visibility:hidden")),
+            Decoration::SlowBlink | Decoration::FastBlink | Decoration::Invert => {}
+        }
+    }
+    if !text_decorations.is_empty() {
+        declarations.push(format!("text-decoration:{}", text_decorations.join(" ")));
+    }
+
+    if declarations.is_empty() {
+        None
+    } else {
+        Some(format!("style=\"{}\"", declarations.join(";")))
+    }
+}
+
+/// Generate the `class` attribute for a style
+///
+/// Returns `None` if the style has no properties.
+fn class_attribute(style: &Style, prefix: &str) -> Option<String> {
+    let mut classes = Vec::new();
+
+    if style.reset {
+        classes.push(format!("{prefix}-reset"));
+    }
+
+    if let Some(foreground) = style.foreground {
+        classes.push(format!("{prefix}-fg-{}", foreground.name()));
+    }
+    if let Some(background) = style.background {
+        classes.push(format!("{prefix}-bg-{}", background.name()));
+    }
+    if let Some(underline_color) = style.underline_color {
+        classes.push(format!("{prefix}-ul-{}", underline_color.name()));
+    }
+    for decoration in style.decoration.iter().flatten() {
+        classes.push(format!("{prefix}-{}", decoration.name()));
+    }
+
+    if classes.is_empty() {
+        None
+    } else {
+        let mut attribute = String::from("class=\"");
+        push_escaped(&mut attribute, &classes.join(" "));
+        attribute.push('"');
+        Some(attribute)
+    }
+}
+
+/// Convert a color to its CSS value
+pub(crate) fn css_color(color: Color) -> &'static str {
+    match color {
+        Color::Black => "#000000",
+        Color::Red => "#cd0000",
+        Color::Green => "#00cd00",
+        Color::Yellow => "#cdcd00",
+        Color::Blue => "#0000ee",
+        Color::Magenta => "#cd00cd",
+        Color::Cyan => "#00cdcd",
+        Color::White => "#e5e5e5",
+        Color::Default => "initial",
+        Color::BrightBlack => "#7f7f7f",
+        Color::BrightRed => "#ff0000",
+        Color::BrightGreen => "#00ff00",
+        Color::BrightYellow => "#ffff00",
+        Color::BrightBlue => "#5c5cff",
+        Color::BrightMagenta => "#ff00ff",
+        Color::BrightCyan => "#00ffff",
+        Color::BrightWhite => "#ffffff",
+    }
+}
+
+/// Push the content to the output, escaping any characters that have a special meaning in HTML
+pub(crate) fn push_escaped(output: &mut String, content: &str) {
+    for ch in content.chars() {
+        match ch {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            _ => output.push(ch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{css_color, default_css, to_html, to_html_classes};
+    use crate::ast::Color;
+
+    #[test]
+    fn unstyled() {
+        assert_eq!(to_html("plain text").unwrap(), "plain text");
+    }
+
+    #[test]
+    fn foreground() {
+        assert_eq!(
+            to_html("[fg:red](red)").unwrap(),
+            "<span style=\"color:#cd0000\">red</span>"
+        );
+    }
+
+    #[test]
+    fn background() {
+        assert_eq!(
+            to_html("[bg:bright-blue](blue)").unwrap(),
+            "<span style=\"background-color:#5c5cff\">blue</span>"
+        );
+    }
+
+    #[test]
+    fn underline_color() {
+        assert_eq!(
+            to_html("[ul:red;deco:underline](x)").unwrap(),
+            "<span style=\"text-decoration-color:#cd0000;text-decoration:underline\">x</span>"
+        );
+    }
+
+    #[test]
+    fn default_color() {
+        assert_eq!(
+            to_html("[fg:default](text)").unwrap(),
+            "<span style=\"color:initial\">text</span>"
+        );
+    }
+
+    #[test]
+    fn decorations() {
+        insta::assert_snapshot!(to_html(
+            "[deco:bold](bold) [deco:dim,italic](dim italic) [deco:underline,strikethrough](lines) [deco:hide](hidden)"
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn unsupported_decorations_are_dropped() {
+        assert_eq!(to_html("[deco:blink-slow,invert](text)").unwrap(), "text");
+    }
+
+    #[test]
+    fn nested() {
+        insta::assert_snapshot!(to_html(
+            "leading [fg:red](one [bg:blue](two [deco:dim](three) two) one) trailing"
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn multiple_styles() {
+        insta::assert_snapshot!(to_html("[fg:black;bg:white;deco:bold,underline](styled)").unwrap());
+    }
+
+    #[test]
+    fn reset() {
+        insta::assert_snapshot!(to_html("[deco:bold](a [reset;fg:red](b))").unwrap());
+    }
+
+    #[test]
+    fn empty_token() {
+        assert_eq!(to_html("[fg:red]()").unwrap(), "");
+    }
+
+    #[test]
+    fn content_is_escaped() {
+        assert_eq!(
+            to_html("<b>\"a\" & 'b'</b> [fg:red](<i>)").unwrap(),
+            "&lt;b&gt;&quot;a&quot; &amp; &#39;b&#39;&lt;/b&gt; <span style=\"color:#cd0000\">&lt;i&gt;</span>"
+        );
+    }
+
+    #[test]
+    fn existing_escape_sequences_are_removed() {
+        assert_eq!(
+            to_html("\x1b[1mbold\x1b[22m [fg:red](red)").unwrap(),
+            "bold <span style=\"color:#cd0000\">red</span>"
+        );
+    }
+
+    #[test]
+    fn invalid_markup() {
+        assert!(to_html("[fg:red](unclosed").is_err());
+    }
+
+    #[test]
+    fn classes_foreground() {
+        assert_eq!(
+            to_html_classes("[fg:red](red)", "antsi").unwrap(),
+            "<span class=\"antsi-fg-red\">red</span>"
+        );
+    }
+
+    #[test]
+    fn classes_underline_color() {
+        assert_eq!(
+            to_html_classes("[ul:red;deco:underline](x)", "antsi").unwrap(),
+            "<span class=\"antsi-ul-red antsi-underline\">x</span>"
+        );
+        assert!(default_css("antsi").contains(".antsi-ul-red { text-decoration-color: #cd0000; }"));
+    }
+
+    #[test]
+    fn classes_bright_colors() {
+        assert_eq!(
+            to_html_classes("[fg:bright-black;bg:bright-cyan](bright)", "antsi").unwrap(),
+            "<span class=\"antsi-fg-bright-black antsi-bg-bright-cyan\">bright</span>"
+        );
+    }
+
+    #[test]
+    fn classes_multiple_decorations() {
+        assert_eq!(
+            to_html_classes("[deco:bold,faint,blink-fast,strikethrough](text)", "x").unwrap(),
+            "<span class=\"x-bold x-dim x-fast-blink x-strike-through\">text</span>"
+        );
+    }
+
+    #[test]
+    fn classes_reset() {
+        assert_eq!(
+            to_html_classes("[reset;fg:red](text)", "x").unwrap(),
+            "<span class=\"x-reset x-fg-red\">text</span>"
+        );
+    }
+
+    #[test]
+    fn classes_nested() {
+        insta::assert_snapshot!(to_html_classes(
+            "leading [fg:red](one [bg:blue](two [deco:dim](three) two) one) trailing",
+            "antsi"
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn classes_content_is_escaped() {
+        assert_eq!(
+            to_html_classes("[fg:red](<i>)", "antsi").unwrap(),
+            "<span class=\"antsi-fg-red\">&lt;i&gt;</span>"
+        );
+    }
+
+    #[test]
+    fn default_css_stylesheet() {
+        insta::assert_snapshot!(default_css("antsi"));
+    }
+
+    #[test]
+    fn default_css_uses_prefix() {
+        let css = default_css("custom");
+        assert!(css.contains(".custom-fg-bright-red { color: #ff0000; }"));
+        assert!(css.contains(".custom-bg-default { background-color: initial; }"));
+        assert!(css.contains(".custom-bold { font-weight: bold; }"));
+        assert!(!css.contains("antsi"));
+    }
+
+    #[test]
+    fn default_css_covers_emitted_classes() {
+        let html = to_html_classes(
+            "[fg:bright-magenta;bg:default;deco:bold,dim,italic,underline,blink-slow,blink-fast,invert,hide,strikethrough](x)",
+            "antsi",
+        )
+        .unwrap();
+        let css = default_css("antsi");
+
+        let classes = html
+            .trim_start_matches("<span class=\"")
+            .split('"')
+            .next()
+            .unwrap();
+        for class in classes.split(' ') {
+            assert!(css.contains(&format!(".{class} ")), "missing {class}");
+        }
+    }
+
+    #[test]
+    fn css_colors_match_rgb() {
+        for &color in Color::ALL {
+            if let Some((r, g, b)) = color.rgb() {
+                assert_eq!(css_color(color), format!("#{r:02x}{g:02x}{b:02x}"));
+            }
+        }
+    }
+
+    #[test]
+    fn classes_nested_content_is_escaped() {
+        assert_eq!(
+            to_html_classes("a & [fg:red](<b> [deco:bold](\"c\"))", "antsi").unwrap(),
+            "a &amp; <span class=\"antsi-fg-red\">&lt;b&gt; <span class=\"antsi-bold\">&quot;c&quot;</span></span>"
+        );
+    }
+}
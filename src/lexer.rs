@@ -1,41 +1,170 @@
+use crate::delimiters::Delimiters;
 use logos::Logos;
 use std::{
+    collections::VecDeque,
     fmt::{Display, Formatter},
     ops::Range,
 };
 use text_size::{TextRange, TextSize};
 
-pub(crate) struct Lexer<'source>(logos::Lexer<'source, SyntaxKind>);
+pub(crate) struct Lexer<'source> {
+    inner: logos::Lexer<'source, SyntaxKind>,
+    delimiters: Delimiters,
+    pending: VecDeque<Lexeme<'source>>,
+}
 
 impl<'source> Lexer<'source> {
     pub fn new(input: &'source str) -> Self {
-        Self(SyntaxKind::lexer(input))
+        Self::with_delimiters(input, Delimiters::default())
     }
-}
 
-impl<'source> Iterator for Lexer<'source> {
-    type Item = Lexeme<'source>;
+    /// Create a lexer that recognizes a custom set of delimiters
+    pub fn with_delimiters(input: &'source str, delimiters: Delimiters) -> Self {
+        Self {
+            inner: SyntaxKind::lexer(input),
+            delimiters,
+            pending: VecDeque::new(),
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let kind = self.0.next()?.unwrap_or(SyntaxKind::Unknown);
-        let span = {
-            let Range { start, end } = self.0.span();
-            let start = TextSize::try_from(start).unwrap();
-            let end = TextSize::try_from(end).unwrap();
+    /// Retokenize a lexeme for the configured delimiters
+    ///
+    /// The default delimiters are always lexed as such, so they must become text if they aren't
+    /// configured. Any other delimiters are lexed as part of a piece of text, which must be split
+    /// around them.
+    fn delimit(&mut self, lexeme: Lexeme<'source>) -> Lexeme<'source> {
+        match lexeme.kind {
+            SyntaxKind::SquareBracketOpen
+            | SyntaxKind::SquareBracketClose
+            | SyntaxKind::ParenthesisOpen
+            | SyntaxKind::ParenthesisClose => {
+                let character = lexeme.text.chars().next().unwrap();
+                Lexeme {
+                    kind: self.delimiters.kind(character).unwrap_or(SyntaxKind::Text),
+                    ..lexeme
+                }
+            }
+            SyntaxKind::Text | SyntaxKind::Asterisk | SyntaxKind::DoubleAsterisk
+                if lexeme
+                    .text
+                    .chars()
+                    .any(|c| self.delimiters.kind(c).is_some()) =>
+            {
+                let mut start = 0;
+                for (i, character) in lexeme.text.char_indices() {
+                    let Some(kind) = self.delimiters.kind(character) else {
+                        continue;
+                    };
+
+                    self.relex(&lexeme, start, i);
+                    self.pending.push_back(Lexeme {
+                        kind,
+                        text: &lexeme.text[i..i + 1],
+                        span: TextRange::at(lexeme.span.start() + offset(i), TextSize::from(1)),
+                    });
+                    start = i + 1;
+                }
+
+                self.relex(&lexeme, start, lexeme.text.len());
+                self.pending.pop_front().unwrap()
+            }
+            _ => lexeme,
+        }
+    }
+
+    /// Lex part of a piece of text on its own, queueing the resulting lexemes
+    fn relex(&mut self, lexeme: &Lexeme<'source>, start: usize, end: usize) {
+        let base = lexeme.span.start() + offset(start);
+        let mut inner = SyntaxKind::lexer(&lexeme.text[start..end]);
 
-            TextRange::new(start, end)
+        while let Some(kind) = inner.next() {
+            let Range { start, end } = inner.span();
+            self.pending.push_back(Lexeme {
+                kind: kind.unwrap_or(SyntaxKind::Unknown),
+                text: inner.slice(),
+                span: TextRange::new(base + offset(start), base + offset(end)),
+            });
+        }
+    }
+
+    /// Lex a comment starting at the opening delimiter of a style specifier, if there is one
+    ///
+    /// Comments span multiple lexemes, so the rest of the comment is skipped in the source.
+    fn comment(&mut self, open: &Lexeme<'source>) -> Option<Lexeme<'source>> {
+        let source = self.inner.source();
+        let start = usize::from(open.span.end());
+        let body = source[start..].strip_prefix("#:")?;
+
+        let (_, close) = self.delimiters.specifier();
+        let mut chars = body.char_indices();
+        let end = loop {
+            match chars.next()? {
+                (_, '\\') => {
+                    chars.next();
+                }
+                (i, c) if c == close => break start + 2 + i + c.len_utf8(),
+                _ => {}
+            }
         };
 
+        // the opening delimiter may have been split from a piece of text, but the comment always
+        // extends past that text since `:` is lexed on its own
+        self.pending.clear();
+        self.inner.bump(end - self.inner.span().end);
+
+        let span = TextRange::new(open.span.start(), offset(end));
         Some(Lexeme {
+            kind: SyntaxKind::Comment,
+            text: &source[span],
             span,
-            kind,
-            text: self.0.slice(),
         })
     }
 }
 
+fn offset(index: usize) -> TextSize {
+    TextSize::try_from(index).unwrap()
+}
+
+impl<'source> Iterator for Lexer<'source> {
+    type Item = Lexeme<'source>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let lexeme = match self.pending.pop_front() {
+            Some(lexeme) => lexeme,
+            None => {
+                let kind = self.inner.next()?.unwrap_or(SyntaxKind::Unknown);
+                let span = {
+                    let Range { start, end } = self.inner.span();
+                    TextRange::new(offset(start), offset(end))
+                };
+
+                let lexeme = Lexeme {
+                    span,
+                    kind,
+                    text: self.inner.slice(),
+                };
+
+                if self.delimiters == Delimiters::default() {
+                    lexeme
+                } else {
+                    self.delimit(lexeme)
+                }
+            }
+        };
+
+        if lexeme.kind == SyntaxKind::SquareBracketOpen {
+            if let Some(comment) = self.comment(&lexeme) {
+                return Some(comment);
+            }
+        }
+
+        Some(lexeme)
+    }
+}
+
+/// The kinds of lexemes that make up styled markup
 #[derive(Clone, Copy, Debug, Eq, Logos, PartialEq)]
-pub(crate) enum SyntaxKind {
+pub enum SyntaxKind {
     #[token("[")]
     SquareBracketOpen,
 
@@ -57,23 +186,43 @@ pub(crate) enum SyntaxKind {
     #[token(",", priority = 10)]
     Comma,
 
+    #[token("*")]
+    Asterisk,
+
+    #[token("**")]
+    DoubleAsterisk,
+
     #[token("fg", priority = 10, ignore(ascii_case))]
     ForegroundSpecifier,
 
     #[token("bg", priority = 10, ignore(ascii_case))]
     BackgroundSpecifier,
 
+    #[token("ul", priority = 10, ignore(ascii_case))]
+    #[token("underline-color", priority = 10, ignore(ascii_case))]
+    UnderlineColorSpecifier,
+
     #[token("deco", priority = 10, ignore(ascii_case))]
     DecorationSpecifier,
 
+    #[token("reset", priority = 10, ignore(ascii_case))]
+    ResetSpecifier,
+
+    #[token("raw", priority = 10, ignore(ascii_case))]
+    RawSpecifier,
+
+    // must accept every name in the color table (`Color::NAMES`), along with the `bright_` spelling
     #[regex(
-        r#"(bright-)?(black|red|green|yellow|blue|magenta|cyan|white)"#,
+        r#"(bright[-_])?(black|red|green|yellow|blue|magenta|cyan|white)|gr[ae]y"#,
         priority = 10,
         ignore(ascii_case)
     )]
     #[token("default", ignore(ascii_case))]
+    #[token("none", ignore(ascii_case))]
+    #[token("unset", ignore(ascii_case))]
     Color,
 
+    // must accept every name in the decoration table (`Decoration::NAMES`)
     #[regex(
         r#"(bold|dim|faint|italic|underline|(fast|slow)-blink|blink-(fast|slow)|invert|reverse|hide|conceal|strike(-)?through)"#,
         priority = 10,
@@ -81,20 +230,36 @@ pub(crate) enum SyntaxKind {
     )]
     Decoration,
 
-    #[regex(r#"\\[^ \r\n\t]"#)]
+    #[regex(r#"\\[^ \r\n\t\x0b\x0c]"#)]
     EscapeCharacter,
 
-    #[regex(r#"\\[ \r\n\t]+"#)]
+    #[regex(r#"\\x[0-9A-Fa-f]{2}"#)]
+    EscapeHex,
+
+    #[regex(r#"\\u\{[0-9A-Fa-f]{1,6}\}"#)]
+    EscapeUnicode,
+
+    // form feeds and vertical tabs can only be escaped, they are plain text otherwise
+    #[regex(r#"\\[ \r\n\t\x0b\x0c]+"#)]
     EscapeWhitespace,
 
     #[regex(r#"[ \r\n\t]+"#, priority = 3)]
     Whitespace,
 
+    // control sequence introducers (CSI) that were already present in the source
+    #[regex(r#"\x1b\[[0-?]*[ -/]*[@-~]"#)]
+    RawAnsi,
+
     // as a temporary fix until https://github.com/maciejhirsz/logos/issues/265 is resolved, the
-    // tokens `:` `;` and `,` are considered stop characters for words
-    #[regex(r#"[^\\\[\]() \r\n\t:;,]+"#, priority = 2)]
+    // tokens `:` `;` and `,` are considered stop characters for words. the escape character is
+    // also a stop character so that it can begin a raw escape sequence, and asterisks are stop
+    // characters so they can be used for emphasis.
+    #[regex(r#"[^\\\[\]() \r\n\t:;,*\x1b]+|\x1b"#, priority = 2)]
     Text,
 
+    // comments are lexed by hand as their delimiters can be changed
+    Comment,
+
     Unknown,
     Eof,
 }
@@ -109,16 +274,25 @@ impl SyntaxKind {
             Self::ParenthesisClose => ")",
             Self::Colon => ":",
             Self::Comma => ",",
+            Self::Asterisk => "*",
+            Self::DoubleAsterisk => "**",
             Self::Semicolon => ";",
             Self::ForegroundSpecifier => "foreground specifier",
             Self::BackgroundSpecifier => "background specifier",
+            Self::UnderlineColorSpecifier => "underline color specifier",
             Self::DecorationSpecifier => "decoration specifier",
+            Self::ResetSpecifier => "reset specifier",
+            Self::RawSpecifier => "raw specifier",
             Self::Color => "color",
             Self::Decoration => "decoration",
             Self::EscapeCharacter => "escape character",
+            Self::EscapeHex => "hex escape",
+            Self::EscapeUnicode => "unicode escape",
             Self::EscapeWhitespace => "escape whitespace",
             Self::Whitespace => "whitespace",
+            Self::RawAnsi => "ANSI escape sequence",
             Self::Text => "text",
+            Self::Comment => "comment",
             Self::Unknown => "unknown",
             Self::Eof => "end of file",
         }
@@ -141,6 +315,10 @@ pub(crate) struct Lexeme<'source> {
 #[cfg(test)]
 mod tests {
     use super::{Lexer, SyntaxKind};
+    use crate::{
+        ast::{Color, Decoration},
+        delimiters::Delimiters,
+    };
     use text_size::TextSize;
 
     fn check(input: &str, kind: SyntaxKind) {
@@ -189,6 +367,29 @@ mod tests {
         check(",", SyntaxKind::Comma);
     }
 
+    #[test]
+    fn asterisk() {
+        check("*", SyntaxKind::Asterisk);
+    }
+
+    #[test]
+    fn double_asterisk() {
+        check("**", SyntaxKind::DoubleAsterisk);
+    }
+
+    #[test]
+    fn asterisks_split_text() {
+        assert_eq!(
+            kinds("a***b", Delimiters::default()),
+            vec![
+                (SyntaxKind::Text, "a"),
+                (SyntaxKind::DoubleAsterisk, "**"),
+                (SyntaxKind::Asterisk, "*"),
+                (SyntaxKind::Text, "b"),
+            ]
+        );
+    }
+
     #[test]
     fn lower_ascii_case_alphabetic_text() {
         check("abcdefghijklmnopqrstuvwxyz", SyntaxKind::Text);
@@ -214,7 +415,15 @@ mod tests {
 
     #[test]
     fn special_characters_text() {
-        check("!@#$%^&*-_+=", SyntaxKind::Text);
+        check("!@#$%^&-_+=", SyntaxKind::Text);
+        assert_eq!(
+            kinds("!@#$%^&*-_+=", Delimiters::default()),
+            vec![
+                (SyntaxKind::Text, "!@#$%^&"),
+                (SyntaxKind::Asterisk, "*"),
+                (SyntaxKind::Text, "-_+="),
+            ]
+        );
     }
 
     #[test]
@@ -227,11 +436,28 @@ mod tests {
         check("bg", SyntaxKind::BackgroundSpecifier);
     }
 
+    #[test]
+    fn underline_color_specifier() {
+        check("ul", SyntaxKind::UnderlineColorSpecifier);
+        check("underline-color", SyntaxKind::UnderlineColorSpecifier);
+    }
+
     #[test]
     fn decoration_specifier() {
         check("deco", SyntaxKind::DecorationSpecifier);
     }
 
+    #[test]
+    fn reset_specifier() {
+        check("reset", SyntaxKind::ResetSpecifier);
+    }
+
+    #[test]
+    fn raw_specifier() {
+        check("raw", SyntaxKind::RawSpecifier);
+        check("RAW", SyntaxKind::RawSpecifier);
+    }
+
     #[test]
     fn whitespace() {
         check("  \n\t", SyntaxKind::Whitespace);
@@ -322,6 +548,12 @@ mod tests {
         check("bright-white", SyntaxKind::Color);
     }
 
+    #[test]
+    fn color_bright_underscore() {
+        check("bright_green", SyntaxKind::Color);
+        check("BRIGHT_WHITE", SyntaxKind::Color);
+    }
+
     #[test]
     fn decoration_bold() {
         check("bold", SyntaxKind::Decoration);
@@ -430,6 +662,65 @@ mod tests {
         check("\\4", SyntaxKind::EscapeCharacter);
     }
 
+    #[test]
+    fn escape_hex_lowercase() {
+        check("\\x1b", SyntaxKind::EscapeHex);
+    }
+
+    #[test]
+    fn escape_hex_uppercase() {
+        check("\\x1B", SyntaxKind::EscapeHex);
+    }
+
+    #[test]
+    fn escape_hex_invalid_digits() {
+        let tokens = Lexer::new("\\xZZ")
+            .map(|lexeme| (lexeme.kind, lexeme.text))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                (SyntaxKind::EscapeCharacter, "\\x"),
+                (SyntaxKind::Text, "ZZ")
+            ]
+        );
+    }
+
+    #[test]
+    fn escape_hex_incomplete() {
+        let tokens = Lexer::new("\\x4")
+            .map(|lexeme| (lexeme.kind, lexeme.text))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                (SyntaxKind::EscapeCharacter, "\\x"),
+                (SyntaxKind::Text, "4")
+            ]
+        );
+    }
+
+    #[test]
+    fn escape_unicode() {
+        check("\\u{e9}", SyntaxKind::EscapeUnicode);
+        check("\\u{1F600}", SyntaxKind::EscapeUnicode);
+        check("\\u{10ffff}", SyntaxKind::EscapeUnicode);
+    }
+
+    #[test]
+    fn escape_unicode_without_digits() {
+        let tokens = Lexer::new("\\u{}")
+            .map(|lexeme| (lexeme.kind, lexeme.text))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                (SyntaxKind::EscapeCharacter, "\\u"),
+                (SyntaxKind::Text, "{}")
+            ]
+        );
+    }
+
     #[test]
     fn escape_whitespace_single_space() {
         check("\\ ", SyntaxKind::EscapeWhitespace);
@@ -470,9 +761,66 @@ mod tests {
         check("\\\t\t\t\t\t", SyntaxKind::EscapeWhitespace);
     }
 
+    #[test]
+    fn escape_whitespace_single_form_feed() {
+        check("\\\x0c", SyntaxKind::EscapeWhitespace);
+    }
+
+    #[test]
+    fn escape_whitespace_multiple_form_feeds() {
+        check("\\\x0c\x0c\x0c", SyntaxKind::EscapeWhitespace);
+    }
+
+    #[test]
+    fn escape_whitespace_single_vertical_tab() {
+        check("\\\x0b", SyntaxKind::EscapeWhitespace);
+    }
+
+    #[test]
+    fn escape_whitespace_multiple_vertical_tabs() {
+        check("\\\x0b\x0b\x0b", SyntaxKind::EscapeWhitespace);
+    }
+
     #[test]
     fn escape_whitespace_mixed() {
         check("\\ \t\r\n", SyntaxKind::EscapeWhitespace);
+        check("\\ \x0c\t\x0b\n", SyntaxKind::EscapeWhitespace);
+    }
+
+    #[test]
+    fn raw_ansi_single_parameter() {
+        check("\x1b[31m", SyntaxKind::RawAnsi);
+    }
+
+    #[test]
+    fn raw_ansi_multiple_parameters() {
+        check("\x1b[1;38;5;208m", SyntaxKind::RawAnsi);
+    }
+
+    #[test]
+    fn raw_ansi_no_parameters() {
+        check("\x1b[m", SyntaxKind::RawAnsi);
+    }
+
+    #[test]
+    fn raw_ansi_non_sgr() {
+        check("\x1b[2K", SyntaxKind::RawAnsi);
+    }
+
+    #[test]
+    fn lone_escape_is_text() {
+        check("\x1b", SyntaxKind::Text);
+    }
+
+    #[test]
+    fn raw_ansi_between_text() {
+        let kinds = Lexer::new("before\x1b[31mafter")
+            .map(|lexeme| lexeme.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![SyntaxKind::Text, SyntaxKind::RawAnsi, SyntaxKind::Text]
+        );
     }
 
     #[test]
@@ -513,4 +861,162 @@ mod tests {
         ).collect::<Vec<_>>();
         insta::assert_debug_snapshot!(tokens);
     }
+
+    #[test]
+    fn every_color_name() {
+        for (name, _) in Color::NAMES {
+            check(name, SyntaxKind::Color);
+        }
+    }
+
+    #[test]
+    fn every_decoration_name() {
+        for (name, _) in Decoration::NAMES {
+            check(name, SyntaxKind::Decoration);
+        }
+    }
+
+    fn kinds(input: &str, delimiters: Delimiters) -> Vec<(SyntaxKind, &str)> {
+        Lexer::with_delimiters(input, delimiters)
+            .map(|lexeme| {
+                assert_eq!(&input[lexeme.span], lexeme.text);
+                (lexeme.kind, lexeme.text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn custom_delimiters() {
+        assert_eq!(
+            kinds("{fg:red}<text>", Delimiters::BRACES_ANGLE_BRACKETS),
+            vec![
+                (SyntaxKind::SquareBracketOpen, "{"),
+                (SyntaxKind::ForegroundSpecifier, "fg"),
+                (SyntaxKind::Colon, ":"),
+                (SyntaxKind::Color, "red"),
+                (SyntaxKind::SquareBracketClose, "}"),
+                (SyntaxKind::ParenthesisOpen, "<"),
+                (SyntaxKind::Text, "text"),
+                (SyntaxKind::ParenthesisClose, ">"),
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_delimiters_make_default_delimiters_text() {
+        assert_eq!(
+            kinds("[a](b)", Delimiters::BRACES_ANGLE_BRACKETS),
+            vec![
+                (SyntaxKind::Text, "["),
+                (SyntaxKind::Text, "a"),
+                (SyntaxKind::Text, "]"),
+                (SyntaxKind::Text, "("),
+                (SyntaxKind::Text, "b"),
+                (SyntaxKind::Text, ")"),
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_delimiters_can_be_escaped() {
+        assert_eq!(
+            kinds("a\\{b", Delimiters::BRACES_ANGLE_BRACKETS),
+            vec![
+                (SyntaxKind::Text, "a"),
+                (SyntaxKind::EscapeCharacter, "\\{"),
+                (SyntaxKind::Text, "b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_delimiters_split_multibyte_text() {
+        assert_eq!(
+            kinds("é<ü>", Delimiters::BRACES_ANGLE_BRACKETS),
+            vec![
+                (SyntaxKind::Text, "é"),
+                (SyntaxKind::ParenthesisOpen, "<"),
+                (SyntaxKind::Text, "ü"),
+                (SyntaxKind::ParenthesisClose, ">"),
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_delimiters_split_asterisks() {
+        let delimiters = Delimiters::new('*', '#', '<', '>').unwrap();
+        assert_eq!(
+            kinds("**a#<b*>", delimiters),
+            vec![
+                (SyntaxKind::SquareBracketOpen, "*"),
+                (SyntaxKind::SquareBracketOpen, "*"),
+                (SyntaxKind::Text, "a"),
+                (SyntaxKind::SquareBracketClose, "#"),
+                (SyntaxKind::ParenthesisOpen, "<"),
+                (SyntaxKind::Text, "b"),
+                (SyntaxKind::SquareBracketOpen, "*"),
+                (SyntaxKind::ParenthesisClose, ">"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comment() {
+        check("[#: a comment]", SyntaxKind::Comment);
+    }
+
+    #[test]
+    fn empty_comment() {
+        check("[#:]", SyntaxKind::Comment);
+    }
+
+    #[test]
+    fn comment_with_escaped_delimiter() {
+        check("[#: a \\] b (c)]", SyntaxKind::Comment);
+    }
+
+    #[test]
+    fn comment_between_text() {
+        assert_eq!(
+            kinds("a[#: b]c", Delimiters::default()),
+            vec![
+                (SyntaxKind::Text, "a"),
+                (SyntaxKind::Comment, "[#: b]"),
+                (SyntaxKind::Text, "c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        assert_eq!(
+            kinds("[#: a", Delimiters::default()),
+            vec![
+                (SyntaxKind::SquareBracketOpen, "["),
+                (SyntaxKind::Text, "#"),
+                (SyntaxKind::Colon, ":"),
+                (SyntaxKind::Whitespace, " "),
+                (SyntaxKind::Text, "a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comment_with_custom_delimiters() {
+        assert_eq!(
+            kinds("a{#: [b] c}d [#: e]", Delimiters::BRACES_ANGLE_BRACKETS),
+            vec![
+                (SyntaxKind::Text, "a"),
+                (SyntaxKind::Comment, "{#: [b] c}"),
+                (SyntaxKind::Text, "d"),
+                (SyntaxKind::Whitespace, " "),
+                (SyntaxKind::Text, "["),
+                (SyntaxKind::Text, "#"),
+                (SyntaxKind::Colon, ":"),
+                (SyntaxKind::Whitespace, " "),
+                (SyntaxKind::Text, "e"),
+                (SyntaxKind::Text, "]"),
+            ]
+        );
+    }
 }
@@ -1,53 +1,185 @@
-use pyo3::exceptions::PyTypeError;
+use pyo3::exceptions::{PyTypeError, PyUnicodeDecodeError, PyValueError};
+use pyo3::types::{PyByteArray, PyBytes, PyDict, PyList, PyString};
 use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use std::borrow::Cow;
+use text_size::{TextLen, TextRange};
 use textwrap::Options as WrapOptions;
 
 #[cfg(test)]
 #[macro_use]
 mod macros;
+mod annotate;
 mod ast;
+mod choice;
 mod color;
+#[cfg(feature = "windows")]
+mod console;
+mod delimiters;
 mod error;
 mod escape;
+mod html;
 mod lexer;
+mod lint;
+mod markdown;
+mod minimize;
+mod pango;
 mod parser;
+mod rtf;
+mod spans;
+mod truncate;
+mod unparse;
+mod usage;
 
-use color::{colorize, Options};
-use error::ErrorReport;
+pub use annotate::annotate;
+#[cfg(feature = "anstyle")]
+pub use ast::UnsupportedStyleError;
+pub use ast::{
+    Color, CurrentStyle, Decoration, InvalidColorError, InvalidDecorationError, Segments, Style,
+    Token, Tokens,
+};
+pub use choice::{supported_decorations, ColorChoice};
+pub use color::{
+    colorize, colorize_auto, colorize_with, colorize_with_choice, strip, validate, ColorizeOptions,
+    Options, ResetMode,
+};
+#[cfg(all(windows, feature = "windows"))]
+pub use console::write_colorized_console;
+pub use delimiters::Delimiters;
+pub use error::{Error, ErrorReport, Reason};
 use escape::escape;
+pub use html::{default_css, to_html, to_html_classes};
+pub use lexer::SyntaxKind;
+pub use lint::{lint, Warning, WarningKind};
+pub use markdown::to_markdown;
+pub use pango::to_pango;
+use parser::DEFAULT_MAX_DEPTH;
+pub use parser::{Event, Events};
+pub use rtf::to_rtf;
+pub use spans::spans;
+pub use truncate::{truncate, width};
+pub use unparse::to_markup;
+pub use usage::{analyze, used_colors, Analysis, StyleUsage};
 
 create_exception!(
     antsi,
     ColorizeError,
     PyException,
-    "A report of all the issues found when applying styling to a piece of text"
+    "A report of all the issues found when applying styling to a piece of text
+
+The message is the human-readable report. Each issue is also available as a dictionary in the
+`errors` attribute, containing the `message`, a stable `code`, the `file`, the byte `span` as a
+`(start, end)` tuple, the 1-based `line` and `column` where it starts, and the kind of syntax item
+the parser was `at`. The same details are available as a JSON array in the `json` attribute, with
+the span as an object containing `start` and `end`. When raised by `colorize_all`, the `index`
+attribute is the position of the source that failed, and `None` otherwise.
+
+>>> try:
+...     antsi.colorize(\"[fg:red](unclosed\")
+... except antsi.ColorizeError as e:
+...     e.errors[0][\"span\"]
+(8, 9)"
 );
 
 impl ColorizeError {
-    /// Create a new error from a report
-    fn from_report(report: ErrorReport, source: &str, file: &str) -> PyErr {
-        match report.emit(file, source, false) {
-            Ok(formatted) => Self::new_err(formatted),
-            Err(e) => PyErr::from(e),
-        }
+    /// Create a new error from a report, attaching each error in a structured form
+    ///
+    /// An empty file name is treated the same as no file name.
+    fn from_report(report: ErrorReport, source: &str, file: Option<&str>) -> PyErr {
+        let file = file.filter(|file| !file.is_empty());
+        let formatted = match report.emit(file, source, false) {
+            Ok(formatted) => formatted,
+            Err(e) => return PyErr::from(e),
+        };
+
+        Python::with_gil(|py| {
+            let err = Self::new_err(formatted);
+            let attached = error_details(py, &report, source, file)
+                .and_then(|errors| err.value(py).setattr("errors", errors))
+                .and_then(|()| {
+                    err.value(py)
+                        .setattr("json", report.emit_json(file, source))
+                })
+                .and_then(|()| err.value(py).setattr("index", py.None()));
+            match attached {
+                Ok(()) => err,
+                Err(e) => e,
+            }
+        })
     }
 }
 
+/// Describe each error in the report as a dictionary
+fn error_details<'py>(
+    py: Python<'py>,
+    report: &ErrorReport,
+    source: &str,
+    file: Option<&str>,
+) -> PyResult<Bound<'py, PyList>> {
+    let eof = TextRange::empty(source.text_len());
+
+    let errors = PyList::empty(py);
+    for error in report.errors() {
+        let span = error.span.unwrap_or(eof);
+        let (line, column) = error.line_col(source).unzip();
+
+        let details = PyDict::new(py);
+        details.set_item("message", report.message(error, eof))?;
+        details.set_item("code", error.reason.code())?;
+        details.set_item("file", file)?;
+        details.set_item("span", (u32::from(span.start()), u32::from(span.end())))?;
+        details.set_item("line", line)?;
+        details.set_item("column", column)?;
+        details.set_item("at", error.at.name())?;
+        errors.append(details)?;
+    }
+
+    Ok(errors)
+}
+
 /// Convert styled markup to ANSI escape codes.
 ///
 /// Converts styled markup within the source text to ANSI escape codes allowing text to be formatted
 /// on the command line. If a string has no styled markup, it will be passed through unchanged. Any
 /// invalid/unparseable markup will cause an exception.
 ///
+/// The source can also be passed as UTF-8 encoded `bytes` or `bytearray`, i.e. when read from a
+/// file. A `UnicodeDecodeError` is raised if it is not valid UTF-8. The output is always a `str`.
+///
+/// >>> antsi.colorize(b"[fg:red](error)") == antsi.colorize("[fg:red](error)")
+/// True
+///
 /// Text wrapping is also supported when the `wrap` parameter is passed with the desired width. The
 /// wrap width must be greater than zero.
 ///
+/// When `respect_no_color` is set, the `NO_COLOR` environment variable is honored: if it is set to
+/// a non-empty value, all styling is removed from the output. Invalid markup still causes an
+/// exception.
+///
+/// Any ANSI escape sequences already present in the source are passed through unchanged. They can
+/// be removed by setting `strip_ansi`, and are always removed when color is disabled.
+///
+/// By default, only the properties a style changed are undone at the end of its content. Setting
+/// `reset_mode` to `"full"` clears every style with `\x1b[0m` instead, and re-applies the
+/// enclosing styles. Consecutive escape sequences are merged into one unless `collapse_redundant`
+/// is disabled. Setting `minimize` also removes codes that have no visible effect anywhere in the
+/// output, such as a reset that is immediately followed by re-applying the same style. Setting
+/// `suppress_blink` drops blinking decorations from the output, while still accepting them.
+/// Similarly, `suppress_colors` drops every color while keeping the decorations, and
+/// `suppress_decorations` drops every decoration while keeping the colors.
+/// Setting `reopen_on_newline` clears the styles before every newline within styled content and
+/// re-applies them afterwards, for consumers that reset the styles at the end of each line.
+///
+/// Markup can be nested at most `max_depth` levels deep (128 by default). Anything nested deeper
+/// raises an exception instead of exhausting the stack. Sticky styles don't count towards the
+/// depth.
+///
 /// Styled markup is defined as follows:
 /// ```text
 /// [ <style specifiers> ]( <content> )
+/// [ <style specifiers> ] <rest of the enclosing content>
 ///
 /// <style specifiers> ::= <style specifier>;+
-///  <style specifier> ::= <tag> : <value>
+///  <style specifier> ::= <tag> : <value> | reset
 ///          <content> ::= any character except \, [, ], (, )
 ///              <tag> ::= (see below)
 ///            <value> ::= (see below)
@@ -55,15 +187,16 @@ impl ColorizeError {
 ///
 /// # Tags
 ///
-/// There are three different ways that styling can be applied: foreground color, background color,
-/// and text decoration. By default, text hsa no styling applied.
+/// There are four different ways that styling can be applied: foreground color, background color,
+/// underline color, and text decoration. By default, text hsa no styling applied.
 ///
 /// ## Foreground color (`fg`)
 ///
 /// Accepted values: `black`, `red`, `green`, `yellow`, `blue`, `magenta`, `cyan`, `white`
 ///
 /// Controls the foreground color of the text. Colors can be made more intense using the `bright-`
-/// prefix.
+/// prefix. `gray` and `grey` are aliases of `bright-black`. `default` (or `none`/`unset`/`reset`)
+/// clears an inherited color, leaving the terminal's default color.
 ///
 /// ## Background color (`bg`)
 ///
@@ -72,6 +205,13 @@ impl ColorizeError {
 /// Controls the background color of the text. Colors can be made more intense using the `bright-`
 /// prefix.
 ///
+/// ## Underline color (`ul`)
+///
+/// Accepted values: the same as `fg`
+///
+/// Controls the color of underlines, independently of the text. Only terminals that support
+/// colored underlines display it. It can also be written as `underline-color`.
+///
 /// ## Text decoration (`deco`)
 ///
 /// Accepted values: `bold`, `dim`, `italic`, `underline`, `fast-blink`, `slow-blink`, `invert`,
@@ -80,6 +220,18 @@ impl ColorizeError {
 /// Controls additional text decoration. Multiple text decorations can be applied by separating the
 /// styles with a comma (i.e. `deco:bold,italic`).
 ///
+/// ## Reset (`reset`)
+///
+/// Discards all styling inherited from the parent markup. It does not accept a value and can be
+/// combined with other tags (i.e. `reset;fg:red`).
+///
+/// ## Raw content (`raw`)
+///
+/// Takes the content literally, without interpreting any markup or escape sequences (i.e.
+/// `[raw](a [fg:red](b))` outputs `a [fg:red](b)`). The content ends at the matching `)`, so
+/// parentheses within it must be balanced, unless they are escaped as `\(` or `\)`. It does not
+/// accept a value and can be combined with other tags.
+///
 /// # Escape sequences
 ///
 /// Certain control characters must be escaped to include them in your text. The valid escape
@@ -95,35 +247,268 @@ impl ColorizeError {
 ///
 /// Additionally, trailing whitespace can be removed by preceding it with a `\`. The types of
 /// whitespace that can be removed are newlines (`\n`), carriage returns (`\r`), spaces (` `),
-/// and tabs (`\t`).
+/// tabs (`\t`), form feeds (`\x0c`), and vertical tabs (`\x0b`).
+/// To keep a space or tab instead, such as trailing whitespace, write it as `\s` or `\t`
+/// respectively.
+///
+/// Any ASCII character can also be written as `\x` followed by its two digit hexadecimal code
+/// (i.e. `\x41` for `A`), and any other character as `\u{...}` with its hexadecimal code point
+/// (i.e. `\u{1F600}`).
+///
+/// # Sticky styles
+///
+/// When a style specifier isn't followed by any content, the style applies to the rest of the
+/// enclosing content, or to the rest of the text at the top level (i.e. `[fg:red](a [deco:bold]b) c`
+/// makes only `b` bold). Markup following a sticky style is nested within it.
+///
+/// # Comments
+///
+/// Text between `[#:` and the next unescaped `]` is a comment and is removed from the output.
+/// Comments can be placed anywhere text is allowed.
 ///
 /// # Notes
 ///
 /// - If tags are repeated in a style specifier, the value of the last tag takes precedence
 /// - When nesting styled markup, styles of the parent will be applied unless overridden
-/// - There is currently no way to remove text decorations from the children of nested markup
+/// - Text decorations can only be removed from the children of nested markup using `reset`
+/// - Styled markup with empty content (i.e. `[fg:red]()`) produces no output, use a sticky style to
+///   style the text after it
 #[pyfunction]
 #[pyo3(name = "colorize")]
-#[pyo3(signature = (source, file="inline", wrap=None, supports_color=true))]
+#[pyo3(signature = (
+    source,
+    file="inline",
+    wrap=None,
+    supports_color=true,
+    respect_no_color=false,
+    strip_ansi=false,
+    reset_mode="targeted",
+    collapse_redundant=true,
+    minimize=false,
+    suppress_blink=false,
+    suppress_colors=false,
+    suppress_decorations=false,
+    reopen_on_newline=false,
+    max_depth=DEFAULT_MAX_DEPTH,
+))]
+#[allow(clippy::too_many_arguments)]
 fn py_colorize(
-    source: &str,
-    file: &str,
+    source: &Bound<'_, PyAny>,
+    file: Option<&str>,
     wrap: Option<usize>,
     supports_color: bool,
+    respect_no_color: bool,
+    strip_ansi: bool,
+    reset_mode: &str,
+    collapse_redundant: bool,
+    minimize: bool,
+    suppress_blink: bool,
+    suppress_colors: bool,
+    suppress_decorations: bool,
+    reopen_on_newline: bool,
+    max_depth: usize,
 ) -> PyResult<String> {
     if let Some(0) = wrap {
         return Err(PyTypeError::new_err("wrap width must be greater than 0"));
     }
 
-    let styled = colorize(source, Options { supports_color })
-        .map_err(|errors| ColorizeError::from_report(errors.into(), source, file))?;
+    let reset_mode = match reset_mode {
+        "targeted" => ResetMode::Targeted,
+        "full" => ResetMode::Full,
+        _ => {
+            return Err(PyValueError::new_err(
+                "reset mode must be either \"targeted\" or \"full\"",
+            ))
+        }
+    };
+
+    let py = source.py();
+    let source = source_text(source)?;
+    let source = source.as_ref();
+
+    let options = ColorizeOptions {
+        output: Options {
+            supports_color,
+            respect_no_color,
+            strip_ansi,
+            reset_mode,
+            collapse_redundant,
+            minimize,
+            suppress_blink,
+            suppress_colors,
+            suppress_decorations,
+            reopen_on_newline,
+        },
+        max_depth,
+        ..ColorizeOptions::default()
+    };
+
+    // styling doesn't touch any Python objects, so other threads can run while large inputs are
+    // being converted
+    let styled = py.allow_threads(|| {
+        colorize_with(source, &options).map(|styled| match wrap {
+            Some(width) => textwrap::fill(&styled, WrapOptions::new(width)),
+            None => styled,
+        })
+    });
+
+    styled.map_err(|errors| ColorizeError::from_report(errors.into(), source, file))
+}
+
+/// Get the text from a `str`, or from `bytes` or a `bytearray` containing UTF-8
+fn source_text<'a>(source: &'a Bound<'_, PyAny>) -> PyResult<Cow<'a, str>> {
+    if let Ok(text) = source.downcast::<PyString>() {
+        return text.to_cow();
+    }
+
+    let py = source.py();
+    let decode_error = |bytes: &[u8], err| match PyUnicodeDecodeError::new_utf8(py, bytes, err) {
+        Ok(err) => PyErr::from_value(err.into_any()),
+        Err(err) => err,
+    };
+
+    if let Ok(bytes) = source.downcast::<PyBytes>() {
+        let bytes = bytes.as_bytes();
+        let text = std::str::from_utf8(bytes).map_err(|err| decode_error(bytes, err))?;
+        Ok(Cow::Borrowed(text))
+    } else if let Ok(bytes) = source.downcast::<PyByteArray>() {
+        let bytes = bytes.to_vec();
+        String::from_utf8(bytes)
+            .map(Cow::Owned)
+            .map_err(|err| decode_error(err.as_bytes(), err.utf8_error()))
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "source must be str, bytes, or bytearray, not {}",
+            source.get_type().name()?
+        )))
+    }
+}
+
+/// Convert styled markup to ANSI escape codes for many pieces of text at once.
+///
+/// Behaves the same as calling `colorize` on each source, but converts all of them in a single
+/// call, which avoids the per-call overhead when styling many short strings. The results are
+/// returned in the same order as the sources.
+///
+/// If any of the sources contain invalid markup, an exception is raised for the first one. The
+/// index of the failing source is available as the `index` attribute of the exception, and is
+/// included in the file name of the report (i.e. `inline[3]`), even when no file name is given
+/// (i.e. `[3]`).
+#[pyfunction]
+#[pyo3(name = "colorize_all")]
+#[pyo3(signature = (sources, file="inline"))]
+fn py_colorize_all(
+    py: Python<'_>,
+    sources: Vec<String>,
+    file: Option<&str>,
+) -> PyResult<Vec<String>> {
+    // styling doesn't touch any Python objects, so other threads can run while the sources are
+    // being converted
+    let styled = py.allow_threads(|| {
+        sources
+            .iter()
+            .enumerate()
+            .map(|(index, source)| {
+                colorize(source, Options::default()).map_err(|errors| (index, errors))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    });
 
-    Ok(match wrap {
-        Some(width) => textwrap::fill(&styled, WrapOptions::new(width)),
-        None => styled,
+    styled.map_err(|(index, errors)| {
+        let file = match file.filter(|file| !file.is_empty()) {
+            Some(file) => format!("{file}[{index}]"),
+            None => format!("[{index}]"),
+        };
+        let err = ColorizeError::from_report(errors.into(), &sources[index], Some(&file));
+        match err.value(py).setattr("index", index) {
+            Ok(()) => err,
+            Err(e) => e,
+        }
     })
 }
 
+/// Convert styled markup to ANSI escape codes, limiting the text to a maximum width.
+///
+/// Behaves the same as `colorize`, but stops emitting content once the visible width of the text
+/// reaches `width` columns. When the text is cut, the optional `ellipsis` is appended using the
+/// style active at the cut, and counts towards the width. Any styles that were open at the cut are
+/// always reset.
+#[pyfunction]
+#[pyo3(name = "truncate")]
+#[pyo3(signature = (source, width, ellipsis=None, file="inline"))]
+fn py_truncate(
+    source: &str,
+    width: usize,
+    ellipsis: Option<&str>,
+    file: Option<&str>,
+) -> PyResult<String> {
+    truncate(source, width, ellipsis)
+        .map_err(|errors| ColorizeError::from_report(errors.into(), source, file))
+}
+
+/// Remove all styled markup from a piece of text, leaving only the text.
+///
+/// Any ANSI escape sequences already present in the source are removed as well, which is useful
+/// when writing the text somewhere other than a terminal, like a log file. Invalid markup causes
+/// an exception, just like `colorize`.
+///
+/// >>> antsi.strip("[fg:red;deco:bold](error): something went wrong")
+/// 'error: something went wrong'
+#[pyfunction]
+#[pyo3(name = "strip")]
+#[pyo3(signature = (source, file="inline"))]
+fn py_strip(source: &str, file: Option<&str>) -> PyResult<String> {
+    strip(source).map_err(|errors| ColorizeError::from_report(errors.into(), source, file))
+}
+
+/// Convert styled markup to HTML for displaying in a browser.
+///
+/// Each piece of styled text is wrapped in a `<span>` with inline styles, and all other text is
+/// escaped. Colors are mapped to the conventional xterm values. Decorations without a CSS
+/// equivalent (i.e. blinking and inverting) are dropped, as are any ANSI escape sequences present
+/// in the source. Invalid markup causes an exception, just like `colorize`.
+///
+/// >>> antsi.to_html("[fg:red](hi)")
+/// '<span style="color:#cd0000">hi</span>'
+#[pyfunction]
+#[pyo3(name = "to_html")]
+#[pyo3(signature = (source, file="inline"))]
+fn py_to_html(source: &str, file: Option<&str>) -> PyResult<String> {
+    to_html(source).map_err(|errors| ColorizeError::from_report(errors.into(), source, file))
+}
+
+/// Compute the on-screen width of styled markup.
+///
+/// Returns the number of columns the text occupies once the markup is displayed, which is useful
+/// for aligning columns. Style specifiers, comments, and existing ANSI escape sequences take up no
+/// space, while wide characters take up two columns. Invalid markup causes an exception, just like
+/// `colorize`.
+///
+/// >>> antsi.width("[fg:red](error): \\(see above\\)")
+/// 18
+#[pyfunction]
+#[pyo3(name = "width")]
+#[pyo3(signature = (source, file="inline"))]
+fn py_width(source: &str, file: Option<&str>) -> PyResult<usize> {
+    width(source).map_err(|errors| ColorizeError::from_report(errors.into(), source, file))
+}
+
+/// Describe how styled markup is displayed, without using any escape codes.
+///
+/// Each run of styled text is wrapped in markers listing the style it is displayed with, including
+/// everything inherited from the enclosing markup. This is useful for debugging how styles combine,
+/// or for showing styled text in tests and documentation. Invalid markup causes an exception, just
+/// like `colorize`.
+///
+/// >>> antsi.annotate("[fg:red](a [deco:bold](b))")
+/// '⟨fg=red⟩a ⟨/⟩⟨fg=red,bold⟩b⟨/⟩'
+#[pyfunction]
+#[pyo3(name = "annotate")]
+#[pyo3(signature = (source, file="inline"))]
+fn py_annotate(source: &str, file: Option<&str>) -> PyResult<String> {
+    annotate(source).map_err(|errors| ColorizeError::from_report(errors.into(), source, file))
+}
+
 /// Escape all styled markup in a piece of text
 #[pyfunction]
 #[pyo3(name = "escape")]
@@ -136,7 +521,13 @@ fn py_escape(source: &str) -> String {
 #[pyo3(name = "_antsi")]
 fn antsi(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add("ColorizeError", m.py().get_type::<ColorizeError>())?;
+    m.add_function(wrap_pyfunction!(py_annotate, m)?)?;
     m.add_function(wrap_pyfunction!(py_colorize, m)?)?;
+    m.add_function(wrap_pyfunction!(py_colorize_all, m)?)?;
     m.add_function(wrap_pyfunction!(py_escape, m)?)?;
+    m.add_function(wrap_pyfunction!(py_strip, m)?)?;
+    m.add_function(wrap_pyfunction!(py_to_html, m)?)?;
+    m.add_function(wrap_pyfunction!(py_width, m)?)?;
+    m.add_function(wrap_pyfunction!(py_truncate, m)?)?;
     Ok(())
 }
@@ -0,0 +1,220 @@
+use crate::{
+    ast::{Color, CurrentStyle, Decoration},
+    parser::{Event, Events},
+};
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use text_size::TextRange;
+
+/// A potential issue with a piece of text that does not prevent it from being styled
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+    pub span: TextRange,
+    pub kind: WarningKind,
+}
+
+impl Warning {
+    /// Convert the warning into a user-friendly diagnostic
+    pub fn to_diagnostic<FileId>(&self, file: FileId) -> Diagnostic<FileId> {
+        let (property, value) = match self.kind {
+            WarningKind::RedundantForeground(color) => ("foreground", color.name()),
+            WarningKind::RedundantBackground(color) => ("background", color.name()),
+            WarningKind::RedundantUnderlineColor(color) => ("underline color", color.name()),
+            WarningKind::RedundantDecoration(decoration) => ("decoration", decoration.name()),
+        };
+
+        Diagnostic::warning()
+            .with_message(format!("redundant {property} style"))
+            .with_labels(vec![Label::primary(file, self.span).with_message(format!(
+                "the {property} `{value}` is already applied by the parent markup"
+            ))])
+    }
+}
+
+/// The kind of issue that was found
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WarningKind {
+    /// The foreground color is the same as the one that would be inherited
+    RedundantForeground(Color),
+    /// The background color is the same as the one that would be inherited
+    RedundantBackground(Color),
+    /// The underline color is the same as the one that would be inherited
+    RedundantUnderlineColor(Color),
+    /// The text decoration is already applied by the parent markup
+    RedundantDecoration(Decoration),
+}
+
+/// Find styles that have no effect because they are the same as the inherited styles
+///
+/// Each warning points at the style specifier containing the redundant style. Only markup before
+/// the first parsing error is checked, as errors are reported when the text is styled.
+pub fn lint(source: &str) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    let mut events = Events::new(source);
+    let mut stack = vec![CurrentStyle::default()];
+    while let Some(Ok(event)) = events.next() {
+        match event {
+            Event::StyleOpen(style) => {
+                let current = stack.last().expect("stack always has the root style");
+                let span = events.style_span().expect("style was just opened");
+
+                if !style.reset {
+                    if let Some(color) = style.foreground.filter(|&c| c == current.foreground()) {
+                        let kind = WarningKind::RedundantForeground(color);
+                        warnings.push(Warning { span, kind });
+                    }
+                    if let Some(color) = style.background.filter(|&c| c == current.background()) {
+                        let kind = WarningKind::RedundantBackground(color);
+                        warnings.push(Warning { span, kind });
+                    }
+                    if let Some(color) = style
+                        .underline_color
+                        .filter(|&c| c == current.underline_color())
+                    {
+                        let kind = WarningKind::RedundantUnderlineColor(color);
+                        warnings.push(Warning { span, kind });
+                    }
+                    for decoration in style.decoration.iter().flatten() {
+                        if current.decoration().contains(decoration) {
+                            let kind = WarningKind::RedundantDecoration(*decoration);
+                            warnings.push(Warning { span, kind });
+                        }
+                    }
+                }
+
+                stack.push(current.extend(&style));
+            }
+            Event::StyleClose => {
+                stack.pop();
+            }
+            Event::Text(_) | Event::Char(_) | Event::Raw(_) => {}
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint, Warning, WarningKind};
+    use crate::ast::{Color, Decoration};
+
+    #[test]
+    fn no_markup() {
+        assert_eq!(lint("plain text"), vec![]);
+    }
+
+    #[test]
+    fn different_styles() {
+        assert_eq!(
+            lint("[fg:red;deco:bold]([fg:blue;bg:red;deco:italic](x))"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn redundant_foreground() {
+        assert_eq!(
+            lint("[fg:red]([fg:red](x))"),
+            vec![Warning {
+                span: span!(9..17),
+                kind: WarningKind::RedundantForeground(Color::Red),
+            }]
+        );
+    }
+
+    #[test]
+    fn redundant_background() {
+        assert_eq!(
+            lint("[bg:blue](a [fg:red](b [bg:blue](c)))"),
+            vec![Warning {
+                span: span!(23..32),
+                kind: WarningKind::RedundantBackground(Color::Blue),
+            }]
+        );
+    }
+
+    #[test]
+    fn redundant_underline_color() {
+        assert_eq!(
+            lint("[ul:red]([ul:red](x))"),
+            vec![Warning {
+                span: span!(9..17),
+                kind: WarningKind::RedundantUnderlineColor(Color::Red),
+            }]
+        );
+        assert_eq!(lint("[ul:red]([ul:blue](x))"), vec![]);
+    }
+
+    #[test]
+    fn redundant_default_color() {
+        assert_eq!(
+            lint("[fg:default](x)"),
+            vec![Warning {
+                span: span!(0..12),
+                kind: WarningKind::RedundantForeground(Color::Default),
+            }]
+        );
+    }
+
+    #[test]
+    fn redundant_decorations() {
+        assert_eq!(
+            lint("[deco:bold,dim]([deco:italic,faint,bold](x))"),
+            vec![
+                Warning {
+                    span: span!(16..40),
+                    kind: WarningKind::RedundantDecoration(Decoration::Dim),
+                },
+                Warning {
+                    span: span!(16..40),
+                    kind: WarningKind::RedundantDecoration(Decoration::Bold),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn siblings_do_not_inherit() {
+        assert_eq!(lint("[fg:red](a) [fg:red](b)"), vec![]);
+    }
+
+    #[test]
+    fn overridden_parent_style() {
+        assert_eq!(lint("[fg:red]([fg:blue]([fg:red](x)))"), vec![]);
+    }
+
+    #[test]
+    fn reset_is_never_redundant() {
+        assert_eq!(
+            lint("[fg:red;deco:bold]([reset;fg:red;deco:bold](x))"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn stops_at_first_error() {
+        assert_eq!(
+            lint("[fg:red]([fg:red](x) \\q [fg:red](y))"),
+            vec![Warning {
+                span: span!(9..17),
+                kind: WarningKind::RedundantForeground(Color::Red),
+            }]
+        );
+    }
+
+    #[test]
+    fn diagnostic_message() {
+        let warning = Warning {
+            span: span!(9..17),
+            kind: WarningKind::RedundantDecoration(Decoration::StrikeThrough),
+        };
+        let diagnostic = warning.to_diagnostic(());
+
+        assert_eq!(diagnostic.message, "redundant decoration style");
+        assert_eq!(
+            diagnostic.labels[0].message,
+            "the decoration `strike-through` is already applied by the parent markup"
+        );
+    }
+}
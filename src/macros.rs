@@ -21,10 +21,18 @@ macro_rules! style {
         $style.background = Some($crate::ast::Color::$color);
         style!(@internal $style; $( $rest ) *)
     }};
+    (@internal $style:expr; ul: $color:ident ; $( $rest:tt )* ) => {{
+        $style.underline_color = Some($crate::ast::Color::$color);
+        style!(@internal $style; $( $rest ) *)
+    }};
     (@internal $style:expr; deco: $( $decoration:ident ),+ ; $( $rest:tt )* ) => {{
         $style.decoration = Some(set!{ $( $crate::ast::Decoration::$decoration, )+ });
         style!(@internal $style; $( $rest ) *)
     }};
+    (@internal $style:expr; reset ; $( $rest:tt )* ) => {{
+        $style.reset = true;
+        style!(@internal $style; $( $rest ) *)
+    }};
     (@internal $style:expr; ) => {
         $style
     };
@@ -0,0 +1,414 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{
+    ast::{Decoration, Token, Tokens},
+    error::Error,
+    parser::Parser,
+};
+
+/// Convert styled markup to Markdown
+///
+/// Bold, italic, and strike-through decorations become `**`, `*`, and `~~` respectively. Italic
+/// text that is made bold by the same markup, or markup nested directly within it, uses `_`
+/// instead (i.e. `**_text_**`), so the delimiters don't run together. Markdown has no way to
+/// express color, so colors are dropped, along with the other decorations and any ANSI escape
+/// sequences present in the source. The text is escaped so it isn't interpreted as Markdown.
+///
+/// Adjacent markup with the same emphasis shares a single set of delimiters. Markdown doesn't
+/// recognize delimiters with punctuation on the inside and a letter or digit on the outside (i.e.
+/// `**a.**b`), so emphasis in that position is written as HTML tags instead (`<strong>`, `<em>`,
+/// and `<del>`), as is emphasis whose delimiters would run into those of the emphasis next to it.
+///
+/// Emphasis can't be undone in Markdown, so resetting the styles has no effect on the emphasis of
+/// the enclosing markup.
+pub fn to_markdown(source: &str) -> Result<String, Vec<Error>> {
+    let (tokens, errors) = Parser::new(source).parse();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    // markup nested directly within other markup is merged, so it can share a set of delimiters
+    let mut tokens = Tokens::from(tokens);
+    tokens.normalize();
+    let tokens = Vec::from(tokens);
+
+    let mut text = String::with_capacity(source.len());
+    let spans = convert_tokens(&mut text, &tokens);
+    Ok(insert_markers(&text, spans))
+}
+
+/// The kinds of emphasis that are currently open
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Emphasis {
+    bold: bool,
+    italic: bool,
+    strike_through: bool,
+}
+
+impl Emphasis {
+    /// Whether any kind of emphasis is set
+    fn any(self) -> bool {
+        self.bold || self.italic || self.strike_through
+    }
+
+    /// The markers that open and close the emphasis, from the outermost to the innermost
+    ///
+    /// HTML tags are used instead of the Markdown delimiters when `html` is set.
+    fn markers(self, html: bool) -> Vec<(&'static str, &'static str)> {
+        let mut markers = Vec::with_capacity(3);
+        if self.bold {
+            markers.push(match html {
+                true => ("<strong>", "</strong>"),
+                false => ("**", "**"),
+            });
+        }
+        if self.italic {
+            markers.push(match (html, self.bold) {
+                (true, _) => ("<em>", "</em>"),
+                (false, true) => ("_", "_"),
+                (false, false) => ("*", "*"),
+            });
+        }
+        if self.strike_through {
+            markers.push(match html {
+                true => ("<del>", "</del>"),
+                false => ("~~", "~~"),
+            });
+        }
+        markers
+    }
+}
+
+/// A range of the escaped text that opens some emphasis
+#[derive(Debug)]
+struct Span {
+    range: Range<usize>,
+    opened: Emphasis,
+}
+
+/// Convert the tokens into escaped text, returning the ranges of the text that open emphasis
+///
+/// The spans are returned in the order they end in, so nested spans come before the spans
+/// containing them.
+fn convert_tokens(output: &mut String, tokens: &[Token]) -> Vec<Span> {
+    let mut spans = Vec::new();
+
+    // each level keeps the emphasis it opened and where its content starts
+    let mut stack = vec![(tokens.iter(), Emphasis::default(), Emphasis::default(), 0)];
+    while let Some((tokens, active, _, _)) = stack.last_mut() {
+        match tokens.next() {
+            Some(Token::Content(content, _)) => push_escaped(output, content),
+            Some(Token::Raw(..)) => {}
+            Some(Token::Styled { content, style, .. }) => {
+                let has = |decoration| {
+                    style
+                        .decoration
+                        .as_ref()
+                        .is_some_and(|decorations| decorations.contains(&decoration))
+                };
+
+                // emphasis that is already open would only be closed early by repeating it
+                let opened = Emphasis {
+                    bold: has(Decoration::Bold) && !active.bold,
+                    italic: has(Decoration::Italic) && !active.italic,
+                    strike_through: has(Decoration::StrikeThrough) && !active.strike_through,
+                };
+                let nested = Emphasis {
+                    bold: active.bold || opened.bold,
+                    italic: active.italic || opened.italic,
+                    strike_through: active.strike_through || opened.strike_through,
+                };
+
+                stack.push((content.iter(), nested, opened, output.len()));
+            }
+            None => {
+                let (_, _, opened, start) = stack.pop().expect("stack is not empty");
+                if !opened.any() {
+                    continue;
+                }
+
+                // emphasis can't start or end with whitespace, so it is moved outside the markers
+                let content = &output[start..];
+                let text = content.trim();
+                if text.is_empty() {
+                    continue;
+                }
+
+                let start = start + (content.len() - content.trim_start().len());
+                spans.push(Span {
+                    range: start..start + text.len(),
+                    opened,
+                });
+            }
+        }
+    }
+
+    spans
+}
+
+/// Insert the markers for each span of emphasis into the escaped text
+fn insert_markers(text: &str, mut spans: Vec<Span>) -> String {
+    // spans with a boundary at the same position are nested, so they close from the innermost, and
+    // open from the outermost
+    let mut boundaries = BTreeMap::<usize, (Vec<usize>, Vec<usize>)>::new();
+    for (index, span) in spans.iter().enumerate() {
+        boundaries.entry(span.range.end).or_default().0.push(index);
+        boundaries
+            .entry(span.range.start)
+            .or_default()
+            .1
+            .insert(0, index);
+    }
+
+    // the outermost spans closing and opening at a position are siblings, so when they open the
+    // same emphasis, the second is merged into the first rather than closing and reopening it
+    let mut merged_into = (0..spans.len()).collect::<Vec<_>>();
+    for (closing, opening) in boundaries.values_mut() {
+        closing
+            .iter_mut()
+            .for_each(|index| *index = merged_into[*index]);
+
+        while let (Some(&first), Some(&second)) = (closing.last(), opening.first()) {
+            if spans[first].opened != spans[second].opened {
+                break;
+            }
+
+            spans[first].range.end = spans[second].range.end;
+            merged_into[second] = first;
+            closing.pop();
+            opening.remove(0);
+        }
+    }
+
+    // markers that aren't recognized as emphasis where they are placed fall back to HTML tags
+    let mut html = vec![false; spans.len()];
+    for (&position, (closing, opening)) in &boundaries {
+        let before = text[..position].chars().next_back();
+        let after = text[position..].chars().next();
+
+        for (i, &index) in opening.iter().enumerate() {
+            let outermost = i == 0;
+            let inside_punctuation = spans[index].opened.markers(false).len() > 1
+                || i + 1 < opening.len()
+                || after.is_some_and(is_punctuation);
+
+            html[index] |= outermost
+                && (!closing.is_empty()
+                    || before.is_some_and(char::is_alphanumeric) && inside_punctuation);
+        }
+        for (i, &index) in closing.iter().enumerate() {
+            let outermost = i + 1 == closing.len();
+            let inside_punctuation = spans[index].opened.markers(false).len() > 1
+                || i > 0
+                || before.is_some_and(is_punctuation);
+
+            html[index] |= outermost
+                && (!opening.is_empty()
+                    || after.is_some_and(char::is_alphanumeric) && inside_punctuation);
+        }
+    }
+
+    let mut result = String::with_capacity(text.len() + spans.len() * 4);
+    let mut position = 0;
+    for (boundary, (closing, opening)) in boundaries {
+        result.push_str(&text[position..boundary]);
+        position = boundary;
+
+        for index in closing {
+            let markers = spans[index].opened.markers(html[index]);
+            markers
+                .iter()
+                .rev()
+                .for_each(|(_, close)| result.push_str(close));
+        }
+        for index in opening {
+            let markers = spans[index].opened.markers(html[index]);
+            markers.iter().for_each(|(open, _)| result.push_str(open));
+        }
+    }
+    result.push_str(&text[position..]);
+
+    result
+}
+
+/// Whether the character counts as punctuation when Markdown decides if a marker is emphasis
+fn is_punctuation(ch: char) -> bool {
+    !ch.is_alphanumeric() && !ch.is_whitespace()
+}
+
+/// Push the content to the output, escaping any characters that have a special meaning in Markdown
+///
+/// Characters that only start a block at the beginning of a line (i.e. list markers) are escaped
+/// wherever they could be at the start of one.
+fn push_escaped(output: &mut String, content: &str) {
+    let mut line_start = output.is_empty() || output.ends_with('\n');
+    // ordered lists start with a number followed by `.` or `)`
+    let mut digits = false;
+
+    for ch in content.chars() {
+        let escape = match ch {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '~' | '|' | '&' => true,
+            '-' | '+' | '=' => line_start && !digits,
+            '.' | ')' => line_start && digits,
+            _ => false,
+        };
+        if escape {
+            output.push('\\');
+        }
+        output.push(ch);
+
+        match ch {
+            '\n' => (line_start, digits) = (true, false),
+            ' ' | '\t' if line_start && !digits => {}
+            '0'..='9' if line_start => digits = true,
+            _ => (line_start, digits) = (false, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{push_escaped, to_markdown};
+
+    fn escaped(content: &str) -> String {
+        let mut output = String::new();
+        push_escaped(&mut output, content);
+        output
+    }
+
+    #[test]
+    fn unstyled() {
+        assert_eq!(to_markdown("plain text").unwrap(), "plain text");
+    }
+
+    #[test]
+    fn bold() {
+        insta::assert_snapshot!(to_markdown("some [deco:bold](bold) text").unwrap());
+    }
+
+    #[test]
+    fn italic() {
+        insta::assert_snapshot!(to_markdown("some [deco:italic](italic) text").unwrap());
+    }
+
+    #[test]
+    fn strike_through() {
+        insta::assert_snapshot!(to_markdown("some [deco:strike-through](struck) text").unwrap());
+    }
+
+    #[test]
+    fn nested() {
+        insta::assert_snapshot!(to_markdown(
+            "[deco:bold]([deco:italic](x)) [deco:bold](a [deco:italic](b [deco:strike-through](c) b) a)"
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn bold_and_italic_together() {
+        assert_eq!(
+            to_markdown("[deco:bold,italic,strike-through](x)").unwrap(),
+            "**_~~x~~_**"
+        );
+    }
+
+    #[test]
+    fn repeated_emphasis_is_not_reopened() {
+        assert_eq!(
+            to_markdown("[deco:bold](a [deco:bold;fg:red](b) c)").unwrap(),
+            "**a b c**"
+        );
+    }
+
+    #[test]
+    fn whitespace_is_moved_outside_emphasis() {
+        assert_eq!(
+            to_markdown("a[deco:bold]( b )c [deco:italic]( )").unwrap(),
+            "a **b** c  "
+        );
+    }
+
+    #[test]
+    fn colors_and_other_decorations_are_dropped() {
+        assert_eq!(
+            to_markdown("[fg:red;bg:blue](a) [deco:dim,underline,invert](b) \x1b[1mc").unwrap(),
+            "a b c"
+        );
+    }
+
+    #[test]
+    fn adjacent_emphasis_is_merged() {
+        assert_eq!(
+            to_markdown("[deco:bold](a)[deco:bold](b)").unwrap(),
+            "**ab**"
+        );
+        assert_eq!(
+            to_markdown("[deco:bold](a [deco:italic](b))[deco:bold](c)[fg:red]([deco:bold](d))")
+                .unwrap(),
+            "**a *b*cd**"
+        );
+    }
+
+    #[test]
+    fn adjacent_different_emphasis_uses_html() {
+        assert_eq!(
+            to_markdown("[deco:bold](a)[deco:italic](b)").unwrap(),
+            "<strong>a</strong><em>b</em>"
+        );
+    }
+
+    #[test]
+    fn punctuation_inside_a_word_uses_html() {
+        assert_eq!(
+            to_markdown("[deco:bold](a.)b").unwrap(),
+            "<strong>a.</strong>b"
+        );
+        assert_eq!(to_markdown("a[deco:italic](.b)").unwrap(), "a<em>.b</em>");
+        assert_eq!(
+            to_markdown("a[deco:bold,italic](b)").unwrap(),
+            "a<strong><em>b</em></strong>"
+        );
+    }
+
+    #[test]
+    fn emphasis_inside_a_word() {
+        assert_eq!(
+            to_markdown("a[deco:bold](b)c \\([deco:italic](.d.)\\)").unwrap(),
+            "a**b**c (*.d.*)"
+        );
+    }
+
+    #[test]
+    fn empty_token() {
+        assert_eq!(to_markdown("[deco:bold]()").unwrap(), "");
+    }
+
+    #[test]
+    fn invalid_markup() {
+        assert!(to_markdown("[fg:red](unclosed").is_err());
+    }
+
+    #[test]
+    fn special_characters_are_escaped() {
+        assert_eq!(
+            escaped("*a* _b_ `c` [d](e) <f> #g ~h~ |i| &j; \\k"),
+            "\\*a\\* \\_b\\_ \\`c\\` \\[d\\](e) \\<f\\> \\#g \\~h\\~ \\|i\\| \\&j; \\\\k"
+        );
+    }
+
+    #[test]
+    fn block_markers_are_escaped_at_line_start() {
+        assert_eq!(
+            escaped("- a\n  + b\n1. c\n2) d\n=\ne - f 3. g"),
+            "\\- a\n  \\+ b\n1\\. c\n2\\) d\n\\=\ne - f 3. g"
+        );
+    }
+
+    #[test]
+    fn content_is_escaped_within_emphasis() {
+        assert_eq!(
+            to_markdown("[deco:bold](\\[*\\])").unwrap(),
+            "**\\[\\*\\]**"
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use crate::color::sgr_parameters;
+
+/// Remove escape codes that have no visible effect from the output of [`colorize`](crate::colorize)
+///
+/// The state of the terminal is tracked across the whole output, and the codes between two pieces
+/// of text are replaced by the shortest sequence that produces the same state. Codes that are
+/// undone before any text is written, or that set a property to the value it already has, are
+/// dropped. Any other escape sequence is treated like text, so the state is always up to date when
+/// it is reached.
+///
+/// Output containing SGR codes that `colorize` doesn't produce (i.e. extended colors from escape
+/// sequences that were already present in the source) is returned unchanged, as their effect
+/// isn't tracked.
+pub(crate) fn minimize_sgr(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rendered = State::default();
+    let mut current = State::default();
+    let mut rest = input;
+
+    while let Some(index) = rest.find('\x1b') {
+        if index > 0 {
+            transition(&mut output, &mut rendered, current);
+            output.push_str(&rest[..index]);
+            rest = &rest[index..];
+        }
+
+        match sgr_parameters(rest) {
+            Some(parameters) => {
+                if current.apply(parameters).is_none() {
+                    return input.to_owned();
+                }
+                rest = &rest[parameters.len() + 3..];
+            }
+            None => {
+                transition(&mut output, &mut rendered, current);
+                output.push('\x1b');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    transition(&mut output, &mut rendered, current);
+    output.push_str(rest);
+    output
+}
+
+/// Write the shortest escape sequence that changes the rendered state to the target state
+fn transition(output: &mut String, rendered: &mut State, target: State) {
+    if *rendered == target {
+        return;
+    }
+
+    let targeted = rendered.codes_to(&target);
+    let full = {
+        let mut codes = vec![0];
+        codes.extend(State::default().codes_to(&target));
+        codes
+    };
+    let codes = if length(&full) < length(&targeted) {
+        full
+    } else {
+        targeted
+    };
+
+    output.push_str("\x1b[");
+    for (i, code) in codes.iter().enumerate() {
+        if i > 0 {
+            output.push(';');
+        }
+        output.push_str(&code.to_string());
+    }
+    output.push('m');
+
+    *rendered = target;
+}
+
+/// The number of bytes the codes take up once written as parameters
+fn length(codes: &[u8]) -> usize {
+    codes.iter().map(|code| code.to_string().len() + 1).sum()
+}
+
+/// The text attributes of the terminal that can be changed by `colorize`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct State {
+    foreground: Option<u8>,
+    background: Option<u8>,
+    /// The index of the underline color in the 256-color palette
+    underline_color: Option<u8>,
+    /// Whether each of the decorations from `1` (bold) to `9` (strike-through) is applied
+    decorations: [bool; 10],
+}
+
+impl State {
+    /// Update the state using the parameters of an SGR sequence
+    ///
+    /// Returns `None` if any of the parameters aren't ones that `colorize` produces.
+    fn apply(&mut self, parameters: &str) -> Option<()> {
+        let mut codes = parameters.split(';');
+
+        while let Some(code) = codes.next() {
+            let code = if code.is_empty() {
+                0
+            } else {
+                code.parse::<u8>().ok()?
+            };
+
+            match code {
+                0 => *self = State::default(),
+                30..=37 | 90..=97 => self.foreground = Some(code),
+                40..=47 | 100..=107 => self.background = Some(code),
+                39 => self.foreground = None,
+                49 => self.background = None,
+                // underline colors are always set using the 256-color palette
+                58 => {
+                    if codes.next()? != "5" {
+                        return None;
+                    }
+                    self.underline_color = Some(codes.next()?.parse().ok()?);
+                }
+                59 => self.underline_color = None,
+                1..=9 => {
+                    // the blink speeds replace each other
+                    if code == 5 || code == 6 {
+                        self.decorations[5] = false;
+                        self.decorations[6] = false;
+                    }
+                    self.decorations[usize::from(code)] = true;
+                }
+                22..=25 | 27..=29 => {
+                    for decoration in decorations_reset_by(code) {
+                        self.decorations[usize::from(*decoration)] = false;
+                    }
+                }
+                _ => return None,
+            }
+        }
+
+        Some(())
+    }
+
+    /// The codes that change this state into the target without clearing everything
+    fn codes_to(&self, target: &State) -> Vec<u8> {
+        let mut codes = Vec::new();
+
+        if self.foreground != target.foreground {
+            codes.push(target.foreground.unwrap_or(39));
+        }
+        if self.background != target.background {
+            codes.push(target.background.unwrap_or(49));
+        }
+        if self.underline_color != target.underline_color {
+            match target.underline_color {
+                Some(index) => codes.extend([58, 5, index]),
+                None => codes.push(59),
+            }
+        }
+
+        for reset in [22, 23, 24, 25, 27, 28, 29] {
+            let group = decorations_reset_by(reset);
+            let changed = group
+                .iter()
+                .any(|&d| self.decorations[usize::from(d)] != target.decorations[usize::from(d)]);
+            if !changed {
+                continue;
+            }
+
+            // a decoration can only be removed by resetting every decoration in its group
+            let removed = group
+                .iter()
+                .any(|&d| self.decorations[usize::from(d)] && !target.decorations[usize::from(d)]);
+            if removed {
+                codes.push(reset);
+            }
+
+            for &decoration in group {
+                let wanted = target.decorations[usize::from(decoration)];
+                if wanted && (removed || !self.decorations[usize::from(decoration)]) {
+                    codes.push(decoration);
+                }
+            }
+        }
+
+        codes
+    }
+}
+
+/// The decorations that are removed by a reset code
+fn decorations_reset_by(code: u8) -> &'static [u8] {
+    match code {
+        22 => &[1, 2],
+        23 => &[3],
+        24 => &[4],
+        25 => &[5, 6],
+        27 => &[7],
+        28 => &[8],
+        29 => &[9],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{minimize_sgr, State};
+    use crate::color::{colorize, Options, ResetMode};
+
+    /// The state each character of the output is rendered with
+    fn render(output: &str) -> Vec<(char, State)> {
+        let mut state = State::default();
+        let mut rendered = Vec::new();
+        let mut rest = output;
+
+        while let Some(ch) = rest.chars().next() {
+            if let Some(parameters) = super::sgr_parameters(rest) {
+                state.apply(parameters).unwrap();
+                rest = &rest[parameters.len() + 3..];
+            } else {
+                rendered.push((ch, state));
+                rest = &rest[ch.len_utf8()..];
+            }
+        }
+
+        rendered
+    }
+
+    /// Colorize the source, checking that minimizing the output doesn't change how it renders
+    fn minimized(source: &str, reset_mode: ResetMode) -> (String, String) {
+        let options = Options {
+            reset_mode,
+            ..Options::default()
+        };
+        let original = colorize(source, options).unwrap();
+        let minimized = minimize_sgr(&original);
+
+        assert_eq!(render(&minimized), render(&original), "{source:?}");
+        assert_eq!(
+            render(&format!("{minimized}x")).last().unwrap().1,
+            State::default(),
+            "{source:?} didn't end in the default state"
+        );
+        (original, minimized)
+    }
+
+    #[test]
+    fn unstyled_text_is_unchanged() {
+        assert_eq!(minimize_sgr("plain text"), "plain text");
+    }
+
+    #[test]
+    fn reset_to_default_uses_shortest_code() {
+        let (_, minimized) = minimized("a [fg:red](b [bg:blue](c)) d", ResetMode::Targeted);
+        assert_eq!(minimized, "a \x1b[31mb \x1b[44mc\x1b[0m d");
+    }
+
+    #[test]
+    fn reset_followed_by_same_style_is_dropped() {
+        assert_eq!(
+            minimize_sgr("\x1b[1ma\x1b[0;1mb\x1b[0m"),
+            "\x1b[1mab\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn styles_undone_before_text_are_dropped() {
+        assert_eq!(minimize_sgr("a\x1b[31m\x1b[39mb"), "ab");
+    }
+
+    #[test]
+    fn shorter_of_targeted_and_full_reset_is_used() {
+        assert_eq!(
+            minimize_sgr("\x1b[31;44;1;4ma\x1b[39;49;22;24m"),
+            "\x1b[31;44;1;4ma\x1b[0m"
+        );
+        assert_eq!(
+            minimize_sgr("\x1b[31;44;1ma\x1b[0;31;44mb\x1b[0m"),
+            "\x1b[31;44;1ma\x1b[22mb\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn removing_one_intensity_keeps_the_other() {
+        assert_eq!(
+            minimize_sgr("\x1b[31;44;1;2ma\x1b[22;1mb"),
+            "\x1b[31;44;1;2ma\x1b[22;1mb"
+        );
+    }
+
+    #[test]
+    fn other_escape_sequences_see_the_current_state() {
+        assert_eq!(
+            minimize_sgr("\x1b[31m\x1b[44m\x1b[2K\x1b[39;49m"),
+            "\x1b[31;44m\x1b[2K\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn underline_color_is_tracked() {
+        let (original, minimized) = minimized(
+            "[ul:red;deco:underline](a [ul:red](b) [ul:blue](c) a)",
+            ResetMode::Full,
+        );
+        assert_eq!(
+            minimized,
+            "\x1b[58;5;1;4ma b \x1b[58;5;4mc\x1b[58;5;1m a\x1b[0m"
+        );
+        assert!(minimized.len() < original.len());
+    }
+
+    #[test]
+    fn unknown_codes_leave_output_unchanged() {
+        let input = "\x1b[38;5;1ma\x1b[0m\x1b[31m\x1b[39m";
+        assert_eq!(minimize_sgr(input), input);
+
+        let input = "\x1b[58;2;1;2;3ma\x1b[59m\x1b[31m\x1b[39m";
+        assert_eq!(minimize_sgr(input), input);
+    }
+
+    #[test]
+    fn nested_full_reset_is_smaller() {
+        let (original, minimized) = minimized(
+            "[fg:red;deco:bold](a [bg:blue](b [deco:underline](c) b) a)",
+            ResetMode::Full,
+        );
+        assert_eq!(
+            minimized,
+            "\x1b[31;1ma \x1b[44mb \x1b[4mc\x1b[24m b\x1b[49m a\x1b[0m"
+        );
+        assert!(minimized.len() < original.len());
+    }
+
+    #[test]
+    fn nested_reset_specifier_is_smaller() {
+        let (original, minimized) = minimized(
+            "[fg:red;deco:bold](a [reset;fg:red;deco:bold,italic](b) a)",
+            ResetMode::Targeted,
+        );
+        assert_eq!(minimized, "\x1b[31;1ma \x1b[3mb\x1b[23m a\x1b[0m");
+        assert!(minimized.len() < original.len());
+    }
+
+    #[test]
+    fn redundant_nested_color_is_dropped() {
+        let (original, minimized) =
+            minimized("[fg:red](a [fg:blue]([fg:red](b)) a)", ResetMode::Targeted);
+        assert_eq!(minimized, "\x1b[31ma b a\x1b[0m");
+        assert!(minimized.len() < original.len());
+    }
+
+    #[test]
+    fn rendering_is_unchanged() {
+        for source in [
+            "[fg:red](a) [fg:red](b) [bg:blue](c)",
+            "[deco:blink-slow](a [deco:blink-fast](b) a)",
+            "[fg:red]sticky [deco:bold,dim]styles [reset]reset",
+            "[fg:green;bg:white;deco:bold,italic,underline,invert,hide,strike-through](a [reset](b) a)",
+            "[bg:blue]([bg:default](x) y)",
+        ] {
+            for reset_mode in [ResetMode::Targeted, ResetMode::Full] {
+                minimized(source, reset_mode);
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{
+    ast::{Color, Decoration, Style},
+    error::Error,
+    html::{css_color, render},
+};
+
+/// Convert styled markup to Pango markup, wrapping each styled piece of text in a `<span>` with
+/// the equivalent attributes
+///
+/// Colors are mapped to the same values as [`to_html`](crate::to_html), with underline colors
+/// becoming `underline_color`. Pango can't restore the
+/// default colors, so `default` colors are dropped and the inherited colors are kept, although a
+/// reset still undoes any inherited decorations. Decorations without a Pango equivalent (i.e.
+/// blinking, inverting, and hiding) are dropped, as are any ANSI escape sequences present in the
+/// source.
+pub fn to_pango(source: &str) -> Result<String, Vec<Error>> {
+    render(source, &span_attributes)
+}
+
+/// The Pango attributes and values that undo every inherited decoration
+const RESET_ATTRIBUTES: &[(&str, &str)] = &[
+    ("weight", "normal"),
+    ("style", "normal"),
+    ("underline", "none"),
+    ("strikethrough", "false"),
+    ("alpha", "100%"),
+];
+
+/// Generate the attributes of the `<span>` for a style
+///
+/// Returns `None` if the style has no Pango equivalent.
+fn span_attributes(style: &Style) -> Option<String> {
+    let mut attributes = Vec::new();
+
+    if style.reset {
+        attributes.extend(RESET_ATTRIBUTES.iter().copied());
+    }
+
+    let color = |color: Option<Color>| color.filter(|&c| c != Color::Default).map(css_color);
+    if let Some(foreground) = color(style.foreground) {
+        attributes.push(("foreground", foreground));
+    }
+    if let Some(background) = color(style.background) {
+        attributes.push(("background", background));
+    }
+    if let Some(underline_color) = color(style.underline_color) {
+        attributes.push(("underline_color", underline_color));
+    }
+
+    for decoration in style.decoration.iter().flatten() {
+        let attribute = match decoration {
+            Decoration::Bold => ("weight", "bold"),
+            Decoration::Dim => ("alpha", "50%"),
+            Decoration::Italic => ("style", "italic"),
+            Decoration::Underline => ("underline", "single"),
+            Decoration::StrikeThrough => ("strikethrough", "true"),
+            Decoration::SlowBlink
+            | Decoration::FastBlink
+            | Decoration::Invert
+            | Decoration::Hide => continue,
+        };
+        attributes.push(attribute);
+    }
+
+    // an attribute can only appear once in a span, so decorations replace the reset values
+    let mut deduplicated: Vec<(&str, &str)> = Vec::with_capacity(attributes.len());
+    for (name, value) in attributes {
+        match deduplicated
+            .iter_mut()
+            .find(|(existing, _)| *existing == name)
+        {
+            Some(attribute) => attribute.1 = value,
+            None => deduplicated.push((name, value)),
+        }
+    }
+
+    if deduplicated.is_empty() {
+        None
+    } else {
+        let attributes = deduplicated
+            .iter()
+            .map(|(name, value)| format!("{name}=\"{value}\""))
+            .collect::<Vec<_>>();
+        Some(attributes.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_pango;
+
+    #[test]
+    fn unstyled() {
+        assert_eq!(to_pango("plain text").unwrap(), "plain text");
+    }
+
+    #[test]
+    fn foreground() {
+        assert_eq!(
+            to_pango("[fg:red](red)").unwrap(),
+            "<span foreground=\"#cd0000\">red</span>"
+        );
+    }
+
+    #[test]
+    fn background() {
+        assert_eq!(
+            to_pango("[bg:bright-blue](blue)").unwrap(),
+            "<span background=\"#5c5cff\">blue</span>"
+        );
+    }
+
+    #[test]
+    fn underline_color() {
+        assert_eq!(
+            to_pango("[ul:red;deco:underline](x)").unwrap(),
+            "<span underline_color=\"#cd0000\" underline=\"single\">x</span>"
+        );
+    }
+
+    #[test]
+    fn default_color_is_dropped() {
+        assert_eq!(
+            to_pango("[fg:default;bg:default;ul:default](text)").unwrap(),
+            "text"
+        );
+    }
+
+    #[test]
+    fn decorations() {
+        assert_eq!(
+            to_pango("[deco:bold,dim,italic,underline,strikethrough](text)").unwrap(),
+            "<span weight=\"bold\" alpha=\"50%\" style=\"italic\" underline=\"single\" strikethrough=\"true\">text</span>"
+        );
+    }
+
+    #[test]
+    fn unsupported_decorations_are_dropped() {
+        assert_eq!(
+            to_pango("[deco:blink-slow,blink-fast,invert,hide](text)").unwrap(),
+            "text"
+        );
+    }
+
+    #[test]
+    fn multiple_styles() {
+        assert_eq!(
+            to_pango("[fg:black;bg:white;deco:bold](styled)").unwrap(),
+            "<span foreground=\"#000000\" background=\"#e5e5e5\" weight=\"bold\">styled</span>"
+        );
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(
+            to_pango("a [fg:red](b [bg:blue](c [deco:italic](d) c) b) a").unwrap(),
+            "a <span foreground=\"#cd0000\">b <span background=\"#0000ee\">c <span style=\"italic\">d</span> c</span> b</span> a"
+        );
+    }
+
+    #[test]
+    fn nested_siblings() {
+        assert_eq!(
+            to_pango("[deco:bold]([fg:red](a)[fg:green](b))").unwrap(),
+            "<span weight=\"bold\"><span foreground=\"#cd0000\">a</span><span foreground=\"#00cd00\">b</span></span>"
+        );
+    }
+
+    #[test]
+    fn reset() {
+        assert_eq!(
+            to_pango("[deco:bold](a [reset;fg:red](b))").unwrap(),
+            "<span weight=\"bold\">a <span weight=\"normal\" style=\"normal\" underline=\"none\" strikethrough=\"false\" alpha=\"100%\" foreground=\"#cd0000\">b</span></span>"
+        );
+    }
+
+    #[test]
+    fn reset_with_decorations() {
+        assert_eq!(
+            to_pango("[reset;deco:italic](text)").unwrap(),
+            "<span weight=\"normal\" style=\"italic\" underline=\"none\" strikethrough=\"false\" alpha=\"100%\">text</span>"
+        );
+    }
+
+    #[test]
+    fn empty_token() {
+        assert_eq!(to_pango("[fg:red]()").unwrap(), "");
+    }
+
+    #[test]
+    fn content_is_escaped() {
+        assert_eq!(
+            to_pango("<b>a & b</b> [fg:red](<i>)").unwrap(),
+            "&lt;b&gt;a &amp; b&lt;/b&gt; <span foreground=\"#cd0000\">&lt;i&gt;</span>"
+        );
+    }
+
+    #[test]
+    fn existing_escape_sequences_are_removed() {
+        assert_eq!(
+            to_pango("\x1b[1mbold\x1b[22m [fg:red](red)").unwrap(),
+            "bold <span foreground=\"#cd0000\">red</span>"
+        );
+    }
+
+    #[test]
+    fn invalid_markup() {
+        assert!(to_pango("[fg:red](unclosed").is_err());
+    }
+}
@@ -1,51 +1,81 @@
 use crate::{
     ast::{Token, Tokens},
+    delimiters::Delimiters,
     error::{Error, Reason},
     lexer::{Lexeme, Lexer, SyntaxKind},
 };
 use std::iter::Peekable;
+use text_size::{TextLen, TextRange, TextSize};
+
+pub use events::{Event, Events};
 
 mod content;
+mod events;
 mod markup;
 mod style;
 mod text;
 
+/// The maximum number of styled markup that can be nested within each other by default
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 128;
+
 /// Convert a piece of text, potentially containing styled markup, to a sequence of tokens
+///
+/// This is the only parser in the crate. [`colorize`](crate::colorize), the HTML renderer, and the
+/// [`Events`] stream are all built on top of it, so they accept exactly the same markup.
 pub struct Parser<'source> {
+    source: &'source str,
     lexer: Peekable<Lexer<'source>>,
+    delimiters: Delimiters,
+    offset: TextSize,
+    max_depth: usize,
+    emphasis: bool,
     errors: Vec<Error>,
 }
 
 impl<'source> Parser<'source> {
     pub fn new(input: &'source str) -> Self {
+        Self::with_delimiters(input, Delimiters::default())
+    }
+
+    /// Create a parser for markup using a custom set of delimiters
+    pub fn with_delimiters(input: &'source str, delimiters: Delimiters) -> Self {
         Self {
-            lexer: Lexer::new(input).peekable(),
+            source: input,
+            lexer: Lexer::with_delimiters(input, delimiters).peekable(),
+            delimiters,
+            offset: TextSize::new(0),
+            max_depth: DEFAULT_MAX_DEPTH,
+            emphasis: false,
             errors: Vec::new(),
         }
     }
 
+    /// Limit how deeply styled markup can be nested before parsing fails
+    ///
+    /// Only markup with its own content counts towards the depth, so any number of sticky styles
+    /// can be used within the same content.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Recognize Markdown-style emphasis, where `*text*` is italic and `**text**` is bold
+    pub fn with_markdown_emphasis(mut self, enabled: bool) -> Self {
+        self.emphasis = enabled;
+        self
+    }
+
     /// Perform the parsing operation
-    pub fn parse(mut self) -> (Vec<Token>, Vec<Error>) {
+    pub fn parse(mut self) -> (Vec<Token<'source>>, Vec<Error>) {
         let mut tokens = Tokens::default();
 
         loop {
             tokens.extend(text::text(&mut self).unwrap_or_default());
 
             if let Some(lexeme) = self.peek() {
-                match lexeme {
-                    SyntaxKind::ParenthesisOpen => {
-                        self.error(Reason::UnescapedControlCharacter('('))
-                    }
-                    SyntaxKind::ParenthesisClose => {
-                        self.error(Reason::UnescapedControlCharacter(')'))
-                    }
-                    SyntaxKind::SquareBracketOpen => {
-                        self.error(Reason::UnescapedControlCharacter('['))
-                    }
-                    SyntaxKind::SquareBracketClose => {
-                        self.error(Reason::UnescapedControlCharacter(']'))
-                    }
-                    _ => self.error(Reason::Expected(vec![SyntaxKind::Eof])),
+                match self.delimiters.character(lexeme) {
+                    Some(character) => self.error(Reason::UnescapedControlCharacter(character)),
+                    None => self.error(Reason::Expected(vec![SyntaxKind::Eof])),
                 }
 
                 self.bump();
@@ -63,13 +93,34 @@ impl<'source> Parser<'source> {
     }
 
     /// Get the next lexeme from the lexer without consuming it
-    pub(crate) fn peek_lexeme(&mut self) -> Option<&Lexeme<'_>> {
+    pub(crate) fn peek_lexeme(&mut self) -> Option<&Lexeme<'source>> {
         self.lexer.peek()
     }
 
     /// Pop the next syntax item from the lexer
-    pub(crate) fn bump(&mut self) -> Lexeme {
-        self.lexer.next().expect("missing token")
+    pub(crate) fn bump(&mut self) -> Lexeme<'source> {
+        let lexeme = self.lexer.next().expect("missing token");
+        self.offset = lexeme.span.end();
+        lexeme
+    }
+
+    /// The position in the source after the most recently consumed syntax item
+    pub(crate) fn offset(&self) -> TextSize {
+        self.offset
+    }
+
+    /// Check if the parser is currently at an emphasis marker, if emphasis is recognized
+    pub(crate) fn at_emphasis(&mut self) -> bool {
+        self.emphasis
+            && matches!(
+                self.peek(),
+                Some(SyntaxKind::Asterisk | SyntaxKind::DoubleAsterisk)
+            )
+    }
+
+    /// Whether the character can follow a backslash in an escape sequence
+    pub(crate) fn is_escapable(&self, character: char) -> bool {
+        self.delimiters.is_escapable(character) || (self.emphasis && character == '*')
     }
 
     /// Check if the parser is currently at the given syntax item
@@ -78,7 +129,7 @@ impl<'source> Parser<'source> {
     }
 
     /// Expect a syntax item, emitting an error if it isn't present
-    pub(crate) fn expect(&mut self, kind: SyntaxKind) -> Option<Lexeme> {
+    pub(crate) fn expect(&mut self, kind: SyntaxKind) -> Option<Lexeme<'source>> {
         if self.at(kind) {
             Some(self.bump())
         } else {
@@ -88,26 +139,48 @@ impl<'source> Parser<'source> {
     }
 
     /// Consume lexemes until a non-whitespace lexeme is reached
+    ///
+    /// Escaped whitespace is treated the same as any other whitespace.
     pub(crate) fn consume_whitespace(&mut self) {
-        while let Some(SyntaxKind::Whitespace) = self.peek() {
+        while let Some(SyntaxKind::Whitespace | SyntaxKind::EscapeWhitespace) = self.peek() {
             self.bump();
         }
     }
 
     /// Report an error during parsing
     pub(crate) fn error(&mut self, reason: Reason) {
+        // errors at the end of the input point just past the last character
         let (span, at) = match self.peek_lexeme() {
-            Some(lexeme) => (Some(lexeme.span), lexeme.kind),
-            None => (None, SyntaxKind::Eof),
+            Some(lexeme) => (lexeme.span, lexeme.kind),
+            None => (TextRange::empty(self.source.text_len()), SyntaxKind::Eof),
         };
 
-        self.errors.push(Error { span, at, reason })
+        self.errors.push(Error {
+            span: Some(span),
+            at,
+            reason,
+        })
+    }
+
+    /// Report an error that points at an earlier syntax item rather than the current one
+    pub(crate) fn error_at(&mut self, span: TextRange, reason: Reason) {
+        let at = self.peek().unwrap_or(SyntaxKind::Eof);
+        self.errors.push(Error {
+            span: Some(span),
+            at,
+            reason,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ast::Token;
+    use crate::{
+        ast::Token,
+        error::{Error, Reason},
+        lexer::SyntaxKind,
+    };
+    use text_size::{TextLen, TextRange};
 
     macro_rules! with_source {
         (
@@ -161,7 +234,7 @@ mod tests {
     #[test]
     fn lowercase_alphabetic() {
         with_source!("abcdef", |result, errors| {
-            assert_eq!(result, vec![Token::Content(String::from("abcdef"))]);
+            assert_eq!(result, vec![Token::Content("abcdef".into(), span!(0..6))]);
             assert!(errors.is_empty());
         });
     }
@@ -169,7 +242,7 @@ mod tests {
     #[test]
     fn uppercase_alphabetic() {
         with_source!("ABCDEF", |result, errors| {
-            assert_eq!(result, vec![Token::Content(String::from("ABCDEF"))]);
+            assert_eq!(result, vec![Token::Content("ABCDEF".into(), span!(0..6))]);
             assert!(errors.is_empty());
         });
     }
@@ -177,7 +250,7 @@ mod tests {
     #[test]
     fn mixed_case_alphabetic() {
         with_source!("aBcDeF", |result, errors| {
-            assert_eq!(result, vec![Token::Content(String::from("aBcDeF"))]);
+            assert_eq!(result, vec![Token::Content("aBcDeF".into(), span!(0..6))]);
             assert!(errors.is_empty());
         });
     }
@@ -185,7 +258,7 @@ mod tests {
     #[test]
     fn numeric() {
         with_source!("123456", |result, errors| {
-            assert_eq!(result, vec![Token::Content(String::from("123456"))]);
+            assert_eq!(result, vec![Token::Content("123456".into(), span!(0..6))]);
             assert!(errors.is_empty());
         });
     }
@@ -193,7 +266,7 @@ mod tests {
     #[test]
     fn lowercase_alphanumeric() {
         with_source!("abc123", |result, errors| {
-            assert_eq!(result, vec![Token::Content(String::from("abc123"))]);
+            assert_eq!(result, vec![Token::Content("abc123".into(), span!(0..6))]);
             assert!(errors.is_empty());
         });
     }
@@ -201,7 +274,7 @@ mod tests {
     #[test]
     fn uppercase_alphanumeric() {
         with_source!("ABC123", |result, errors| {
-            assert_eq!(result, vec![Token::Content(String::from("ABC123"))]);
+            assert_eq!(result, vec![Token::Content("ABC123".into(), span!(0..6))]);
             assert!(errors.is_empty());
         });
     }
@@ -209,7 +282,7 @@ mod tests {
     #[test]
     fn mixed_case_alphanumeric() {
         with_source!("AbCd1234", |result, errors| {
-            assert_eq!(result, vec![Token::Content(String::from("AbCd1234"))]);
+            assert_eq!(result, vec![Token::Content("AbCd1234".into(), span!(0..8))]);
             assert!(errors.is_empty());
         });
     }
@@ -217,7 +290,7 @@ mod tests {
     #[test]
     fn special_characters() {
         with_source!("!@#$%^", |result, errors| {
-            assert_eq!(result, vec![Token::Content(String::from("!@#$%^"))]);
+            assert_eq!(result, vec![Token::Content("!@#$%^".into(), span!(0..6))]);
             assert!(errors.is_empty());
         });
     }
@@ -225,7 +298,10 @@ mod tests {
     #[test]
     fn mixed_characters() {
         with_source!("ABCdef123!@#", |result, errors| {
-            assert_eq!(result, vec![Token::Content(String::from("ABCdef123!@#"))]);
+            assert_eq!(
+                result,
+                vec![Token::Content("ABCdef123!@#".into(), span!(0..12))]
+            );
             assert!(errors.is_empty());
         });
     }
@@ -233,7 +309,7 @@ mod tests {
     #[test]
     fn escaped_characters() {
         with_source!("\\(\\)\\[\\]", |result, errors| {
-            assert_eq!(result, vec![Token::Content(String::from("()[]"))]);
+            assert_eq!(result, vec![Token::Content("()[]".into(), span!(0..8))]);
             assert!(errors.is_empty());
         });
     }
@@ -246,6 +322,98 @@ mod tests {
         });
     }
 
+    #[test]
+    fn existing_escape_sequences() {
+        with_source!("before \x1b[31m after", |result, errors| {
+            assert_eq!(
+                result,
+                vec![
+                    Token::Content("before ".into(), span!(0..7)),
+                    Token::Raw("\x1b[31m", span!(7..12)),
+                    Token::Content(" after".into(), span!(12..18)),
+                ]
+            );
+            assert!(errors.is_empty());
+        });
+    }
+
+    #[test]
+    fn existing_escape_sequences_in_token() {
+        with_source!("[fg:red](\x1b[1mbold\x1b[22m)", |result, errors| {
+            assert_eq!(
+                result,
+                vec![Token::Styled {
+                    content: vec![
+                        Token::Raw("\x1b[1m", span!(9..13)),
+                        Token::Content("bold".into(), span!(13..17)),
+                        Token::Raw("\x1b[22m", span!(17..22)),
+                    ],
+                    style: style!(fg: Red;),
+                    span: span!(0..23),
+                }]
+            );
+            assert!(errors.is_empty());
+        });
+    }
+
+    #[test]
+    fn invalid_color_points_at_value() {
+        with_source!("[fg:pink](x)", |_result, errors| {
+            assert_eq!(
+                errors.first(),
+                Some(&Error {
+                    span: Some(span!(4..8)),
+                    at: SyntaxKind::Text,
+                    reason: Reason::Expected(vec![SyntaxKind::Color]),
+                })
+            );
+        });
+    }
+
+    #[test]
+    fn invalid_specifier_recovers_trailing_markup() {
+        with_source!("[fg:pink](a) [fg:red](b)", |result, errors| {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(
+                result,
+                vec![
+                    Token::Styled {
+                        style: style!(),
+                        content: vec![Token::Content("a".into(), span!(10..11))],
+                        span: span!(0..12),
+                    },
+                    Token::Content(" ".into(), span!(12..13)),
+                    Token::Styled {
+                        style: style!(fg: Red;),
+                        content: vec![Token::Content("b".into(), span!(22..23))],
+                        span: span!(13..24),
+                    },
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn invalid_specifiers_are_each_reported() {
+        with_source!(
+            "[bg:blue;foreground:red](a) [fg:red bg:blue](b) [deco:bold](c)",
+            |result, errors| {
+                assert_eq!(
+                    errors.iter().map(|error| error.span).collect::<Vec<_>>(),
+                    vec![Some(span!(9..19)), Some(span!(36..38))]
+                );
+                assert_eq!(
+                    result.last(),
+                    Some(&Token::Styled {
+                        style: style!(deco: Bold;),
+                        content: vec![Token::Content("c".into(), span!(60..61))],
+                        span: span!(48..62),
+                    })
+                );
+            }
+        );
+    }
+
     #[test]
     fn empty_token() {
         with_source!("[fg:red]()", |result, errors| {
@@ -419,4 +587,127 @@ mod tests {
             assert_snapshot!({ snapshot_suffix => "errors" }, errors);
         });
     }
+
+    #[test]
+    fn parse_empty_input() {
+        let (result, errors) = super::Parser::new("").parse();
+        assert_eq!(result, vec![]);
+        assert_eq!(errors, vec![]);
+    }
+
+    #[test]
+    fn parse_truncated_input_reports_end_of_file() {
+        let sources = [
+            "[",
+            "[fg",
+            "[fg:",
+            "[fg:red",
+            "[fg:red;",
+            "[fg:red;deco:bold,",
+            "[fg:red](",
+            "[fg:red](text",
+            "[fg:red](text [bg:blue](nested)",
+        ];
+        for source in sources {
+            let (_, errors) = super::Parser::new(source).parse();
+            let error = errors
+                .last()
+                .unwrap_or_else(|| panic!("{source}: no errors"));
+            assert_eq!(error.at, SyntaxKind::Eof, "{source}");
+
+            // unclosed groups point at where they were opened, everything else at the end of input
+            let end = TextRange::empty(source.text_len());
+            match error.reason {
+                Reason::UnterminatedSpecifier | Reason::UnterminatedContent => {
+                    assert_ne!(error.span, Some(end), "{source}")
+                }
+                _ => assert_eq!(error.span, Some(end), "{source}"),
+            }
+        }
+    }
+
+    fn nested(depth: usize) -> String {
+        format!("{}x{}", "[fg:red](".repeat(depth), ")".repeat(depth))
+    }
+
+    #[test]
+    fn parse_nesting_at_max_depth() {
+        let source = nested(super::DEFAULT_MAX_DEPTH);
+        let (_, errors) = super::Parser::new(&source).parse();
+        assert_eq!(errors, vec![]);
+    }
+
+    #[test]
+    fn parse_nesting_too_deep() {
+        let source = nested(100_000);
+        let (result, errors) = super::Parser::new(&source).parse();
+
+        // each level of nesting is 9 characters long
+        assert_eq!(result, vec![]);
+        assert_eq!(
+            errors,
+            vec![Error {
+                span: Some(span!(1152..1153)),
+                at: SyntaxKind::SquareBracketOpen,
+                reason: Reason::NestingTooDeep(super::DEFAULT_MAX_DEPTH),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_sticky_styles_are_not_nested() {
+        // each sticky style is nested within the previous one, but they share the same content
+        let source = "[fg:red]a [fg:blue]b ".repeat(super::DEFAULT_MAX_DEPTH);
+        let (_, errors) = super::Parser::new(&source).parse();
+        assert_eq!(errors, vec![]);
+
+        let source = format!("[fg:red]({})", "[deco:bold]a ".repeat(200));
+        let (_, errors) = super::Parser::new(&source).with_max_depth(1).parse();
+        assert_eq!(errors, vec![]);
+
+        let (_, errors) = super::Parser::new("[fg:red]a [bg:red](b [deco:bold](c))")
+            .with_max_depth(1)
+            .parse();
+        assert_eq!(errors[0].span, Some(span!(21..22)));
+        assert_eq!(errors[0].reason, Reason::NestingTooDeep(1));
+    }
+
+    #[test]
+    fn parse_nesting_without_max_depth() {
+        // deep enough to overflow the stack of a test thread if each level of nesting recursed
+        let source = nested(10_000);
+        let (result, errors) = super::Parser::new(&source)
+            .with_max_depth(usize::MAX)
+            .parse();
+        assert_eq!(errors, vec![]);
+
+        let mut depth = 0;
+        let mut tokens = &result;
+        while let [Token::Styled { content, .. }] = tokens.as_slice() {
+            depth += 1;
+            tokens = content;
+        }
+        assert_eq!(depth, 10_000);
+        assert_eq!(
+            tokens,
+            &vec![Token::Content("x".into(), span!(90_000..90_001))]
+        );
+    }
+
+    #[test]
+    fn parse_nesting_with_custom_max_depth() {
+        let source = nested(3);
+        let (_, errors) = super::Parser::new(&source).with_max_depth(3).parse();
+        assert_eq!(errors, vec![]);
+
+        let (_, errors) = super::Parser::new(&source).with_max_depth(2).parse();
+        assert_eq!(
+            errors,
+            vec![Error {
+                span: Some(span!(18..19)),
+                at: SyntaxKind::SquareBracketOpen,
+                reason: Reason::NestingTooDeep(2),
+            }]
+        );
+    }
 }
@@ -1,26 +1,94 @@
-use super::{text::text, Parser};
-use crate::{ast::Tokens, lexer::SyntaxKind};
+use super::Parser;
+use crate::{error::Reason, lexer::SyntaxKind};
+use text_size::TextRange;
+
+/// Parse the next piece of the content of a raw block, returning the text and where it is in the
+/// source
+///
+/// Nothing in a raw block is interpreted, except for the content delimiters. Parentheses must be
+/// balanced, so `depth` tracks how many are currently open, and an escaped delimiter (i.e. `\)`)
+/// produces the delimiter on its own. Returns `None` once the parenthesis that closes the block,
+/// or the end of the input, is reached.
+pub(super) fn raw<'source>(
+    p: &mut Parser<'source>,
+    depth: &mut usize,
+) -> Option<(&'source str, TextRange)> {
+    let delimiters = p.delimiters;
+    let is_escaped_delimiter = |text: &str| {
+        let character = text.chars().nth(1);
+        character.is_some_and(|c| delimiters.kind(c).is_some_and(is_content_delimiter))
+    };
+
+    let lexeme = p.peek_lexeme()?;
+    match lexeme.kind {
+        SyntaxKind::ParenthesisClose if *depth == 0 => return None,
+        SyntaxKind::EscapeCharacter if is_escaped_delimiter(lexeme.text) => {
+            let lexeme = p.bump();
+            return Some((&lexeme.text[1..], lexeme.span));
+        }
+        _ => {}
+    }
 
-/// Parse a piece of styled content
-pub(crate) fn content(p: &mut Parser) -> Option<Tokens> {
-    p.expect(SyntaxKind::ParenthesisOpen)?;
+    // everything up to the next escaped delimiter is contiguous, so it can be borrowed as one
+    let mut span = None::<TextRange>;
+    while let Some(lexeme) = p.peek_lexeme() {
+        match lexeme.kind {
+            SyntaxKind::ParenthesisClose if *depth == 0 => break,
+            SyntaxKind::EscapeCharacter if is_escaped_delimiter(lexeme.text) => break,
+            SyntaxKind::ParenthesisOpen => *depth += 1,
+            SyntaxKind::ParenthesisClose => *depth -= 1,
+            _ => {}
+        }
+
+        let lexeme = p.bump();
+        span = Some(span.map_or(lexeme.span, |span| span.cover(lexeme.span)));
+    }
 
-    let tokens = text(p)?;
+    span.map(|span| (&p.source[span], span))
+}
+
+fn is_content_delimiter(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::ParenthesisOpen | SyntaxKind::ParenthesisClose
+    )
+}
 
+/// Start a piece of styled content, returning the span of its opening parenthesis
+pub(super) fn open(p: &mut Parser) -> Option<TextRange> {
+    p.expect(SyntaxKind::ParenthesisOpen)
+        .map(|lexeme| lexeme.span)
+}
+
+/// Finish a piece of styled content that was started by the given parenthesis
+pub(super) fn close(p: &mut Parser, open: TextRange) -> Option<()> {
+    if p.peek().is_none() {
+        p.error_at(open, Reason::UnterminatedContent);
+        return None;
+    }
     p.expect(SyntaxKind::ParenthesisClose)?;
 
-    Some(tokens)
+    Some(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{content, Parser};
+    use super::{close, open, Parser};
     use crate::{
         ast::{Token, Tokens},
         error::{Error, Reason},
         lexer::SyntaxKind,
+        parser::text::text,
     };
 
+    /// Parse a piece of styled content on its own
+    fn content<'source>(p: &mut Parser<'source>) -> Option<Tokens<'source>> {
+        let open = open(p)?;
+        let tokens = text(p)?;
+        close(p, open)?;
+        Some(tokens)
+    }
+
     #[test]
     fn empty() {
         let mut parser = Parser::new("()");
@@ -32,9 +100,10 @@ mod tests {
         let mut parser = Parser::new("(abcdefghijklmnopqrstuvwxyz)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "abcdefghijklmnopqrstuvwxyz"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "abcdefghijklmnopqrstuvwxyz".into(),
+                span!(1..27)
+            )]))
         )
     }
 
@@ -43,9 +112,10 @@ mod tests {
         let mut parser = Parser::new("(ABCDEFGHIJKLMNOPQRSTUVWXYZ)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "ABCDEFGHIJKLMNOPQRSTUVWXYZ"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZ".into(),
+                span!(1..27)
+            )]))
         )
     }
 
@@ -54,9 +124,10 @@ mod tests {
         let mut parser = Parser::new("(AaBbCcDdEeFfGgHhIiJjKkLlMmNnOoPpQqRrSsTtUuVvWwXxYuZz)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "AaBbCcDdEeFfGgHhIiJjKkLlMmNnOoPpQqRrSsTtUuVvWwXxYuZz"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "AaBbCcDdEeFfGgHhIiJjKkLlMmNnOoPpQqRrSsTtUuVvWwXxYuZz".into(),
+                span!(1..53)
+            )]))
         )
     }
 
@@ -65,9 +136,10 @@ mod tests {
         let mut parser = Parser::new("(~!@#$%^&*-=_+~)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "~!@#$%^&*-=_+~"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "~!@#$%^&*-=_+~".into(),
+                span!(1..15)
+            )]))
         )
     }
 
@@ -76,7 +148,10 @@ mod tests {
         let mut parser = Parser::new("( \n\t\r)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(" \n\t\r"))]))
+            Some(Tokens::from(vec![Token::Content(
+                " \n\t\r".into(),
+                span!(1..5)
+            )]))
         )
     }
 
@@ -85,7 +160,10 @@ mod tests {
         let mut parser = Parser::new("(black)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("black"))]))
+            Some(Tokens::from(vec![Token::Content(
+                "black".into(),
+                span!(1..6)
+            )]))
         );
     }
 
@@ -94,9 +172,10 @@ mod tests {
         let mut parser = Parser::new("(bright-blue)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "bright-blue"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "bright-blue".into(),
+                span!(1..12)
+            )]))
         );
     }
 
@@ -105,7 +184,10 @@ mod tests {
         let mut parser = Parser::new("(default)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("default"))]))
+            Some(Tokens::from(vec![Token::Content(
+                "default".into(),
+                span!(1..8)
+            )]))
         );
     }
 
@@ -114,9 +196,10 @@ mod tests {
         let mut parser = Parser::new("(fast-blink)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "fast-blink"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "fast-blink".into(),
+                span!(1..11)
+            )]))
         )
     }
 
@@ -125,7 +208,7 @@ mod tests {
         let mut parser = Parser::new("(:)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(":"))]))
+            Some(Tokens::from(vec![Token::Content(":".into(), span!(1..2))]))
         )
     }
 
@@ -134,7 +217,7 @@ mod tests {
         let mut parser = Parser::new("(;)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(";"))]))
+            Some(Tokens::from(vec![Token::Content(";".into(), span!(1..2))]))
         )
     }
 
@@ -143,7 +226,7 @@ mod tests {
         let mut parser = Parser::new("(,)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(","))]))
+            Some(Tokens::from(vec![Token::Content(",".into(), span!(1..2))]))
         )
     }
 
@@ -152,7 +235,7 @@ mod tests {
         let mut parser = Parser::new("(fg)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("fg"))]))
+            Some(Tokens::from(vec![Token::Content("fg".into(), span!(1..3))]))
         );
     }
 
@@ -161,7 +244,7 @@ mod tests {
         let mut parser = Parser::new("(bg)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("bg"))]))
+            Some(Tokens::from(vec![Token::Content("bg".into(), span!(1..3))]))
         );
     }
 
@@ -170,7 +253,10 @@ mod tests {
         let mut parser = Parser::new("(deco)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("deco"))]))
+            Some(Tokens::from(vec![Token::Content(
+                "deco".into(),
+                span!(1..5)
+            )]))
         );
     }
 
@@ -179,7 +265,7 @@ mod tests {
         let mut parser = Parser::new("(\\\\)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("\\"))]))
+            Some(Tokens::from(vec![Token::Content("\\".into(), span!(1..3))]))
         )
     }
 
@@ -188,7 +274,7 @@ mod tests {
         let mut parser = Parser::new("(\\[)");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("["))]))
+            Some(Tokens::from(vec![Token::Content("[".into(), span!(1..3))]))
         )
     }
 
@@ -197,7 +283,7 @@ mod tests {
         let mut parser = Parser::new("(\\])");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("]"))]))
+            Some(Tokens::from(vec![Token::Content("]".into(), span!(1..3))]))
         )
     }
 
@@ -206,7 +292,7 @@ mod tests {
         let mut parser = Parser::new("(\\()");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("("))]))
+            Some(Tokens::from(vec![Token::Content("(".into(), span!(1..3))]))
         )
     }
 
@@ -215,7 +301,7 @@ mod tests {
         let mut parser = Parser::new("(\\))");
         assert_eq!(
             content(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(")"))]))
+            Some(Tokens::from(vec![Token::Content(")".into(), span!(1..3))]))
         )
     }
 
@@ -232,9 +318,9 @@ mod tests {
         assert_eq!(
             parser.errors,
             vec![Error {
-                span: None,
+                span: Some(span!(0..1)),
                 at: SyntaxKind::Eof,
-                reason: Reason::Expected(vec![SyntaxKind::ParenthesisClose])
+                reason: Reason::UnterminatedContent,
             }]
         );
     }
@@ -251,7 +337,10 @@ mod tests {
                 reason: Reason::Expected(vec![
                     SyntaxKind::ForegroundSpecifier,
                     SyntaxKind::BackgroundSpecifier,
-                    SyntaxKind::DecorationSpecifier
+                    SyntaxKind::UnderlineColorSpecifier,
+                    SyntaxKind::DecorationSpecifier,
+                    SyntaxKind::ResetSpecifier,
+                    SyntaxKind::RawSpecifier
                 ])
             }]
         );
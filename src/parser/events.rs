@@ -0,0 +1,669 @@
+use super::{
+    content,
+    style::specifiers,
+    text::{decode_hex, decode_unicode, decode_whitespace, is_plain_text},
+    Parser,
+};
+use crate::{
+    ast::Style,
+    delimiters::Delimiters,
+    error::{Error, Reason},
+    lexer::SyntaxKind,
+};
+use text_size::TextRange;
+
+/// A single piece of styled markup
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event<'source> {
+    /// The start of a piece of styled text
+    StyleOpen(Style),
+    /// A piece of text that inherits the styles of all the currently open styles
+    Text(&'source str),
+    /// A character written as a unicode escape, which can't be borrowed from the source
+    Char(char),
+    /// An ANSI escape sequence that was already present in the source
+    Raw(&'source str),
+    /// The end of the most recently opened style
+    StyleClose,
+}
+
+/// Lazily parse a piece of text, potentially containing styled markup, into a sequence of
+/// [`Event`]s
+///
+/// Unlike parsing into a sequence of tokens, the whole token tree is never built, and all the text
+/// is borrowed from the source, except for unicode escapes, which produce an [`Event::Char`].
+/// Adjacent text may be split across multiple [`Event::Text`]s (i.e. around escape sequences).
+/// Parsing stops at the first error.
+pub struct Events<'source> {
+    parser: Parser<'source>,
+    source: &'source str,
+    /// The span of the opening delimiter of each piece of content that is currently open, or `None`
+    /// for sticky styles, which are closed along with their enclosing content
+    open: Vec<Option<TextRange>>,
+    /// How many pieces of content are open, which is what the maximum depth limits
+    depth: usize,
+    /// How many parentheses are open within the raw block being parsed, if there is one
+    raw: Option<usize>,
+    style_span: Option<TextRange>,
+    finished: bool,
+}
+
+impl<'source> Events<'source> {
+    pub fn new(source: &'source str) -> Self {
+        Self::with_delimiters(source, Delimiters::default())
+    }
+
+    /// Lazily parse markup using a custom set of delimiters
+    pub fn with_delimiters(source: &'source str, delimiters: Delimiters) -> Self {
+        Self {
+            parser: Parser::with_delimiters(source, delimiters),
+            source,
+            open: Vec::new(),
+            depth: 0,
+            raw: None,
+            style_span: None,
+            finished: false,
+        }
+    }
+
+    /// Limit how deeply styled markup can be nested before parsing fails
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.parser.max_depth = max_depth;
+        self
+    }
+
+    /// The span of the style specifier for the most recent [`Event::StyleOpen`]
+    pub(crate) fn style_span(&self) -> Option<TextRange> {
+        self.style_span
+    }
+
+    /// How many pieces of content the parser is within, ignoring any sticky styles
+    pub(crate) fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Report an error at the current position and stop parsing
+    fn fail(&mut self, reason: Reason) -> Option<Result<Event<'source>, Error>> {
+        self.parser.error(reason);
+        self.take_error()
+    }
+
+    /// Stop parsing, returning the first error that was reported
+    fn take_error(&mut self) -> Option<Result<Event<'source>, Error>> {
+        self.finished = true;
+        self.parser.errors.drain(..).next().map(Err)
+    }
+}
+
+impl<'source> Iterator for Events<'source> {
+    type Item = Result<Event<'source>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        if let Some(depth) = &mut self.raw {
+            if let Some((text, _)) = content::raw(&mut self.parser, depth) {
+                return Some(Ok(Event::Text(text)));
+            }
+
+            // the end of the block is handled the same as any other content
+            self.raw = None;
+        }
+
+        loop {
+            let Some(kind) = self.parser.peek() else {
+                match self.open.last() {
+                    Some(None) => {
+                        self.open.pop();
+                        return Some(Ok(Event::StyleClose));
+                    }
+                    Some(&Some(open)) => {
+                        self.parser.error_at(open, Reason::UnterminatedContent);
+                        return self.take_error();
+                    }
+                    None => {
+                        self.finished = true;
+                        return None;
+                    }
+                }
+            };
+
+            match kind {
+                SyntaxKind::SquareBracketOpen => {
+                    let opening = self.parser.peek_lexeme().unwrap().span;
+                    let start = opening.start();
+
+                    // the style may be recovered, but parsing still stops at the first error
+                    let specifiers = specifiers(&mut self.parser);
+                    let Some(specifiers) = specifiers.filter(|_| self.parser.errors.is_empty())
+                    else {
+                        return self.take_error();
+                    };
+                    if self.parser.at(SyntaxKind::ParenthesisOpen) {
+                        // sticky styles don't have any content of their own, so they aren't
+                        // nested within each other
+                        if self.depth >= self.parser.max_depth {
+                            self.parser.errors.push(Error {
+                                span: Some(opening),
+                                at: kind,
+                                reason: Reason::NestingTooDeep(self.parser.max_depth),
+                            });
+                            return self.take_error();
+                        }
+
+                        let open = self.parser.bump();
+                        self.depth += 1;
+                        self.style_span = Some(TextRange::new(start, open.span.start()));
+                        self.open.push(Some(open.span));
+                    } else if specifiers.raw {
+                        return self.fail(Reason::Expected(vec![SyntaxKind::ParenthesisOpen]));
+                    } else {
+                        self.style_span = Some(TextRange::new(start, self.parser.offset()));
+                        self.open.push(None);
+                    }
+
+                    if specifiers.raw {
+                        self.raw = Some(0);
+                    }
+                    return Some(Ok(Event::StyleOpen(specifiers.style)));
+                }
+                SyntaxKind::ParenthesisClose if self.depth > 0 => {
+                    // sticky styles end with their enclosing content
+                    if self.open.pop().unwrap().is_some() {
+                        self.parser.bump();
+                        self.depth -= 1;
+                    }
+                    return Some(Ok(Event::StyleClose));
+                }
+                SyntaxKind::ParenthesisOpen | SyntaxKind::SquareBracketClose if self.depth > 0 => {
+                    return self.fail(Reason::Expected(vec![SyntaxKind::ParenthesisClose]));
+                }
+                SyntaxKind::ParenthesisOpen
+                | SyntaxKind::ParenthesisClose
+                | SyntaxKind::SquareBracketClose => {
+                    let character = self.parser.delimiters.character(kind).unwrap();
+                    return self.fail(Reason::UnescapedControlCharacter(character));
+                }
+                SyntaxKind::EscapeWhitespace | SyntaxKind::Comment => {
+                    self.parser.bump();
+                }
+                SyntaxKind::RawAnsi => {
+                    let lexeme = self.parser.bump();
+                    return Some(Ok(Event::Raw(lexeme.text)));
+                }
+                SyntaxKind::EscapeCharacter => {
+                    let text = self.parser.peek_lexeme().unwrap().text;
+                    let character = text.chars().nth(1).unwrap();
+                    let escaped = if self.parser.delimiters.is_escapable(character) {
+                        &text[1..]
+                    } else if let Some(whitespace) = decode_whitespace(character) {
+                        whitespace
+                    } else {
+                        return self.fail(match character {
+                            'x' => Reason::InvalidHexEscape,
+                            'u' => Reason::InvalidUnicodeEscape,
+                            _ => Reason::UnknownEscapeSequence(character),
+                        });
+                    };
+
+                    self.parser.bump();
+                    return Some(Ok(Event::Text(escaped)));
+                }
+                SyntaxKind::EscapeHex => {
+                    let text = self.parser.peek_lexeme().unwrap().text;
+                    let Some(character) = decode_hex(text) else {
+                        return self.fail(Reason::InvalidHexEscape);
+                    };
+
+                    self.parser.bump();
+                    return Some(Ok(Event::Text(character)));
+                }
+                SyntaxKind::EscapeUnicode => {
+                    let text = self.parser.peek_lexeme().unwrap().text;
+                    let Some(character) = decode_unicode(text) else {
+                        return self.fail(Reason::InvalidUnicodeEscape);
+                    };
+
+                    self.parser.bump();
+                    return Some(Ok(Event::Char(character)));
+                }
+                SyntaxKind::Unknown
+                    if self.parser.peek_lexeme().unwrap().text.starts_with('\\') =>
+                {
+                    // a backslash at the end of the input doesn't escape anything
+                    return self.fail(Reason::Expected(vec![SyntaxKind::EscapeCharacter]));
+                }
+                SyntaxKind::Eof => unreachable!(),
+                _ => {
+                    // consecutive plain lexemes are contiguous, so they can be borrowed as one
+                    let mut span = self.parser.bump().span;
+                    while self.parser.peek().is_some_and(is_plain_text) {
+                        span = span.cover(self.parser.bump().span);
+                    }
+
+                    return Some(Ok(Event::Text(&self.source[span])));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, Events};
+    use crate::{
+        ast::{Token, Tokens},
+        delimiters::Delimiters,
+        error::{Error, Reason},
+        lexer::SyntaxKind,
+        parser::Parser,
+    };
+    use text_size::TextRange;
+
+    fn events(source: &str) -> Result<Vec<Event<'_>>, Error> {
+        Events::new(source).collect()
+    }
+
+    /// Build the token tree from the event stream
+    ///
+    /// Events don't keep track of where they are in the source, so none of the tokens have spans.
+    fn tree(source: &str) -> Vec<Token<'_>> {
+        let mut stack = vec![(None, Tokens::default())];
+
+        for event in Events::new(source) {
+            match event.unwrap() {
+                Event::StyleOpen(style) => stack.push((Some(style), Tokens::default())),
+                Event::Text(text) => {
+                    let tokens = &mut stack.last_mut().unwrap().1;
+                    tokens.push_str(text, TextRange::default());
+                }
+                Event::Char(character) => {
+                    let tokens = &mut stack.last_mut().unwrap().1;
+                    tokens.push_char(character, TextRange::default());
+                }
+                Event::Raw(sequence) => {
+                    let tokens = &mut stack.last_mut().unwrap().1;
+                    tokens.push(Token::Raw(sequence, TextRange::default()));
+                }
+                Event::StyleClose => {
+                    let (style, content) = stack.pop().unwrap();
+                    stack.last_mut().unwrap().1.push(Token::Styled {
+                        style: style.unwrap(),
+                        content: content.into(),
+                        span: TextRange::default(),
+                    });
+                }
+            }
+        }
+
+        assert_eq!(stack.len(), 1);
+        stack.pop().unwrap().1.into()
+    }
+
+    /// Remove the spans from the tokens so they can be compared with the event stream
+    fn without_spans(tokens: &mut [Token]) {
+        for token in tokens {
+            match token {
+                Token::Content(_, span) | Token::Raw(_, span) => *span = TextRange::default(),
+                Token::Styled { content, span, .. } => {
+                    *span = TextRange::default();
+                    without_spans(content);
+                }
+            }
+        }
+    }
+
+    fn assert_matches_tree(source: &str) {
+        let (mut expected, errors) = Parser::new(source).parse();
+        assert!(errors.is_empty());
+
+        without_spans(&mut expected);
+        assert_eq!(tree(source), expected, "{source:?}");
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(events(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn plain_text_is_borrowed_as_one_event() {
+        assert_eq!(
+            events("some plain: text, here;"),
+            Ok(vec![Event::Text("some plain: text, here;")])
+        );
+    }
+
+    #[test]
+    fn styled_text() {
+        assert_eq!(
+            events("leading [fg:red](styled) trailing"),
+            Ok(vec![
+                Event::Text("leading "),
+                Event::StyleOpen(style!(fg: Red;)),
+                Event::Text("styled"),
+                Event::StyleClose,
+                Event::Text(" trailing"),
+            ])
+        );
+    }
+
+    #[test]
+    fn empty_token() {
+        assert_eq!(
+            events("[fg:red]()"),
+            Ok(vec![Event::StyleOpen(style!(fg: Red;)), Event::StyleClose])
+        );
+    }
+
+    #[test]
+    fn escape_sequences_split_text() {
+        assert_eq!(
+            events("a\\(b\\\n c"),
+            Ok(vec![
+                Event::Text("a"),
+                Event::Text("("),
+                Event::Text("b"),
+                Event::Text("c"),
+            ])
+        );
+    }
+
+    #[test]
+    fn hex_escapes() {
+        assert_eq!(
+            events("a\\x42\\x43"),
+            Ok(vec![Event::Text("a"), Event::Text("B"), Event::Text("C")])
+        );
+    }
+
+    #[test]
+    fn whitespace_escapes() {
+        assert_eq!(
+            events("hello\\s\\t"),
+            Ok(vec![
+                Event::Text("hello"),
+                Event::Text(" "),
+                Event::Text("\t")
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_hex_escape() {
+        assert_eq!(
+            events("\\xZZ"),
+            Err(Error {
+                span: Some(span!(0..2)),
+                at: SyntaxKind::EscapeCharacter,
+                reason: Reason::InvalidHexEscape,
+            })
+        );
+        assert_eq!(
+            events("\\x80"),
+            Err(Error {
+                span: Some(span!(0..4)),
+                at: SyntaxKind::EscapeHex,
+                reason: Reason::InvalidHexEscape,
+            })
+        );
+    }
+
+    #[test]
+    fn unicode_escapes() {
+        assert_eq!(
+            events("smile \\u{1F600}"),
+            Ok(vec![Event::Text("smile "), Event::Char('😀')])
+        );
+    }
+
+    #[test]
+    fn invalid_unicode_escape() {
+        assert_eq!(
+            events("\\u{DFFF}"),
+            Err(Error {
+                span: Some(span!(0..8)),
+                at: SyntaxKind::EscapeUnicode,
+                reason: Reason::InvalidUnicodeEscape,
+            })
+        );
+    }
+
+    #[test]
+    fn existing_escape_sequences() {
+        assert_eq!(
+            events("\x1b[1mbold\x1b[22m"),
+            Ok(vec![
+                Event::Raw("\x1b[1m"),
+                Event::Text("bold"),
+                Event::Raw("\x1b[22m"),
+            ])
+        );
+    }
+
+    #[test]
+    fn kitchen_sink_matches_tree() {
+        assert_matches_tree(
+            "leading [fg:red](one [bg:blue](two [deco:dim](three) two) one) trailing",
+        );
+    }
+
+    #[test]
+    fn matches_tree() {
+        for source in [
+            "",
+            "plain",
+            "[fg:red](a)[fg:blue](b)",
+            "[fg:red]([bg:blue](inner))",
+            "[fg:red]()",
+            "\\[escaped\\] [deco:bold,italic](\\(text\\)) \\\n done",
+            "before \x1b[31m [fg:red](\x1b[1mbold\x1b[22m) after",
+            "[fg:red]",
+            "a [fg:red]b [deco:bold](c) d",
+            "[bg:blue](x [fg:red]y [deco:bold]z) after",
+            "[fg:red]a [fg:blue]b",
+            "\\x41 [fg:red](caf\\u{e9} \\u{1F600})",
+            "[raw](literal [fg:red](text) \\q) after",
+            "[raw;fg:red](f(x) \\) [deco:bold]) [raw]()",
+        ] {
+            assert_matches_tree(source);
+        }
+    }
+
+    #[test]
+    fn raw_block() {
+        assert_eq!(
+            events("[raw](a [fg:red](b) \\( c)"),
+            Ok(vec![
+                Event::StyleOpen(style!()),
+                Event::Text("a [fg:red](b) "),
+                Event::Text("("),
+                Event::Text(" c"),
+                Event::StyleClose,
+            ])
+        );
+    }
+
+    #[test]
+    fn raw_block_without_content() {
+        let error = events("[raw] text").unwrap_err();
+        assert_eq!(
+            error.reason,
+            Reason::Expected(vec![SyntaxKind::ParenthesisOpen])
+        );
+    }
+
+    #[test]
+    fn unterminated_raw_block() {
+        let error = events("[raw](a (b)").unwrap_err();
+        assert_eq!(error.reason, Reason::UnterminatedContent);
+        assert_eq!(error.span, Some(span!(5..6)));
+    }
+
+    #[test]
+    fn sticky_style_closes_with_enclosing_content() {
+        assert_eq!(
+            events("[bg:blue](a [fg:red]b) c"),
+            Ok(vec![
+                Event::StyleOpen(style!(bg: Blue;)),
+                Event::Text("a "),
+                Event::StyleOpen(style!(fg: Red;)),
+                Event::Text("b"),
+                Event::StyleClose,
+                Event::StyleClose,
+                Event::Text(" c"),
+            ])
+        );
+    }
+
+    #[test]
+    fn sticky_style_span() {
+        let mut events = Events::new("x [fg:red] y");
+        assert_eq!(events.nth(1), Some(Ok(Event::StyleOpen(style!(fg: Red;)))));
+        assert_eq!(events.style_span(), Some(span!(2..10)));
+    }
+
+    #[test]
+    fn unclosed_content() {
+        assert_eq!(
+            events("[fg:red](test"),
+            Err(Error {
+                span: Some(span!(8..9)),
+                at: SyntaxKind::Eof,
+                reason: Reason::UnterminatedContent,
+            })
+        );
+        assert_eq!(
+            events("[fg:red](a [bg:blue](b) c"),
+            Err(Error {
+                span: Some(span!(8..9)),
+                at: SyntaxKind::Eof,
+                reason: Reason::UnterminatedContent,
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_color() {
+        assert_eq!(
+            events("[fg:pink](x)"),
+            Err(Error {
+                span: Some(span!(4..8)),
+                at: SyntaxKind::Text,
+                reason: Reason::Expected(vec![SyntaxKind::Color]),
+            })
+        );
+    }
+
+    #[test]
+    fn unescaped_control_character() {
+        assert_eq!(
+            events("before ) after"),
+            Err(Error {
+                span: Some(span!(7..8)),
+                at: SyntaxKind::ParenthesisClose,
+                reason: Reason::UnescapedControlCharacter(')'),
+            })
+        );
+    }
+
+    #[test]
+    fn unescaped_control_character_in_token() {
+        assert_eq!(
+            events("[fg:red](before ] after)"),
+            Err(Error {
+                span: Some(span!(16..17)),
+                at: SyntaxKind::SquareBracketClose,
+                reason: Reason::Expected(vec![SyntaxKind::ParenthesisClose]),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_escape_character() {
+        assert_eq!(
+            events("\\a"),
+            Err(Error {
+                span: Some(span!(0..2)),
+                at: SyntaxKind::EscapeCharacter,
+                reason: Reason::UnknownEscapeSequence('a'),
+            })
+        );
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let mut events = Events::new("ok ) more");
+        assert_eq!(events.next(), Some(Ok(Event::Text("ok "))));
+        assert!(matches!(events.next(), Some(Err(_))));
+        assert_eq!(events.next(), None);
+    }
+
+    #[test]
+    fn custom_delimiters() {
+        let events: Result<Vec<_>, _> =
+            Events::with_delimiters("[a] {fg:red}<b\\>>", Delimiters::BRACES_ANGLE_BRACKETS)
+                .collect();
+        assert_eq!(
+            events,
+            Ok(vec![
+                Event::Text("[a] "),
+                Event::StyleOpen(style!(fg: Red;)),
+                Event::Text("b"),
+                Event::Text(">"),
+                Event::StyleClose,
+            ])
+        );
+    }
+
+    #[test]
+    fn nesting_too_deep() {
+        let source = format!("{}x{}", "[fg:red](".repeat(3), ")".repeat(3));
+        assert!(events(&source).is_ok());
+
+        let result: Result<Vec<_>, _> = Events::new(&source).with_max_depth(2).collect();
+        assert_eq!(
+            result,
+            Err(Error {
+                span: Some(span!(18..19)),
+                at: SyntaxKind::SquareBracketOpen,
+                reason: Reason::NestingTooDeep(2),
+            })
+        );
+    }
+
+    #[test]
+    fn sticky_styles_are_not_nested() {
+        let source = "[fg:red]a [fg:blue]b ".repeat(100);
+        let result: Result<Vec<_>, _> = Events::new(&source).with_max_depth(1).collect();
+        assert_eq!(result.map(|events| events.len()), Ok(600));
+
+        let result: Result<Vec<_>, _> = Events::new("[fg:red]a [bg:red](b [deco:bold](c))")
+            .with_max_depth(1)
+            .collect();
+        assert_eq!(
+            result,
+            Err(Error {
+                span: Some(span!(21..22)),
+                at: SyntaxKind::SquareBracketOpen,
+                reason: Reason::NestingTooDeep(1),
+            })
+        );
+    }
+
+    #[test]
+    fn comments_are_dropped() {
+        assert_eq!(
+            events("a [#: one]b [fg:red]([#: two]c)"),
+            Ok(vec![
+                Event::Text("a "),
+                Event::Text("b "),
+                Event::StyleOpen(style!(fg: Red;)),
+                Event::Text("c"),
+                Event::StyleClose,
+            ])
+        );
+    }
+}
@@ -1,11 +1,168 @@
-use super::{content::content, style::style, Parser};
-use crate::ast::Token;
+use super::{content, style::specifiers, text::plain_text, Parser};
+use crate::{
+    ast::{Decoration, Style, Token, Tokens},
+    error::{Error, Reason},
+    lexer::SyntaxKind,
+};
+use text_size::{TextRange, TextSize};
+
+/// Styled markup whose content is still being parsed
+struct Open<'source> {
+    style: Style,
+    start: TextSize,
+    /// The parenthesis that opened the content, if there is any
+    parenthesis: Option<TextRange>,
+    /// The kind and span of the marker that opened Markdown-style emphasis, if there is any
+    emphasis: Option<(SyntaxKind, TextRange)>,
+    content: Tokens<'source>,
+}
+
+impl Open<'_> {
+    /// Whether the style applies to the rest of the enclosing content, rather than being closed by
+    /// its own delimiter
+    fn is_sticky(&self) -> bool {
+        self.parenthesis.is_none() && self.emphasis.is_none()
+    }
+}
 
 /// Parse a segment of text with styling
-pub(crate) fn markup(p: &mut Parser) -> Option<Token> {
+///
+/// When the style specifier isn't followed by any content, the style is sticky and applies to the
+/// rest of the enclosing content instead. The content of markup with the `raw` specifier is taken
+/// as-is, so it must always be present.
+///
+/// Markdown-style emphasis is parsed the same way, with the emphasis marker acting as both the
+/// opening and closing delimiter. A marker closes the nearest enclosing emphasis if it is the same
+/// kind, and opens a new emphasis otherwise.
+///
+/// Markup nested within the content is kept on an explicit stack rather than parsed recursively,
+/// so the depth of the input is only limited by the parser's maximum depth, not the native stack.
+pub(crate) fn markup<'source>(p: &mut Parser<'source>) -> Option<Token<'source>> {
+    let mut stack = vec![open(p, 0)?];
+    // sticky styles are closed along with their enclosing content, so only the markup with its own
+    // content counts towards the depth
+    let mut depth = usize::from(!stack[0].is_sticky());
+
+    loop {
+        let current = stack.last_mut().expect("markup is still open");
+        plain_text(p, &mut current.content);
+
+        if p.at(SyntaxKind::SquareBracketOpen) || (p.at_emphasis() && !closes_emphasis(p, &stack)) {
+            let nested = open(p, depth)?;
+            depth += usize::from(!nested.is_sticky());
+            stack.push(nested);
+            continue;
+        }
+
+        let markup = stack.pop().expect("markup is still open");
+        depth -= usize::from(!markup.is_sticky());
+        let token = close(p, markup)?;
+        match stack.last_mut() {
+            Some(parent) => parent.content.push(token),
+            None => return Some(token),
+        }
+    }
+}
+
+/// Start a piece of markup nested within the content of `depth` others by parsing its style
+/// specifiers
+///
+/// Sticky styles don't have any content of their own, so they can be opened at any depth.
+fn open<'source>(p: &mut Parser<'source>, depth: usize) -> Option<Open<'source>> {
+    let start = p.offset();
+    let opening = p.peek_lexeme().map(|lexeme| (lexeme.span, lexeme.kind))?;
+
+    if p.at_emphasis() {
+        if depth >= p.max_depth {
+            return nesting_too_deep(p, opening);
+        }
+
+        let marker = p.bump();
+        let decoration = match marker.kind {
+            SyntaxKind::DoubleAsterisk => Decoration::Bold,
+            _ => Decoration::Italic,
+        };
+
+        return Some(Open {
+            style: Style::deco(decoration),
+            start,
+            parenthesis: None,
+            emphasis: Some((marker.kind, marker.span)),
+            content: Tokens::default(),
+        });
+    }
+
+    let specifiers = specifiers(p)?;
+    let parenthesis = if p.at(SyntaxKind::ParenthesisOpen) || specifiers.raw {
+        if depth >= p.max_depth {
+            return nesting_too_deep(p, opening);
+        }
+        Some(content::open(p)?)
+    } else {
+        None
+    };
+
+    let mut content = Tokens::default();
+    if specifiers.raw {
+        // the raw text is the entire content, so the markup is closed right after
+        let mut depth = 0;
+        while let Some((text, span)) = content::raw(p, &mut depth) {
+            content.push_str(text, span);
+        }
+    }
+
+    Some(Open {
+        style: specifiers.style,
+        start,
+        parenthesis,
+        emphasis: None,
+        content,
+    })
+}
+
+/// Report that the markup opened by the lexeme is nested too deeply
+fn nesting_too_deep<T>(p: &mut Parser, (span, at): (TextRange, SyntaxKind)) -> Option<T> {
+    p.errors.push(Error {
+        span: Some(span),
+        at,
+        reason: Reason::NestingTooDeep(p.max_depth),
+    });
+
+    // the rest of the markup can't be parsed without going deeper, so give up entirely
+    while p.peek().is_some() {
+        p.bump();
+    }
+    None
+}
+
+/// Whether the emphasis marker the parser is at closes the nearest enclosing emphasis
+fn closes_emphasis(p: &mut Parser, stack: &[Open]) -> bool {
+    let enclosing = stack
+        .iter()
+        .rev()
+        .find(|markup| !markup.is_sticky())
+        .and_then(|markup| markup.emphasis);
+
+    enclosing.is_some_and(|(kind, _)| p.at(kind))
+}
+
+/// Finish a piece of markup once the end of its content is reached
+fn close<'source>(p: &mut Parser<'source>, markup: Open<'source>) -> Option<Token<'source>> {
+    if let Some(parenthesis) = markup.parenthesis {
+        content::close(p, parenthesis)?;
+    }
+    if let Some((kind, marker)) = markup.emphasis {
+        if !p.at(kind) {
+            p.error_at(marker, Reason::UnterminatedEmphasis);
+            return None;
+        }
+        p.bump();
+    }
+
     Some(Token::Styled {
-        style: style(p)?,
-        content: content(p)?.into(),
+        style: markup.style,
+        content: markup.content.into(),
+        span: TextRange::new(markup.start, p.offset()),
     })
 }
 
@@ -13,6 +170,7 @@ pub(crate) fn markup(p: &mut Parser) -> Option<Token> {
 mod tests {
     use super::{markup, Parser};
     use crate::{
+        ast::Token,
         error::{Error, Reason},
         lexer::SyntaxKind,
     };
@@ -90,7 +248,14 @@ mod tests {
     #[test]
     fn empty_style_specifiers() {
         let mut parser = Parser::new("[](content)");
-        assert_eq!(markup(&mut parser), None);
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(),
+                content: vec![Token::Content("content".into(), span!(3..10))],
+                span: span!(0..11),
+            })
+        );
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -99,7 +264,10 @@ mod tests {
                 reason: Reason::Expected(vec![
                     SyntaxKind::ForegroundSpecifier,
                     SyntaxKind::BackgroundSpecifier,
-                    SyntaxKind::DecorationSpecifier
+                    SyntaxKind::UnderlineColorSpecifier,
+                    SyntaxKind::DecorationSpecifier,
+                    SyntaxKind::ResetSpecifier,
+                    SyntaxKind::RawSpecifier
                 ])
             }]
         );
@@ -108,7 +276,14 @@ mod tests {
     #[test]
     fn missing_closing_square_bracket_on_style_specifiers() {
         let mut parser = Parser::new("[fg:red(content)");
-        assert_eq!(markup(&mut parser), None);
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(fg: Red;),
+                content: vec![Token::Content("content".into(), span!(8..15))],
+                span: span!(0..16),
+            })
+        );
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -120,17 +295,55 @@ mod tests {
     }
 
     #[test]
-    fn missing_open_parenthesis_for_content() {
+    fn sticky_style_ends_at_enclosing_content() {
         let mut parser = Parser::new("[fg:red]content)");
-        assert_eq!(markup(&mut parser), None);
         assert_eq!(
-            parser.errors,
-            vec![Error {
-                span: Some(span!(8..15)),
-                at: SyntaxKind::Text,
-                reason: Reason::Expected(vec![SyntaxKind::ParenthesisOpen])
-            }]
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(fg: Red;),
+                content: vec![Token::Content("content".into(), span!(8..15))],
+                span: span!(0..15),
+            })
         );
+        assert_eq!(parser.errors, vec![]);
+        assert_eq!(parser.peek(), Some(SyntaxKind::ParenthesisClose));
+    }
+
+    #[test]
+    fn sticky_style_ends_at_end_of_input() {
+        let mut parser = Parser::new("[deco:bold]some [fg:red](nested) text");
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(deco: Bold;),
+                content: vec![
+                    Token::Content("some ".into(), span!(11..16)),
+                    Token::Styled {
+                        style: style!(fg: Red;),
+                        content: vec![Token::Content("nested".into(), span!(25..31))],
+                        span: span!(16..32),
+                    },
+                    Token::Content(" text".into(), span!(32..37)),
+                ],
+                span: span!(0..37),
+            })
+        );
+        assert_eq!(parser.errors, vec![]);
+        assert_eq!(parser.peek(), None);
+    }
+
+    #[test]
+    fn sticky_style_without_text() {
+        let mut parser = Parser::new("[fg:red]");
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(fg: Red;),
+                content: vec![],
+                span: span!(0..8),
+            })
+        );
+        assert_eq!(parser.errors, vec![]);
     }
 
     #[test]
@@ -140,23 +353,209 @@ mod tests {
         assert_eq!(
             parser.errors,
             vec![Error {
-                span: None,
+                span: Some(span!(8..9)),
                 at: SyntaxKind::Eof,
-                reason: Reason::Expected(vec![SyntaxKind::ParenthesisClose])
+                reason: Reason::UnterminatedContent,
             }]
         );
     }
 
     #[test]
     fn content_does_not_immediately_follow_style_specifiers() {
+        // the style is sticky, so the content is an unescaped parenthesis in its text
         let mut parser = Parser::new("[fg:red] (content)");
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(fg: Red;),
+                content: vec![Token::Content(" ".into(), span!(8..9))],
+                span: span!(0..9),
+            })
+        );
+        assert_eq!(parser.peek(), Some(SyntaxKind::ParenthesisOpen));
+    }
+
+    #[test]
+    fn raw() {
+        let mut parser = Parser::new("[raw](literal [fg:red] \\n stays)");
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(),
+                content: vec![Token::Content(
+                    "literal [fg:red] \\n stays".into(),
+                    span!(6..31)
+                )],
+                span: span!(0..32),
+            })
+        );
+        assert_eq!(parser.errors, vec![]);
+    }
+
+    #[test]
+    fn raw_with_style() {
+        let mut parser = Parser::new("[fg:red;raw](a [b] (c [d](e)) \\) f)");
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(fg: Red;),
+                content: vec![Token::Content("a [b] (c [d](e)) ) f".into(), span!(13..34))],
+                span: span!(0..35),
+            })
+        );
+        assert_eq!(parser.errors, vec![]);
+    }
+
+    #[test]
+    fn raw_within_markup() {
+        let mut parser = Parser::new("[fg:red](a [raw]([deco:bold](b)) c)");
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(fg: Red;),
+                content: vec![
+                    Token::Content("a ".into(), span!(9..11)),
+                    Token::Styled {
+                        style: style!(),
+                        content: vec![Token::Content("[deco:bold](b)".into(), span!(17..31))],
+                        span: span!(11..32),
+                    },
+                    Token::Content(" c".into(), span!(32..34)),
+                ],
+                span: span!(0..35),
+            })
+        );
+    }
+
+    #[test]
+    fn raw_without_content() {
+        let mut parser = Parser::new("[raw] text");
         assert_eq!(markup(&mut parser), None);
         assert_eq!(
             parser.errors,
             vec![Error {
-                span: Some(span!(8..9)),
+                span: Some(span!(5..6)),
                 at: SyntaxKind::Whitespace,
-                reason: Reason::Expected(vec![SyntaxKind::ParenthesisOpen])
+                reason: Reason::Expected(vec![SyntaxKind::ParenthesisOpen]),
+            }]
+        );
+    }
+
+    #[test]
+    fn unterminated_raw() {
+        let mut parser = Parser::new("[raw](a (b) [fg:red](c)");
+        assert_eq!(markup(&mut parser), None);
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(5..6)),
+                at: SyntaxKind::Eof,
+                reason: Reason::UnterminatedContent,
+            }]
+        );
+    }
+
+    #[test]
+    fn emphasis() {
+        let mut parser = Parser::new("*italic* rest").with_markdown_emphasis(true);
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(deco: Italic;),
+                content: vec![Token::Content("italic".into(), span!(1..7))],
+                span: span!(0..8),
+            })
+        );
+        assert_eq!(parser.peek(), Some(SyntaxKind::Whitespace));
+    }
+
+    #[test]
+    fn nested_emphasis() {
+        let mut parser = Parser::new("**bold *and italic* **").with_markdown_emphasis(true);
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(deco: Bold;),
+                content: vec![
+                    Token::Content("bold ".into(), span!(2..7)),
+                    Token::Styled {
+                        style: style!(deco: Italic;),
+                        content: vec![Token::Content("and italic".into(), span!(8..18))],
+                        span: span!(7..19),
+                    },
+                    Token::Content(" ".into(), span!(19..20)),
+                ],
+                span: span!(0..22),
+            })
+        );
+        assert_eq!(parser.errors, vec![]);
+    }
+
+    #[test]
+    fn emphasis_within_markup() {
+        let mut parser = Parser::new("[fg:red](a *b* c)").with_markdown_emphasis(true);
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(fg: Red;),
+                content: vec![
+                    Token::Content("a ".into(), span!(9..11)),
+                    Token::Styled {
+                        style: style!(deco: Italic;),
+                        content: vec![Token::Content("b".into(), span!(12..13))],
+                        span: span!(11..14),
+                    },
+                    Token::Content(" c".into(), span!(14..16)),
+                ],
+                span: span!(0..17),
+            })
+        );
+    }
+
+    #[test]
+    fn emphasis_closes_enclosing_sticky_styles() {
+        let mut parser = Parser::new("*a [fg:red] b*").with_markdown_emphasis(true);
+        assert_eq!(
+            markup(&mut parser),
+            Some(Token::Styled {
+                style: style!(deco: Italic;),
+                content: vec![
+                    Token::Content("a ".into(), span!(1..3)),
+                    Token::Styled {
+                        style: style!(fg: Red;),
+                        content: vec![Token::Content(" b".into(), span!(11..13))],
+                        span: span!(3..13),
+                    },
+                ],
+                span: span!(0..14),
+            })
+        );
+    }
+
+    #[test]
+    fn unterminated_emphasis() {
+        let mut parser = Parser::new("**bold").with_markdown_emphasis(true);
+        assert_eq!(markup(&mut parser), None);
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(0..2)),
+                at: SyntaxKind::Eof,
+                reason: Reason::UnterminatedEmphasis,
+            }]
+        );
+    }
+
+    #[test]
+    fn emphasis_must_be_closed_within_content() {
+        let mut parser = Parser::new("[fg:red](*a)*").with_markdown_emphasis(true);
+        assert_eq!(markup(&mut parser), None);
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(9..10)),
+                at: SyntaxKind::ParenthesisClose,
+                reason: Reason::UnterminatedEmphasis,
             }]
         );
     }
@@ -1,16 +1,34 @@
 use super::{Parser, Reason};
 use crate::{
     ast::{Color, Decoration, Style},
+    error::Error,
     lexer::SyntaxKind,
 };
 use indexmap::IndexSet;
 use std::str::FromStr;
 
+/// Everything set by the style specifiers of a piece of markup
+pub(crate) struct Specifiers {
+    pub style: Style,
+    /// Whether the content is raw text, where no markup or escapes are interpreted
+    pub raw: bool,
+}
+
 /// Extract style information from the token stream
 pub(crate) fn style(p: &mut Parser) -> Option<Style> {
-    p.expect(SyntaxKind::SquareBracketOpen)?;
+    specifiers(p).map(|specifiers| specifiers.style)
+}
+
+/// Extract style information from the token stream, along with any flags that change how the
+/// content is parsed
+pub(crate) fn specifiers(p: &mut Parser) -> Option<Specifiers> {
+    let open = p.expect(SyntaxKind::SquareBracketOpen)?;
 
-    let mut style = Style::default();
+    let mut specifiers = Specifiers {
+        style: Style::default(),
+        raw: false,
+    };
+    let style = &mut specifiers.style;
     let mut first_specifier = true;
 
     loop {
@@ -27,33 +45,139 @@ pub(crate) fn style(p: &mut Parser) -> Option<Style> {
 
         match p.peek() {
             Some(SyntaxKind::ForegroundSpecifier) => {
-                let color = color_specifier(p, SyntaxKind::ForegroundSpecifier)?;
+                let Some(color) = color_specifier(p, SyntaxKind::ForegroundSpecifier) else {
+                    return recover(p, specifiers);
+                };
                 style.foreground = Some(color);
             }
             Some(SyntaxKind::BackgroundSpecifier) => {
-                let color = color_specifier(p, SyntaxKind::BackgroundSpecifier)?;
+                let Some(color) = color_specifier(p, SyntaxKind::BackgroundSpecifier) else {
+                    return recover(p, specifiers);
+                };
                 style.background = Some(color);
             }
+            Some(SyntaxKind::UnderlineColorSpecifier) => {
+                let Some(color) = color_specifier(p, SyntaxKind::UnderlineColorSpecifier) else {
+                    return recover(p, specifiers);
+                };
+                style.underline_color = Some(color);
+            }
             Some(SyntaxKind::DecorationSpecifier) => {
-                let decorations = decorations_specifier(p, SyntaxKind::DecorationSpecifier)?;
+                let Some(decorations) = decorations_specifier(p, SyntaxKind::DecorationSpecifier)
+                else {
+                    return recover(p, specifiers);
+                };
                 style.decoration = Some(decorations);
             }
+            Some(SyntaxKind::ResetSpecifier) => {
+                p.bump();
+                style.reset = true;
+            }
+            Some(SyntaxKind::RawSpecifier) => {
+                p.bump();
+                specifiers.raw = true;
+            }
+            None => {
+                p.error_at(open.span, Reason::UnterminatedSpecifier);
+                return None;
+            }
+            Some(_) if at_unknown_tag(p) => {
+                let tag = p.peek_lexeme().unwrap().text.to_string();
+                p.error(Reason::UnknownSpecifier(tag));
+                return recover(p, specifiers);
+            }
             _ => {
                 p.error(Reason::Expected(vec![
                     SyntaxKind::ForegroundSpecifier,
                     SyntaxKind::BackgroundSpecifier,
+                    SyntaxKind::UnderlineColorSpecifier,
                     SyntaxKind::DecorationSpecifier,
+                    SyntaxKind::ResetSpecifier,
+                    SyntaxKind::RawSpecifier,
                 ]));
-                return None;
+                return recover(p, specifiers);
             }
         }
 
         first_specifier = false;
     }
 
-    p.expect(SyntaxKind::SquareBracketClose)?;
+    if p.peek().is_none() {
+        p.error_at(open.span, Reason::UnterminatedSpecifier);
+        return None;
+    }
+    if p.expect(SyntaxKind::SquareBracketClose).is_none() {
+        return recover(p, specifiers);
+    }
+
+    Some(specifiers)
+}
+
+/// Parse a style from a style specifier, using the same syntax as styled markup (i.e.
+/// `[fg:red;deco:bold]`)
+///
+/// Whitespace is allowed around the specifier. Any errors point into the string, so they can be
+/// reported using an [`ErrorReport`](crate::ErrorReport) the same as errors in markup. The `raw`
+/// specifier is accepted but ignored, as it only changes how the content is parsed.
+impl FromStr for Style {
+    type Err = Vec<Error>;
+
+    fn from_str(specifier: &str) -> Result<Self, Self::Err> {
+        let mut p = Parser::new(specifier);
+        p.consume_whitespace();
+
+        let style = style(&mut p);
+        if style.is_some() {
+            p.consume_whitespace();
+            if p.peek().is_some() {
+                p.error(Reason::Expected(vec![SyntaxKind::Eof]));
+            }
+        }
 
-    Some(style)
+        match style {
+            Some(style) if p.errors.is_empty() => Ok(style),
+            _ => Err(p.errors),
+        }
+    }
+}
+
+/// Check if the parser is at a word followed by a colon, i.e. a specifier with an unknown tag
+fn at_unknown_tag(p: &mut Parser) -> bool {
+    let Some(lexeme) = p.peek_lexeme() else {
+        return false;
+    };
+    if !matches!(
+        lexeme.kind,
+        SyntaxKind::Text | SyntaxKind::Color | SyntaxKind::Decoration
+    ) {
+        return false;
+    }
+
+    let end = usize::from(lexeme.span.end());
+    p.source[end..]
+        .trim_start_matches([' ', '\r', '\n', '\t'])
+        .starts_with(':')
+}
+
+/// Skip the rest of an invalid style specifier so that parsing can continue after it
+///
+/// The error must already be reported. If the end of the specifier (or the start of the content)
+/// is found, the styles parsed before the error are returned so the content can still be parsed.
+/// Otherwise, the markup can't be recovered.
+fn recover(p: &mut Parser, specifiers: Specifiers) -> Option<Specifiers> {
+    loop {
+        match p.peek() {
+            Some(SyntaxKind::SquareBracketClose) => {
+                p.bump();
+                return Some(specifiers);
+            }
+            Some(SyntaxKind::ParenthesisOpen) => return Some(specifiers),
+            Some(SyntaxKind::ParenthesisClose) | None => return None,
+            Some(_) => {
+                p.bump();
+            }
+        }
+    }
 }
 
 /// Parse a specifier with a [`Color`] value
@@ -64,8 +188,28 @@ fn color_specifier(p: &mut Parser, tag: SyntaxKind) -> Option<Color> {
     p.expect(SyntaxKind::Colon)?;
     p.consume_whitespace();
 
+    color(p)
+}
+
+/// Parse the name of a [`Color`]
+///
+/// The lexer only produces color lexemes for valid names, but an error is reported rather than
+/// panicking in case the two ever disagree.
+fn color(p: &mut Parser) -> Option<Color> {
+    // `reset` is lexed as the reset specifier, but reverts the color the same as `default`
+    if p.at(SyntaxKind::ResetSpecifier) {
+        p.bump();
+        return Some(Color::Default);
+    }
+
     let token = p.expect(SyntaxKind::Color)?;
-    Some(Color::from_str(token.text).expect("invalid color"))
+    match Color::from_str(token.text) {
+        Ok(color) => Some(color),
+        Err(_) => {
+            p.error_at(token.span, Reason::Expected(vec![SyntaxKind::Color]));
+            None
+        }
+    }
 }
 
 /// Parse a specifier with a [`Decoration`]s value
@@ -91,20 +235,37 @@ fn decorations_specifier(p: &mut Parser, tag: SyntaxKind) -> Option<IndexSet<Dec
             }
         }
 
-        let token = p.expect(SyntaxKind::Decoration)?;
-        decorations.insert(Decoration::from_str(token.text).expect("invalid decoration"));
-
+        decorations.insert(decoration(p)?);
         first_decoration = false;
     }
 
     Some(decorations)
 }
 
+/// Parse the name of a [`Decoration`]
+///
+/// Like [`color`], an error is reported if the lexer accepted a name the decoration table doesn't.
+fn decoration(p: &mut Parser) -> Option<Decoration> {
+    let token = p.expect(SyntaxKind::Decoration)?;
+    match Decoration::from_str(token.text) {
+        Ok(decoration) => Some(decoration),
+        Err(_) => {
+            p.error_at(token.span, Reason::Expected(vec![SyntaxKind::Decoration]));
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{color_specifier, decorations_specifier, style, Parser};
+    use super::{
+        color, color_specifier, decoration, decorations_specifier, specifiers, style, Parser,
+    };
+    use crate::ast::Style;
+    use crate::parser::Events;
     use crate::{
         ast::{Color, Decoration},
+        color::{colorize, Options},
         error::{Error, Reason},
         lexer::SyntaxKind,
     };
@@ -137,6 +298,59 @@ mod tests {
         assert_eq!(result, Some(Color::Blue));
     }
 
+    #[test]
+    fn foreground_color_specifier_reset() {
+        let mut parser = Parser::new("fg:Reset");
+        let result = color_specifier(&mut parser, SyntaxKind::ForegroundSpecifier);
+        assert_eq!(result, Some(Color::Default));
+        assert!(parser.errors.is_empty());
+    }
+
+    #[test]
+    fn reset_color_is_distinct_from_reset_specifier() {
+        let style = "[reset;fg:reset;bg:reset]".parse::<Style>().unwrap();
+        assert_eq!(
+            style,
+            Style {
+                reset: true,
+                ..Style::fg(Color::Default).background(Color::Default)
+            }
+        );
+    }
+
+    #[test]
+    fn color_rejected_by_table() {
+        // simulate the lexer accepting a name that the color table doesn't
+        let mut parser = Parser::new("pink");
+        parser.lexer.peek_mut().unwrap().kind = SyntaxKind::Color;
+
+        assert_eq!(color(&mut parser), None);
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(0..4)),
+                at: SyntaxKind::Eof,
+                reason: Reason::Expected(vec![SyntaxKind::Color]),
+            }]
+        );
+    }
+
+    #[test]
+    fn decoration_rejected_by_table() {
+        let mut parser = Parser::new("sparkle");
+        parser.lexer.peek_mut().unwrap().kind = SyntaxKind::Decoration;
+
+        assert_eq!(decoration(&mut parser), None);
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(0..7)),
+                at: SyntaxKind::Eof,
+                reason: Reason::Expected(vec![SyntaxKind::Decoration]),
+            }]
+        );
+    }
+
     #[test]
     fn background_color_specifier() {
         let mut parser = Parser::new("bg:red");
@@ -165,6 +379,20 @@ mod tests {
         assert_eq!(result, Some(Color::Blue));
     }
 
+    #[test]
+    fn underline_color_specifier() {
+        let mut parser = Parser::new("ul:red");
+        let result = color_specifier(&mut parser, SyntaxKind::UnderlineColorSpecifier);
+        assert_eq!(result, Some(Color::Red));
+    }
+
+    #[test]
+    fn underline_color_specifier_long_tag() {
+        let mut parser = Parser::new("Underline-Color:bright-blue");
+        let result = color_specifier(&mut parser, SyntaxKind::UnderlineColorSpecifier);
+        assert_eq!(result, Some(Color::BrightBlue));
+    }
+
     #[test]
     fn color_specifier_not_starting_with_tag_returns_none() {
         let mut parser = Parser::new("deco:blue");
@@ -403,6 +631,116 @@ mod tests {
         assert!(parser.errors.is_empty());
     }
 
+    #[test]
+    fn from_str() {
+        for (specifier, expected) in [
+            ("[fg:red]", style!(fg: Red;)),
+            ("[bg:red]", style!(bg: Red;)),
+            ("[deco:bold]", style!(deco: Bold;)),
+            ("[deco:bold,italic]", style!(deco: Bold, Italic;)),
+            ("[reset]", style!(reset;)),
+            ("[RESET;fg:red]", style!(reset; fg: Red;)),
+            ("[fg:red;bg:blue]", style!(fg: Red; bg: Blue;)),
+            ("[ul:red;deco:underline]", style!(ul: Red; deco: Underline;)),
+            ("[deco:bold;fg:red]", style!(fg: Red; deco: Bold;)),
+            (
+                "[fg:red;deco:bold,italic;bg:blue]",
+                style!(fg: Red; bg: Blue; deco: Bold, Italic;),
+            ),
+            ("[fg:red;fg:blue]", style!(fg: Blue;)),
+        ] {
+            assert_eq!(specifier.parse::<Style>(), Ok(expected), "{specifier}");
+        }
+    }
+
+    #[test]
+    fn from_str_whitespace() {
+        for specifier in [
+            "[ fg : red ; deco : bold , italic ]",
+            "[\n\tfg\t:\r\n red;\n\tdeco:\n bold,italic\n]",
+            "[fg:red;\\\n  deco:\\ bold,italic]",
+            "  [fg:red;deco:bold,italic]\n",
+        ] {
+            assert_eq!(
+                specifier.parse::<Style>(),
+                Ok(style!(fg: Red; deco: Bold, Italic;)),
+                "{specifier:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn from_str_round_trips_through_display() {
+        let style = style!(reset; fg: BrightRed; bg: Default; deco: Dim, StrikeThrough;);
+        assert_eq!(style.to_string().parse::<Style>(), Ok(style));
+    }
+
+    #[test]
+    fn from_str_invalid_value() {
+        assert_eq!(
+            "[fg:bold]".parse::<Style>(),
+            Err(vec![Error {
+                span: Some(span!(4..8)),
+                at: SyntaxKind::Decoration,
+                reason: Reason::Expected(vec![SyntaxKind::Color]),
+            }])
+        );
+    }
+
+    #[test]
+    fn from_str_unknown_specifier() {
+        assert_eq!(
+            "[color:red]".parse::<Style>(),
+            Err(vec![Error {
+                span: Some(span!(1..6)),
+                at: SyntaxKind::Text,
+                reason: Reason::UnknownSpecifier(String::from("color")),
+            }])
+        );
+    }
+
+    #[test]
+    fn from_str_unclosed() {
+        assert_eq!(
+            "[fg:red".parse::<Style>(),
+            Err(vec![Error {
+                span: Some(span!(0..1)),
+                at: SyntaxKind::Eof,
+                reason: Reason::UnterminatedSpecifier,
+            }])
+        );
+    }
+
+    #[test]
+    fn from_str_without_brackets() {
+        assert_eq!(
+            "fg:red".parse::<Style>(),
+            Err(vec![Error {
+                span: Some(span!(0..2)),
+                at: SyntaxKind::ForegroundSpecifier,
+                reason: Reason::Expected(vec![SyntaxKind::SquareBracketOpen]),
+            }])
+        );
+    }
+
+    #[test]
+    fn from_str_trailing_content() {
+        assert_eq!(
+            "[fg:red](text)".parse::<Style>(),
+            Err(vec![Error {
+                span: Some(span!(8..9)),
+                at: SyntaxKind::ParenthesisOpen,
+                reason: Reason::Expected(vec![SyntaxKind::Eof]),
+            }])
+        );
+    }
+
+    #[test]
+    fn from_str_empty() {
+        assert!("".parse::<Style>().is_err());
+        assert!("[]".parse::<Style>().is_err());
+    }
+
     #[test]
     fn style_foreground() {
         let mut parser = Parser::new("[fg:red]");
@@ -431,6 +769,31 @@ mod tests {
         assert_eq!(result, Some(style!(deco: Bold, Italic;)));
     }
 
+    #[test]
+    fn style_reset() {
+        let mut parser = Parser::new("[reset]");
+        let result = style(&mut parser);
+        assert_eq!(result, Some(style!(reset;)));
+    }
+
+    #[test]
+    fn style_raw() {
+        let mut parser = Parser::new("[raw;fg:red]");
+        let result = specifiers(&mut parser).unwrap();
+        assert_eq!(result.style, style!(fg: Red;));
+        assert!(result.raw);
+
+        let mut parser = Parser::new("[fg:red]");
+        assert!(!specifiers(&mut parser).unwrap().raw);
+    }
+
+    #[test]
+    fn style_reset_with_foreground() {
+        let mut parser = Parser::new("[RESET;fg:red]");
+        let result = style(&mut parser);
+        assert_eq!(result, Some(style!(reset; fg: Red;)));
+    }
+
     #[test]
     fn style_foreground_and_background() {
         let mut parser = Parser::new("[fg:red;bg:blue]");
@@ -438,6 +801,13 @@ mod tests {
         assert_eq!(result, Some(style!(fg: Red; bg: Blue;)));
     }
 
+    #[test]
+    fn style_underline_color_and_decoration() {
+        let mut parser = Parser::new("[deco:underline;underline-color:red]");
+        let result = style(&mut parser);
+        assert_eq!(result, Some(style!(ul: Red; deco: Underline;)));
+    }
+
     #[test]
     fn style_foreground_and_single_decoration() {
         let mut parser = Parser::new("[fg:red;deco:bold]");
@@ -609,7 +979,49 @@ mod tests {
     #[test]
     fn style_invalid_specifier_tag() {
         let mut parser = Parser::new("[foreground:black]");
-        assert_eq!(style(&mut parser), None);
+        assert_eq!(style(&mut parser), Some(style!()));
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(1..11)),
+                at: SyntaxKind::Text,
+                reason: Reason::UnknownSpecifier(String::from("foreground")),
+            }]
+        );
+    }
+
+    #[test]
+    fn style_invalid_specifier_tag_with_whitespace_before_colon() {
+        let mut parser = Parser::new("[fg:red; colour :blue]");
+        assert_eq!(style(&mut parser), Some(style!(fg: Red;)));
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(9..15)),
+                at: SyntaxKind::Text,
+                reason: Reason::UnknownSpecifier(String::from("colour")),
+            }]
+        );
+    }
+
+    #[test]
+    fn style_invalid_specifier_tag_matching_a_value() {
+        let mut parser = Parser::new("[red:bold]");
+        assert_eq!(style(&mut parser), Some(style!()));
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(1..4)),
+                at: SyntaxKind::Color,
+                reason: Reason::UnknownSpecifier(String::from("red")),
+            }]
+        );
+    }
+
+    #[test]
+    fn style_invalid_specifier_without_value() {
+        let mut parser = Parser::new("[foreground]");
+        assert_eq!(style(&mut parser), Some(style!()));
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -618,7 +1030,10 @@ mod tests {
                 reason: Reason::Expected(vec![
                     SyntaxKind::ForegroundSpecifier,
                     SyntaxKind::BackgroundSpecifier,
-                    SyntaxKind::DecorationSpecifier
+                    SyntaxKind::UnderlineColorSpecifier,
+                    SyntaxKind::DecorationSpecifier,
+                    SyntaxKind::ResetSpecifier,
+                    SyntaxKind::RawSpecifier
                 ])
             }]
         );
@@ -627,7 +1042,7 @@ mod tests {
     #[test]
     fn style_invalid_foreground_specifier_value() {
         let mut parser = Parser::new("[fg:invalid]");
-        assert_eq!(style(&mut parser), None);
+        assert_eq!(style(&mut parser), Some(style!()));
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -641,7 +1056,7 @@ mod tests {
     #[test]
     fn style_invalid_background_specifier_value() {
         let mut parser = Parser::new("[bg:invalid]");
-        assert_eq!(style(&mut parser), None);
+        assert_eq!(style(&mut parser), Some(style!()));
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -655,7 +1070,7 @@ mod tests {
     #[test]
     fn style_invalid_decoration_specifier_value() {
         let mut parser = Parser::new("[deco:invalid]");
-        assert_eq!(style(&mut parser), None);
+        assert_eq!(style(&mut parser), Some(style!()));
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -669,7 +1084,7 @@ mod tests {
     #[test]
     fn style_invalid_key_value_pair_format() {
         let mut parser = Parser::new("[fg]");
-        assert_eq!(style(&mut parser), None);
+        assert_eq!(style(&mut parser), Some(style!()));
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -683,7 +1098,7 @@ mod tests {
     #[test]
     fn style_invalid_foreground_specifier_value_when_surrounded_by_valid_specifiers() {
         let mut parser = Parser::new("[bg:red;fg:invalid;deco:bold]");
-        assert_eq!(style(&mut parser), None);
+        assert_eq!(style(&mut parser), Some(style!(bg: Red;)));
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -697,7 +1112,7 @@ mod tests {
     #[test]
     fn style_invalid_background_specifier_value_when_surrounded_by_valid_specifiers() {
         let mut parser = Parser::new("[fg:red;bg:invalid;deco:bold]");
-        assert_eq!(style(&mut parser), None);
+        assert_eq!(style(&mut parser), Some(style!(fg: Red;)));
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -711,7 +1126,7 @@ mod tests {
     #[test]
     fn style_invalid_decoration_specifier_value_when_surrounded_by_valid_specifiers() {
         let mut parser = Parser::new("[fg:red;deco:invalid;bg:blue]");
-        assert_eq!(style(&mut parser), None);
+        assert_eq!(style(&mut parser), Some(style!(fg: Red;)));
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -725,7 +1140,7 @@ mod tests {
     #[test]
     fn style_invalid_key_value_pair_format_when_surrounded_by_valid_specifiers() {
         let mut parser = Parser::new("[bg:white;fg;deco:italic,bold]");
-        assert_eq!(style(&mut parser), None);
+        assert_eq!(style(&mut parser), Some(style!(bg: White;)));
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -743,17 +1158,33 @@ mod tests {
         assert_eq!(
             parser.errors,
             vec![Error {
-                span: None,
+                span: Some(span!(0..1)),
                 at: SyntaxKind::Eof,
-                reason: Reason::Expected(vec![SyntaxKind::SquareBracketClose]),
+                reason: Reason::UnterminatedSpecifier,
             }]
         )
     }
 
+    #[test]
+    fn style_recovers_at_start_of_content() {
+        let mut parser = Parser::new("[fg:red;fg:pink(content)");
+        assert_eq!(style(&mut parser), Some(style!(fg: Red;)));
+        assert_eq!(parser.peek(), Some(SyntaxKind::ParenthesisOpen));
+        assert_eq!(parser.errors.len(), 1);
+    }
+
+    #[test]
+    fn style_cannot_recover_at_end_of_content() {
+        let mut parser = Parser::new("[fg:pink)");
+        assert_eq!(style(&mut parser), None);
+        assert_eq!(parser.peek(), Some(SyntaxKind::ParenthesisClose));
+        assert_eq!(parser.errors.len(), 1);
+    }
+
     #[test]
     fn style_empty_specifier_list() {
         let mut parser = Parser::new("[]");
-        assert_eq!(style(&mut parser), None);
+        assert_eq!(style(&mut parser), Some(style!()));
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -762,7 +1193,10 @@ mod tests {
                 reason: Reason::Expected(vec![
                     SyntaxKind::ForegroundSpecifier,
                     SyntaxKind::BackgroundSpecifier,
-                    SyntaxKind::DecorationSpecifier
+                    SyntaxKind::UnderlineColorSpecifier,
+                    SyntaxKind::DecorationSpecifier,
+                    SyntaxKind::ResetSpecifier,
+                    SyntaxKind::RawSpecifier
                 ])
             }]
         )
@@ -837,4 +1271,52 @@ mod tests {
         assert_eq!(style(&mut parser), Some(style!(fg: Red; bg: Blue;)));
         assert!(parser.errors.is_empty());
     }
+
+    #[test]
+    fn style_whitespace_around_every_part() {
+        let mut parser = Parser::new("[ fg : red ; bg : blue ; deco : bold , italic ]");
+        assert_eq!(
+            style(&mut parser),
+            Some(style!(fg: Red; bg: Blue; deco: Bold, Italic;))
+        );
+        assert!(parser.errors.is_empty());
+    }
+
+    #[test]
+    fn style_newlines_and_tabs() {
+        let mut parser = Parser::new("[\n\tfg\t:\r\n red;\n\tdeco:\n bold\n]");
+        assert_eq!(style(&mut parser), Some(style!(fg: Red; deco: Bold;)));
+        assert!(parser.errors.is_empty());
+    }
+
+    #[test]
+    fn style_escaped_whitespace() {
+        let mut parser = Parser::new("[fg:red;\\\n  bg:\\ blue]");
+        assert_eq!(style(&mut parser), Some(style!(fg: Red; bg: Blue;)));
+        assert!(parser.errors.is_empty());
+    }
+
+    #[test]
+    fn style_escaped_form_feed_and_vertical_tab() {
+        let mut parser = Parser::new("[fg:red;\\\x0c\x0bbg:\\\x0bblue]");
+        assert_eq!(style(&mut parser), Some(style!(fg: Red; bg: Blue;)));
+        assert!(parser.errors.is_empty());
+    }
+
+    #[test]
+    fn style_whitespace_parses_the_same_as_without() {
+        for (spaced, compact) in [
+            ("[fg : red](x)", "[fg:red](x)"),
+            ("[ bg: blue ;deco :dim ](x)", "[bg:blue;deco:dim](x)"),
+            ("[fg:red]([ deco : bold ](x))", "[fg:red]([deco:bold](x))"),
+        ] {
+            // the spans of the tokens differ, so compare what they render to instead
+            let expected = colorize(compact, Options::default()).unwrap();
+            let result = colorize(spaced, Options::default());
+            assert_eq!(result, Ok(expected), "{spaced:?}");
+
+            let events = Events::new(spaced).collect::<Result<Vec<_>, _>>();
+            assert_eq!(events, Events::new(compact).collect(), "{spaced:?}");
+        }
+    }
 }
@@ -1,50 +1,170 @@
 use super::{markup::markup, Parser};
-use crate::{ast::Tokens, error::Reason, lexer::SyntaxKind};
+use crate::{
+    ast::{Token, Tokens},
+    error::Reason,
+    lexer::SyntaxKind,
+};
 
 /// Parse a piece of text that may content styled markup
-pub(crate) fn text(p: &mut Parser) -> Option<Tokens> {
+pub(crate) fn text<'source>(p: &mut Parser<'source>) -> Option<Tokens<'source>> {
     let mut tokens = Tokens::default();
 
     loop {
+        plain_text(p, &mut tokens);
+
+        if !p.at(SyntaxKind::SquareBracketOpen) && !p.at_emphasis() {
+            break;
+        }
+        tokens.push(markup(p)?);
+    }
+
+    Some(tokens)
+}
+
+/// Parse text up to the next styled markup or the end of the enclosing content
+///
+/// Emphasis markers also end the text when Markdown-style emphasis is recognized.
+pub(super) fn plain_text<'source>(p: &mut Parser<'source>, tokens: &mut Tokens<'source>) {
+    loop {
+        if p.at_emphasis() {
+            break;
+        }
+
         match p.peek() {
             Some(
                 SyntaxKind::ParenthesisClose
                 | SyntaxKind::ParenthesisOpen
+                | SyntaxKind::SquareBracketOpen
                 | SyntaxKind::SquareBracketClose,
             ) => break,
-            Some(SyntaxKind::SquareBracketOpen) => {
-                let styled = markup(p)?;
-                tokens.push(styled);
-            }
-            Some(SyntaxKind::EscapeWhitespace) => {
+            Some(SyntaxKind::EscapeWhitespace | SyntaxKind::Comment) => {
                 p.bump();
             }
+            Some(SyntaxKind::RawAnsi) => {
+                let lexeme = p.bump();
+                tokens.push(Token::Raw(lexeme.text, lexeme.span));
+            }
             Some(SyntaxKind::EscapeCharacter) => {
                 let lexeme = p.peek_lexeme().unwrap();
+                let (text, span) = (lexeme.text, lexeme.span);
 
-                assert_eq!(lexeme.text.len(), 2);
-                let character = lexeme.text.chars().nth(1).unwrap();
-                match character {
-                    '\\' | '(' | ')' | '[' | ']' => {
-                        tokens.push_char(character);
-                    }
-                    _ => {
-                        p.error(Reason::UnknownEscapeSequence(character));
-                    }
+                let character = text.chars().nth(1).unwrap();
+                if p.is_escapable(character) {
+                    tokens.push_str(&text[1..], span);
+                } else if let Some(whitespace) = decode_whitespace(character) {
+                    tokens.push_str(whitespace, span);
+                } else if character == 'x' {
+                    p.error(Reason::InvalidHexEscape);
+                } else if character == 'u' {
+                    p.error(Reason::InvalidUnicodeEscape);
+                } else {
+                    p.error(Reason::UnknownEscapeSequence(character));
                 }
 
                 p.bump();
             }
-            Some(SyntaxKind::Eof | SyntaxKind::Unknown) => unreachable!(),
+            Some(SyntaxKind::EscapeHex) => {
+                let lexeme = p.peek_lexeme().unwrap();
+                let (text, span) = (lexeme.text, lexeme.span);
+
+                match decode_hex(text) {
+                    Some(character) => tokens.push_str(character, span),
+                    None => p.error(Reason::InvalidHexEscape),
+                }
+
+                p.bump();
+            }
+            Some(SyntaxKind::EscapeUnicode) => {
+                let lexeme = p.peek_lexeme().unwrap();
+                let (text, span) = (lexeme.text, lexeme.span);
+
+                match decode_unicode(text) {
+                    Some(character) => tokens.push_char(character, span),
+                    None => p.error(Reason::InvalidUnicodeEscape),
+                }
+
+                p.bump();
+            }
+            Some(SyntaxKind::Unknown) if p.peek_lexeme().unwrap().text.starts_with('\\') => {
+                // a backslash at the end of the input doesn't escape anything
+                p.error(Reason::Expected(vec![SyntaxKind::EscapeCharacter]));
+                p.bump();
+            }
+            Some(SyntaxKind::Eof) => unreachable!(),
             Some(_) => {
-                let lexeme = p.bump();
-                tokens.push_str(lexeme.text);
+                // consecutive plain lexemes are contiguous, so they can be borrowed as one
+                let mut span = p.bump().span;
+                while !p.at_emphasis() && p.peek().is_some_and(is_plain_text) {
+                    span = span.cover(p.bump().span);
+                }
+
+                tokens.push_str(&p.source[span], span);
             }
             None => break,
         }
     }
+}
 
-    Some(tokens)
+/// Every ASCII character, so decoded hex escapes can be borrowed like the rest of the text
+static ASCII: [u8; 128] = {
+    let mut table = [0; 128];
+    let mut i = 0;
+    while i < table.len() {
+        table[i] = i as u8;
+        i += 1;
+    }
+    table
+};
+
+/// Decode a hex escape (i.e. `\x41`) into the character it represents
+///
+/// Only ASCII characters (`\x00` through `\x7f`) can be written as hex escapes, so `None` is
+/// returned for anything larger.
+pub(super) fn decode_hex(text: &str) -> Option<&'static str> {
+    let code = u8::from_str_radix(&text[2..], 16).ok()?;
+    let byte = ASCII.get(usize::from(code))?;
+    Some(std::str::from_utf8(std::slice::from_ref(byte)).expect("ASCII is valid UTF-8"))
+}
+
+/// Decode a whitespace escape (i.e. `\s` for a space, or `\t` for a tab) into the whitespace it
+/// represents
+///
+/// Unlike a backslash before literal whitespace, which removes it, these always produce the
+/// whitespace, so they can be used to keep trailing spaces.
+pub(super) fn decode_whitespace(character: char) -> Option<&'static str> {
+    match character {
+        's' => Some(" "),
+        't' => Some("\t"),
+        _ => None,
+    }
+}
+
+/// Decode a unicode escape (i.e. `\u{1F600}`) into the character it represents
+///
+/// Returns `None` if the code point is a surrogate or larger than `10FFFF`.
+pub(super) fn decode_unicode(text: &str) -> Option<char> {
+    let digits = &text[3..text.len() - 1];
+    let code = u32::from_str_radix(digits, 16).ok()?;
+    char::from_u32(code)
+}
+
+/// Whether the syntax item is emitted exactly as it appears in the source
+pub(super) fn is_plain_text(kind: SyntaxKind) -> bool {
+    !matches!(
+        kind,
+        SyntaxKind::SquareBracketOpen
+            | SyntaxKind::SquareBracketClose
+            | SyntaxKind::ParenthesisOpen
+            | SyntaxKind::ParenthesisClose
+            | SyntaxKind::EscapeWhitespace
+            | SyntaxKind::EscapeCharacter
+            | SyntaxKind::EscapeHex
+            | SyntaxKind::EscapeUnicode
+            | SyntaxKind::RawAnsi
+            | SyntaxKind::Comment
+            | SyntaxKind::Eof
+            | SyntaxKind::Unknown
+    )
 }
 
 #[cfg(test)]
@@ -55,6 +175,7 @@ mod tests {
         error::{Error, Reason},
         lexer::SyntaxKind,
     };
+    use std::borrow::Cow;
 
     #[test]
     fn empty() {
@@ -67,7 +188,10 @@ mod tests {
         let mut parser = Parser::new("before(after");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("before"))]))
+            Some(Tokens::from(vec![Token::Content(
+                "before".into(),
+                span!(0..6)
+            )]))
         );
         assert!(parser.at(SyntaxKind::ParenthesisOpen));
     }
@@ -77,7 +201,10 @@ mod tests {
         let mut parser = Parser::new("before)after");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("before"))]))
+            Some(Tokens::from(vec![Token::Content(
+                "before".into(),
+                span!(0..6)
+            )]))
         );
         assert!(parser.at(SyntaxKind::ParenthesisClose));
     }
@@ -87,7 +214,10 @@ mod tests {
         let mut parser = Parser::new("before]after");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("before"))]))
+            Some(Tokens::from(vec![Token::Content(
+                "before".into(),
+                span!(0..6)
+            )]))
         );
         assert!(parser.at(SyntaxKind::SquareBracketClose));
     }
@@ -97,9 +227,10 @@ mod tests {
         let mut parser = Parser::new("abcdefghijklmnopqrstuvwxyz");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "abcdefghijklmnopqrstuvwxyz"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "abcdefghijklmnopqrstuvwxyz".into(),
+                span!(0..26)
+            )]))
         )
     }
 
@@ -108,9 +239,10 @@ mod tests {
         let mut parser = Parser::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "ABCDEFGHIJKLMNOPQRSTUVWXYZ"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZ".into(),
+                span!(0..26)
+            )]))
         )
     }
 
@@ -119,9 +251,10 @@ mod tests {
         let mut parser = Parser::new("AaBbCcDdEeFfGgHhIiJjKkLlMmNnOoPpQqRrSsTtUuVvWwXxYuZz");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "AaBbCcDdEeFfGgHhIiJjKkLlMmNnOoPpQqRrSsTtUuVvWwXxYuZz"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "AaBbCcDdEeFfGgHhIiJjKkLlMmNnOoPpQqRrSsTtUuVvWwXxYuZz".into(),
+                span!(0..52)
+            )]))
         )
     }
 
@@ -130,9 +263,10 @@ mod tests {
         let mut parser = Parser::new("~!@#$%^&*-=_+~");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "~!@#$%^&*-=_+~"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "~!@#$%^&*-=_+~".into(),
+                span!(0..14)
+            )]))
         )
     }
 
@@ -141,7 +275,10 @@ mod tests {
         let mut parser = Parser::new(" \n\t\r");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(" \n\t\r"))]))
+            Some(Tokens::from(vec![Token::Content(
+                " \n\t\r".into(),
+                span!(0..4)
+            )]))
         )
     }
 
@@ -150,7 +287,10 @@ mod tests {
         let mut parser = Parser::new("black");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("black"))]))
+            Some(Tokens::from(vec![Token::Content(
+                "black".into(),
+                span!(0..5)
+            )]))
         );
     }
 
@@ -159,9 +299,10 @@ mod tests {
         let mut parser = Parser::new("bright-blue");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "bright-blue"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "bright-blue".into(),
+                span!(0..11)
+            )]))
         );
     }
 
@@ -170,7 +311,10 @@ mod tests {
         let mut parser = Parser::new("default");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("default"))]))
+            Some(Tokens::from(vec![Token::Content(
+                "default".into(),
+                span!(0..7)
+            )]))
         );
     }
 
@@ -179,9 +323,10 @@ mod tests {
         let mut parser = Parser::new("fast-blink");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "fast-blink"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "fast-blink".into(),
+                span!(0..10)
+            )]))
         )
     }
 
@@ -190,7 +335,7 @@ mod tests {
         let mut parser = Parser::new(":");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(":"))]))
+            Some(Tokens::from(vec![Token::Content(":".into(), span!(0..1))]))
         )
     }
 
@@ -199,7 +344,7 @@ mod tests {
         let mut parser = Parser::new(";");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(";"))]))
+            Some(Tokens::from(vec![Token::Content(";".into(), span!(0..1))]))
         )
     }
 
@@ -208,7 +353,7 @@ mod tests {
         let mut parser = Parser::new(",");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(","))]))
+            Some(Tokens::from(vec![Token::Content(",".into(), span!(0..1))]))
         )
     }
 
@@ -217,7 +362,7 @@ mod tests {
         let mut parser = Parser::new("fg");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("fg"))]))
+            Some(Tokens::from(vec![Token::Content("fg".into(), span!(0..2))]))
         );
     }
 
@@ -226,7 +371,7 @@ mod tests {
         let mut parser = Parser::new("bg");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("bg"))]))
+            Some(Tokens::from(vec![Token::Content("bg".into(), span!(0..2))]))
         );
     }
 
@@ -235,7 +380,10 @@ mod tests {
         let mut parser = Parser::new("deco");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("deco"))]))
+            Some(Tokens::from(vec![Token::Content(
+                "deco".into(),
+                span!(0..4)
+            )]))
         );
     }
 
@@ -244,7 +392,7 @@ mod tests {
         let mut parser = Parser::new("\\\\");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("\\"))]))
+            Some(Tokens::from(vec![Token::Content("\\".into(), span!(0..2))]))
         )
     }
 
@@ -253,7 +401,7 @@ mod tests {
         let mut parser = Parser::new("\\[");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("["))]))
+            Some(Tokens::from(vec![Token::Content("[".into(), span!(0..2))]))
         )
     }
 
@@ -262,7 +410,7 @@ mod tests {
         let mut parser = Parser::new("\\]");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("]"))]))
+            Some(Tokens::from(vec![Token::Content("]".into(), span!(0..2))]))
         )
     }
 
@@ -271,7 +419,7 @@ mod tests {
         let mut parser = Parser::new("\\(");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("("))]))
+            Some(Tokens::from(vec![Token::Content("(".into(), span!(0..2))]))
         )
     }
 
@@ -280,35 +428,249 @@ mod tests {
         let mut parser = Parser::new("\\)");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(")"))]))
+            Some(Tokens::from(vec![Token::Content(")".into(), span!(0..2))]))
         )
     }
 
+    #[test]
+    fn escaped_hex() {
+        let mut parser = Parser::new("\\x41\\x7e");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Content("A~".into(), span!(0..8))]))
+        );
+        assert_eq!(parser.errors, vec![]);
+    }
+
+    #[test]
+    fn escaped_hex_is_case_insensitive() {
+        for source in ["\\x1b", "\\x1B"] {
+            let mut parser = Parser::new(source);
+            assert_eq!(
+                text(&mut parser),
+                Some(Tokens::from(vec![Token::Content(
+                    "\x1b".into(),
+                    span!(0..4)
+                )]))
+            );
+        }
+    }
+
+    #[test]
+    fn escaped_hex_merges_with_content() {
+        let mut parser = Parser::new("[fg:red](a\\x42c)");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Styled {
+                style: style!(fg: Red;),
+                content: vec![Token::Content("aBc".into(), span!(9..15))],
+                span: span!(0..16),
+            }]))
+        );
+    }
+
+    #[test]
+    fn invalid_hex_escape() {
+        let mut parser = Parser::new("a\\xZZ");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Content(
+                "aZZ".into(),
+                span!(0..5)
+            ),]))
+        );
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(1..3)),
+                at: SyntaxKind::EscapeCharacter,
+                reason: Reason::InvalidHexEscape
+            }]
+        );
+    }
+
+    #[test]
+    fn non_ascii_hex_escape() {
+        let mut parser = Parser::new("\\xff");
+        assert_eq!(text(&mut parser), Some(Tokens::from(vec![])));
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(0..4)),
+                at: SyntaxKind::EscapeHex,
+                reason: Reason::InvalidHexEscape
+            }]
+        );
+    }
+
+    #[test]
+    fn escaped_unicode_bmp() {
+        let mut parser = Parser::new("caf\\u{e9}");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Content(
+                "café".into(),
+                span!(0..9)
+            )]))
+        );
+        assert_eq!(parser.errors, vec![]);
+    }
+
+    #[test]
+    fn escaped_unicode_astral() {
+        let mut parser = Parser::new("[fg:yellow](\\u{1F600})");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Styled {
+                style: style!(fg: Yellow;),
+                content: vec![Token::Content("😀".into(), span!(12..21))],
+                span: span!(0..22),
+            }]))
+        );
+        assert_eq!(parser.errors, vec![]);
+    }
+
+    #[test]
+    fn invalid_unicode_escape() {
+        for (source, span) in [("\\u{D800}", span!(0..8)), ("\\u{110000}", span!(0..10))] {
+            let mut parser = Parser::new(source);
+            assert_eq!(text(&mut parser), Some(Tokens::from(vec![])));
+            assert_eq!(
+                parser.errors,
+                vec![Error {
+                    span: Some(span),
+                    at: SyntaxKind::EscapeUnicode,
+                    reason: Reason::InvalidUnicodeEscape
+                }],
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_unicode_escape() {
+        let mut parser = Parser::new("\\u1F600");
+        text(&mut parser);
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(0..2)),
+                at: SyntaxKind::EscapeCharacter,
+                reason: Reason::InvalidUnicodeEscape
+            }]
+        );
+    }
+
     #[test]
     fn escaped_whitespace() {
         let mut parser = Parser::new("\\ \n\t\r");
         assert_eq!(text(&mut parser), Some(Tokens::from(vec![])));
     }
 
+    #[test]
+    fn escaped_form_feed_and_vertical_tab() {
+        for source in ["\\\x0c", "\\\x0b", "\\\x0c \x0b\n"] {
+            let mut parser = Parser::new(source);
+            assert_eq!(text(&mut parser), Some(Tokens::from(vec![])), "{source:?}");
+            assert_eq!(parser.errors, vec![]);
+        }
+
+        let mut parser = Parser::new("a\\\x0c\x0bb");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Content("ab".into(), span!(0..5))]))
+        );
+    }
+
+    #[test]
+    fn escaped_space_and_tab() {
+        let mut parser = Parser::new("hello\\s");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Content(
+                "hello ".into(),
+                span!(0..7)
+            )]))
+        );
+        assert_eq!(parser.errors, vec![]);
+
+        let mut parser = Parser::new("a\\t\\s\\\n");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Content(
+                "a\t ".into(),
+                span!(0..5)
+            )]))
+        );
+    }
+
+    #[test]
+    fn unescaped_form_feed_and_vertical_tab_are_text() {
+        let mut parser = Parser::new("a\x0cb\x0bc");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Content(
+                "a\x0cb\x0bc".into(),
+                span!(0..5)
+            )]))
+        );
+    }
+
     #[test]
     fn multiple_distinct_tokens() {
         let mut parser = Parser::new("some plaintext \\(ascii\\] \\\n\n :+1:");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "some plaintext (ascii] :+1:"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "some plaintext (ascii] :+1:".into(),
+                span!(0..33)
+            )]))
         );
     }
 
+    #[test]
+    fn plain_text_is_borrowed() {
+        let mut parser = Parser::new("some plain: text, here;");
+        let tokens = Vec::from(text(&mut parser).unwrap());
+        assert!(matches!(
+            tokens.as_slice(),
+            [Token::Content(Cow::Borrowed("some plain: text, here;"), _)]
+        ));
+    }
+
+    #[test]
+    fn escape_character_alone_is_borrowed() {
+        let mut parser = Parser::new("\\(");
+        let tokens = Vec::from(text(&mut parser).unwrap());
+        assert!(matches!(
+            tokens.as_slice(),
+            [Token::Content(Cow::Borrowed("("), _)]
+        ));
+    }
+
+    #[test]
+    fn styled_content_is_borrowed() {
+        let mut parser = Parser::new("leading [fg:red](styled) trailing");
+        let tokens = Vec::from(text(&mut parser).unwrap());
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                Token::Content(Cow::Borrowed("leading "), _),
+                Token::Styled { content, .. },
+                Token::Content(Cow::Borrowed(" trailing"), _),
+            ] if matches!(content.as_slice(), [Token::Content(Cow::Borrowed("styled"), _)])
+        ));
+    }
+
     #[test]
     fn mixed_characters_and_escape_characters() {
         let mut parser = Parser::new("abc\\(DEF\\)12\\   34\\[!@#$\\]");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from(
-                "abc(DEF)1234[!@#$]"
-            ))]))
+            Some(Tokens::from(vec![Token::Content(
+                "abc(DEF)1234[!@#$]".into(),
+                span!(0..26)
+            )]))
         );
     }
 
@@ -372,7 +734,10 @@ mod tests {
         let mut parser = Parser::new("before ( after");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("before "))]))
+            Some(Tokens::from(vec![Token::Content(
+                "before ".into(),
+                span!(0..7)
+            )]))
         );
         assert_eq!(parser.peek(), Some(SyntaxKind::ParenthesisOpen));
     }
@@ -382,7 +747,10 @@ mod tests {
         let mut parser = Parser::new("before ) after");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("before "))]))
+            Some(Tokens::from(vec![Token::Content(
+                "before ".into(),
+                span!(0..7)
+            )]))
         );
         assert_eq!(parser.peek(), Some(SyntaxKind::ParenthesisClose));
     }
@@ -399,7 +767,10 @@ mod tests {
                 reason: Reason::Expected(vec![
                     SyntaxKind::ForegroundSpecifier,
                     SyntaxKind::BackgroundSpecifier,
+                    SyntaxKind::UnderlineColorSpecifier,
                     SyntaxKind::DecorationSpecifier,
+                    SyntaxKind::ResetSpecifier,
+                    SyntaxKind::RawSpecifier,
                 ])
             }]
         );
@@ -410,7 +781,10 @@ mod tests {
         let mut parser = Parser::new("before ] after");
         assert_eq!(
             text(&mut parser),
-            Some(Tokens::from(vec![Token::Content(String::from("before "))]))
+            Some(Tokens::from(vec![Token::Content(
+                "before ".into(),
+                span!(0..7)
+            )]))
         );
         assert_eq!(parser.peek(), Some(SyntaxKind::SquareBracketClose));
     }
@@ -436,10 +810,11 @@ mod tests {
             text(&mut parser),
             Some(Tokens::from(vec![
                 Token::Styled {
-                    content: vec![Token::Content(String::from("before "))],
-                    style: style!(fg: Red;)
+                    content: vec![Token::Content("before ".into(), span!(9..16))],
+                    style: style!(fg: Red;),
+                    span: span!(0..17),
                 },
-                Token::Content(String::from(" after"))
+                Token::Content(" after".into(), span!(17..23))
             ]))
         );
         assert_eq!(parser.peek(), Some(SyntaxKind::ParenthesisClose));
@@ -457,7 +832,10 @@ mod tests {
                 reason: Reason::Expected(vec![
                     SyntaxKind::ForegroundSpecifier,
                     SyntaxKind::BackgroundSpecifier,
+                    SyntaxKind::UnderlineColorSpecifier,
                     SyntaxKind::DecorationSpecifier,
+                    SyntaxKind::ResetSpecifier,
+                    SyntaxKind::RawSpecifier,
                 ])
             }]
         );
@@ -480,7 +858,14 @@ mod tests {
     #[test]
     fn token_empty_specifier() {
         let mut parser = Parser::new("[]()");
-        assert_eq!(text(&mut parser), None);
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Styled {
+                style: style!(),
+                content: vec![],
+                span: span!(0..4),
+            }]))
+        );
         assert_eq!(
             parser.errors,
             vec![Error {
@@ -489,7 +874,10 @@ mod tests {
                 reason: Reason::Expected(vec![
                     SyntaxKind::ForegroundSpecifier,
                     SyntaxKind::BackgroundSpecifier,
-                    SyntaxKind::DecorationSpecifier
+                    SyntaxKind::UnderlineColorSpecifier,
+                    SyntaxKind::DecorationSpecifier,
+                    SyntaxKind::ResetSpecifier,
+                    SyntaxKind::RawSpecifier
                 ])
             }]
         );
@@ -502,9 +890,9 @@ mod tests {
         assert_eq!(
             parser.errors,
             vec![Error {
-                span: None,
+                span: Some(span!(0..1)),
                 at: SyntaxKind::Eof,
-                reason: Reason::Expected(vec![SyntaxKind::SquareBracketClose])
+                reason: Reason::UnterminatedSpecifier,
             }]
         );
     }
@@ -516,9 +904,9 @@ mod tests {
         assert_eq!(
             parser.errors,
             vec![Error {
-                span: None,
+                span: Some(span!(8..9)),
                 at: SyntaxKind::Eof,
-                reason: Reason::Expected(vec![SyntaxKind::ParenthesisClose])
+                reason: Reason::UnterminatedContent,
             }]
         );
     }
@@ -537,6 +925,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn escaped_non_ascii_character() {
+        let mut parser = Parser::new("\\é");
+        assert_eq!(text(&mut parser), Some(Tokens::from(vec![])));
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(0..3)),
+                at: SyntaxKind::EscapeCharacter,
+                reason: Reason::UnknownEscapeSequence('é')
+            }]
+        );
+    }
+
+    #[test]
+    fn trailing_backslash() {
+        let mut parser = Parser::new("text\\");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Content(
+                "text".into(),
+                span!(0..4)
+            )]))
+        );
+        assert_eq!(
+            parser.errors,
+            vec![Error {
+                span: Some(span!(4..5)),
+                at: SyntaxKind::Unknown,
+                reason: Reason::Expected(vec![SyntaxKind::EscapeCharacter])
+            }]
+        );
+    }
+
+    #[test]
+    fn truncated_ansi_sequence_is_text() {
+        let mut parser = Parser::new("a\x1b[31");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Content(
+                "a\x1b[31".into(),
+                span!(0..5)
+            )]))
+        );
+        assert_eq!(parser.errors, vec![]);
+    }
+
     #[test]
     fn token_invalid_escape_character() {
         let mut parser = Parser::new("[fg:red](\\a)");
@@ -544,7 +979,8 @@ mod tests {
             text(&mut parser),
             Some(Tokens::from(vec![Token::Styled {
                 style: style!(fg: Red;),
-                content: vec![]
+                content: vec![],
+                span: span!(0..12),
             }]))
         );
         assert_eq!(
@@ -556,4 +992,54 @@ mod tests {
             }]
         );
     }
+
+    #[test]
+    fn comment_is_dropped() {
+        let mut parser = Parser::new("[#: a comment]");
+        assert_eq!(text(&mut parser), Some(Tokens::from(vec![])));
+        assert_eq!(parser.errors, vec![]);
+    }
+
+    #[test]
+    fn comment_between_content_is_merged() {
+        let mut parser = Parser::new("before [#: a comment]after");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Content(
+                "before after".into(),
+                span!(0..26)
+            )]))
+        );
+    }
+
+    #[test]
+    fn comment_adjacent_to_token() {
+        let mut parser = Parser::new("[#: leading][fg:red](styled)[#: trailing] after");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![
+                Token::Styled {
+                    style: style!(fg: Red;),
+                    content: vec![Token::Content("styled".into(), span!(21..27))],
+                    span: span!(12..28),
+                },
+                Token::Content(" after".into(), span!(41..47))
+            ]))
+        );
+        assert_eq!(parser.errors, vec![]);
+    }
+
+    #[test]
+    fn comment_in_token() {
+        let mut parser = Parser::new("[fg:red](a[#: (b) \\] c]d)");
+        assert_eq!(
+            text(&mut parser),
+            Some(Tokens::from(vec![Token::Styled {
+                style: style!(fg: Red;),
+                content: vec![Token::Content("ad".into(), span!(9..24))],
+                span: span!(0..25),
+            }]))
+        );
+        assert_eq!(parser.errors, vec![]);
+    }
 }
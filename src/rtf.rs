@@ -0,0 +1,281 @@
+use crate::{
+    ast::{Color, Decoration, Style, Token},
+    error::Error,
+    parser::Parser,
+};
+
+/// Convert styled markup to an RTF document for pasting into word processors
+///
+/// Each styled piece of text becomes a group using the equivalent control words, with colors
+/// taken from a color table containing only the colors that are used. Colors use the same values
+/// as [`to_html`](crate::to_html), while `default` colors use the document's automatic color.
+/// Underline colors become `\ulc`.
+/// Decorations without an RTF equivalent (i.e. dimming, blinking, and inverting) are dropped, as
+/// are any ANSI escape sequences present in the source.
+pub fn to_rtf(source: &str) -> Result<String, Vec<Error>> {
+    let (tokens, errors) = Parser::new(source).parse();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut colors = Vec::new();
+    collect_colors(&mut colors, &tokens);
+
+    let mut result = String::with_capacity(source.len());
+    result.push_str("{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0\\fmodern Courier New;}}");
+    result.push_str("{\\colortbl ;");
+    for color in &colors {
+        let (red, green, blue) = color.rgb().expect("default color is never collected");
+        result.push_str(&format!("\\red{red}\\green{green}\\blue{blue};"));
+    }
+    result.push_str("}\n\\f0 ");
+
+    convert_tokens(&mut result, &colors, &tokens);
+
+    result.push('}');
+    Ok(result)
+}
+
+/// Collect every distinct color used by the tokens in the order they first appear
+///
+/// The default color is left out, as it always refers to the automatic color at index 0.
+fn collect_colors(colors: &mut Vec<Color>, tokens: &[Token]) {
+    let mut stack = vec![tokens.iter()];
+    while let Some(tokens) = stack.last_mut() {
+        match tokens.next() {
+            Some(Token::Styled { content, style, .. }) => {
+                let used = [style.foreground, style.background, style.underline_color];
+                for color in used.into_iter().flatten() {
+                    if color != Color::Default && !colors.contains(&color) {
+                        colors.push(color);
+                    }
+                }
+
+                stack.push(content.iter());
+            }
+            Some(Token::Content(..) | Token::Raw(..)) => {}
+            None => {
+                stack.pop();
+            }
+        }
+    }
+}
+
+/// Convert the tokens into the body of the RTF document
+///
+/// The tokens are walked with an explicit stack, so arbitrarily deep nesting can't overflow the
+/// call stack. Each level tracks whether it is wrapped in a group that must be closed.
+fn convert_tokens(output: &mut String, colors: &[Color], tokens: &[Token]) {
+    let mut stack = vec![(tokens.iter(), false)];
+    while let Some((tokens, _)) = stack.last_mut() {
+        match tokens.next() {
+            Some(Token::Content(content, _)) => push_escaped(output, content),
+            Some(Token::Raw(..)) => {}
+            Some(Token::Styled { content, style, .. }) => {
+                if content.is_empty() {
+                    continue;
+                }
+
+                let words = control_words(colors, style);
+                if let Some(words) = &words {
+                    output.push('{');
+                    output.push_str(words);
+                    // the space ends the last control word and isn't part of the text
+                    output.push(' ');
+                }
+                stack.push((content.iter(), words.is_some()));
+            }
+            None => {
+                if let Some((_, true)) = stack.pop() {
+                    output.push('}');
+                }
+            }
+        }
+    }
+}
+
+/// Generate the control words that apply a style to the rest of the group
+///
+/// Returns `None` if the style has no RTF equivalent.
+fn control_words(colors: &[Color], style: &Style) -> Option<String> {
+    // the color table always starts with the automatic color, so the indices start at 1
+    let index = |color: Color| match color {
+        Color::Default => 0,
+        color => colors.iter().position(|&c| c == color).unwrap() + 1,
+    };
+
+    let mut words = Vec::new();
+    if style.reset {
+        words.push(String::from("\\plain\\f0"));
+    }
+    if let Some(foreground) = style.foreground {
+        words.push(format!("\\cf{}", index(foreground)));
+    }
+    if let Some(background) = style.background {
+        words.push(format!("\\cb{}", index(background)));
+    }
+    if let Some(underline_color) = style.underline_color {
+        words.push(format!("\\ulc{}", index(underline_color)));
+    }
+    for decoration in style.decoration.iter().flatten() {
+        let word = match decoration {
+            Decoration::Bold => "\\b",
+            Decoration::Italic => "\\i",
+            Decoration::Underline => "\\ul",
+            Decoration::StrikeThrough => "\\strike",
+            Decoration::Hide => "\\v",
+            Decoration::Dim
+            | Decoration::SlowBlink
+            | Decoration::FastBlink
+            | Decoration::Invert => continue,
+        };
+        words.push(String::from(word));
+    }
+
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.concat())
+    }
+}
+
+/// Push the content to the output, escaping any characters that have a special meaning in RTF
+///
+/// Non-ASCII characters are written as their UTF-16 code units using `\uN`, followed by `?` for
+/// readers that don't support unicode.
+fn push_escaped(output: &mut String, content: &str) {
+    for ch in content.chars() {
+        match ch {
+            '\\' | '{' | '}' => {
+                output.push('\\');
+                output.push(ch);
+            }
+            '\n' => output.push_str("\\line "),
+            '\t' => output.push_str("\\tab "),
+            '\r' => {}
+            ' '..='~' => output.push(ch),
+            _ => {
+                let mut units = [0; 2];
+                for unit in ch.encode_utf16(&mut units) {
+                    // control word parameters are signed 16-bit integers
+                    output.push_str(&format!("\\u{}?", *unit as i16));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{push_escaped, to_rtf};
+
+    /// Get the body of the document, after the font and color tables
+    fn body(source: &str) -> String {
+        let document = to_rtf(source).unwrap();
+        let (_, body) = document.split_once("\n\\f0 ").unwrap();
+        body.strip_suffix('}').unwrap().to_owned()
+    }
+
+    fn escaped(content: &str) -> String {
+        let mut output = String::new();
+        push_escaped(&mut output, content);
+        output
+    }
+
+    #[test]
+    fn unstyled() {
+        assert_eq!(body("plain text"), "plain text");
+    }
+
+    #[test]
+    fn document() {
+        insta::assert_snapshot!(to_rtf(
+            "[fg:red;deco:bold](error): {build} failed\n[fg:bright-blue;bg:white](hint): see [deco:italic,underline](C:\\\\logs) [fg:red](again)"
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn color_table_only_contains_used_colors() {
+        let document = to_rtf("[fg:green](a [bg:blue](b [fg:green](c)))").unwrap();
+        assert!(document.contains("{\\colortbl ;\\red0\\green205\\blue0;\\red0\\green0\\blue238;}"));
+    }
+
+    #[test]
+    fn default_color_uses_automatic_color() {
+        let document = to_rtf("[fg:red]([fg:default](text))").unwrap();
+        assert!(document.contains("{\\colortbl ;\\red205\\green0\\blue0;}"));
+        assert_eq!(body("[fg:red]([fg:default](text))"), "{\\cf1 {\\cf0 text}}");
+    }
+
+    #[test]
+    fn underline_color() {
+        let document = to_rtf("[ul:red;deco:underline](x)").unwrap();
+        assert!(document.contains("{\\colortbl ;\\red205\\green0\\blue0;}"));
+        assert_eq!(body("[ul:red;deco:underline](x)"), "{\\ulc1\\ul x}");
+    }
+
+    #[test]
+    fn decorations() {
+        assert_eq!(
+            body("[deco:bold,italic,underline,strikethrough,hide](text)"),
+            "{\\b\\i\\ul\\strike\\v text}"
+        );
+    }
+
+    #[test]
+    fn unsupported_decorations_are_dropped() {
+        assert_eq!(
+            body("[deco:dim,blink-slow,blink-fast,invert](text)"),
+            "text"
+        );
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(
+            body("a [fg:red](b [bg:blue](c) b) a"),
+            "a {\\cf1 b {\\cb2 c} b} a"
+        );
+    }
+
+    #[test]
+    fn reset() {
+        assert_eq!(
+            body("[deco:bold](a [reset;fg:red](b))"),
+            "{\\b a {\\plain\\f0\\cf1 b}}"
+        );
+    }
+
+    #[test]
+    fn empty_token() {
+        assert_eq!(body("[fg:red]()"), "");
+    }
+
+    #[test]
+    fn existing_escape_sequences_are_removed() {
+        assert_eq!(body("\x1b[1mbold\x1b[22m"), "bold");
+    }
+
+    #[test]
+    fn invalid_markup() {
+        assert!(to_rtf("[fg:red](unclosed").is_err());
+    }
+
+    #[test]
+    fn special_characters_are_escaped() {
+        assert_eq!(escaped("a\\b {c}"), "a\\\\b \\{c\\}");
+    }
+
+    #[test]
+    fn whitespace_is_converted() {
+        assert_eq!(escaped("a\r\n\tb"), "a\\line \\tab b");
+    }
+
+    #[test]
+    fn non_ascii_is_escaped() {
+        assert_eq!(escaped("café"), "caf\\u233?");
+        assert_eq!(escaped("\u{ffe9}"), "\\u-23?");
+        assert_eq!(escaped("😀"), "\\u-10179?\\u-8704?");
+    }
+}
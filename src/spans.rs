@@ -0,0 +1,128 @@
+use crate::{
+    ast::{CurrentStyle, Tokens},
+    error::Error,
+};
+
+/// Convert styled markup to a flat sequence of text runs, each with the style it is displayed with
+///
+/// The styles are absolute rather than relative to the previous run, resolving all the enclosing
+/// markup the same way as [`CurrentStyle::extend`]. Adjacent runs with the same style are merged,
+/// while empty runs and any ANSI escape sequences present in the source are dropped.
+pub fn spans(source: &str) -> Result<Vec<(String, CurrentStyle)>, Vec<Error>> {
+    let tokens = Tokens::parse(source)?;
+
+    let mut spans: Vec<(String, CurrentStyle)> = Vec::new();
+    for (text, style) in tokens.iter() {
+        match spans.last_mut() {
+            Some((last, last_style)) if *last_style == style => last.push_str(text),
+            _ => spans.push((text.to_owned(), style)),
+        }
+    }
+
+    Ok(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::spans;
+    use crate::ast::{Color, CurrentStyle, Decoration};
+
+    fn span(text: &str, style: CurrentStyle) -> (String, CurrentStyle) {
+        (text.to_owned(), style)
+    }
+
+    #[test]
+    fn unstyled() {
+        assert_eq!(
+            spans("plain text").unwrap(),
+            vec![span("plain text", CurrentStyle::default())]
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(spans("").unwrap(), vec![]);
+        assert_eq!(spans("[fg:red]()").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn styled() {
+        assert_eq!(
+            spans("before [fg:red](red) after").unwrap(),
+            vec![
+                span("before ", CurrentStyle::default()),
+                span("red", style!(fg: Red;).into()),
+                span(" after", CurrentStyle::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_styles_are_resolved() {
+        let outer = CurrentStyle::from(style!(fg: Red; deco: Bold;));
+        let inner = outer.extend(&style!(bg: Blue; deco: Italic;));
+
+        let result = spans("[fg:red;deco:bold](a [bg:blue;deco:italic](b) c)").unwrap();
+        assert_eq!(
+            result,
+            vec![
+                span("a ", outer.clone()),
+                span("b", inner.clone()),
+                span(" c", outer)
+            ]
+        );
+
+        let (_, style) = &result[1];
+        assert_eq!(style.foreground(), Color::Red);
+        assert_eq!(style.background(), Color::Blue);
+        assert!(style.decoration().contains(&Decoration::Bold));
+        assert!(style.decoration().contains(&Decoration::Italic));
+    }
+
+    #[test]
+    fn nested_overrides() {
+        assert_eq!(
+            spans("[fg:red](a [fg:blue](b [fg:default](c)))").unwrap(),
+            vec![
+                span("a ", style!(fg: Red;).into()),
+                span("b ", style!(fg: Blue;).into()),
+                span("c", CurrentStyle::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reset_clears_enclosing_styles() {
+        assert_eq!(
+            spans("[fg:red;deco:dim](a [reset;bg:green](b))").unwrap(),
+            vec![
+                span("a ", style!(fg: Red; deco: Dim;).into()),
+                span("b", style!(bg: Green;).into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn adjacent_runs_with_the_same_style_are_merged() {
+        assert_eq!(
+            spans("[fg:red](a)[fg:red](b)[deco:bold]()c\x1b[1md").unwrap(),
+            vec![
+                span("ab", style!(fg: Red;).into()),
+                span("cd", CurrentStyle::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_characters() {
+        assert_eq!(
+            spans("[fg:red](\\(a\\))").unwrap(),
+            vec![span("(a)", style!(fg: Red;).into())]
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(spans("[fg:red](unclosed").is_err());
+    }
+}
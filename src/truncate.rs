@@ -0,0 +1,366 @@
+use crate::{
+    ast::{CurrentStyle, Style, Token},
+    error::Error,
+    parser::Parser,
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Convert styled markup to ANSI escape codes, limiting the output to a maximum visible width
+///
+/// When the content is wider than `max_width`, it is cut at the last character that fits and the
+/// optional ellipsis is appended inside the active style. The ellipsis counts towards the width
+/// limit, and is itself cut if it is wider than the limit. All styles that were open at the cut
+/// are reset, so the output never contains dangling escape sequences. If any ANSI escape
+/// sequences from the source were emitted before the cut, a full reset (`\x1b[0m`) is appended
+/// as well.
+pub fn truncate(
+    source: &str,
+    max_width: usize,
+    ellipsis: Option<&str>,
+) -> Result<String, Vec<Error>> {
+    let (tokens, errors) = Parser::new(source).parse();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut result = String::with_capacity(source.len());
+    if visible_width(&tokens) <= max_width {
+        convert_tokens(&mut result, CurrentStyle::default(), &tokens, &mut None);
+    } else {
+        let ellipsis = fit_width(ellipsis.unwrap_or_default(), max_width);
+        let mut state = Some(Truncation {
+            remaining: max_width.saturating_sub(ellipsis.width()),
+            ellipsis,
+            raw: false,
+        });
+        let cut = convert_tokens(&mut result, CurrentStyle::default(), &tokens, &mut state);
+
+        // existing escape sequences could have changed anything, and whatever undid them was cut
+        if cut && state.is_some_and(|state| state.raw) {
+            result.push_str("\x1b[0m");
+        }
+    }
+
+    result.shrink_to_fit();
+    Ok(result)
+}
+
+/// Compute the number of columns styled markup occupies once it is displayed
+///
+/// Style specifiers, comments, and any existing ANSI escape sequences take up no space, and escaped
+/// characters count as the character they produce.
+pub fn width(source: &str) -> Result<usize, Vec<Error>> {
+    let (tokens, errors) = Parser::new(source).parse();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(visible_width(&tokens))
+}
+
+/// Tracks the progress of truncating the output
+struct Truncation<'e> {
+    /// The number of columns left before the content must be cut
+    remaining: usize,
+    /// The text to insert where the content was cut
+    ellipsis: &'e str,
+    /// Whether any ANSI escape sequences from the source have been emitted
+    raw: bool,
+}
+
+/// Get the longest prefix of the text that fits within the given number of columns
+fn fit_width(text: &str, max_width: usize) -> &str {
+    let mut width = 0;
+    for (index, ch) in text.char_indices() {
+        width += ch.width().unwrap_or_default();
+        if width > max_width {
+            return &text[..index];
+        }
+    }
+
+    text
+}
+
+/// Compute the number of columns the content of the tokens occupies
+fn visible_width(tokens: &[Token]) -> usize {
+    let mut width = 0;
+    let mut stack = vec![tokens.iter()];
+    while let Some(tokens) = stack.last_mut() {
+        match tokens.next() {
+            Some(Token::Content(content, _)) => width += content.width(),
+            Some(Token::Raw(..)) => {}
+            Some(Token::Styled { content, .. }) => stack.push(content.iter()),
+            None => {
+                stack.pop();
+            }
+        }
+    }
+
+    width
+}
+
+/// Convert the tokens into the resulting string, stopping once the content is cut
+///
+/// The tokens are walked with an explicit stack, so arbitrarily deep nesting can't overflow the
+/// call stack. Returns whether the content was cut, in which case every open style has already
+/// been reset.
+fn convert_tokens(
+    output: &mut String,
+    parent_style: CurrentStyle,
+    tokens: &[Token],
+    truncation: &mut Option<Truncation>,
+) -> bool {
+    // each level keeps the style it was opened with, so it can be reset once the level ends
+    let mut stack = vec![(tokens.iter(), parent_style, None::<&Style>)];
+    let mut cut = false;
+
+    while let Some((tokens, current, _)) = stack.last_mut() {
+        // once cut, the remaining levels are only closed
+        let token = if cut { None } else { tokens.next() };
+        match token {
+            Some(Token::Content(content, _)) => cut = push_content(output, content, truncation),
+            Some(Token::Raw(sequence, _)) => {
+                output.push_str(sequence);
+                if let Some(truncation) = truncation {
+                    truncation.raw = true;
+                }
+            }
+            Some(Token::Styled { content, style, .. }) => {
+                if content.is_empty() {
+                    continue;
+                }
+
+                // avoid opening a style that can't contain any content
+                if let Some(truncation) = truncation.as_ref().filter(|t| t.remaining == 0) {
+                    output.push_str(truncation.ellipsis);
+                    cut = true;
+                    continue;
+                }
+
+                style.apply(current, output);
+                let nested = current.extend(style);
+                stack.push((content.iter(), nested, Some(style)));
+            }
+            None => {
+                let (_, _, style) = stack.pop().expect("stack is not empty");
+                if let Some(style) = style {
+                    let (_, parent, _) = stack.last().expect("the top level has no style");
+                    style.reset(parent, output);
+                }
+            }
+        }
+    }
+
+    cut
+}
+
+/// Push as much of the content as fits within the remaining width
+///
+/// Returns whether the content was cut.
+fn push_content(output: &mut String, content: &str, truncation: &mut Option<Truncation>) -> bool {
+    let Some(truncation) = truncation else {
+        output.push_str(content);
+        return false;
+    };
+
+    for ch in content.chars() {
+        let width = ch.width().unwrap_or_default();
+        if width > truncation.remaining {
+            output.push_str(truncation.ellipsis);
+            return true;
+        }
+
+        truncation.remaining -= width;
+        output.push(ch);
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{truncate, width};
+    use unicode_width::UnicodeWidthStr;
+
+    #[test]
+    fn width_of_plain_text() {
+        assert_eq!(width("").unwrap(), 0);
+        assert_eq!(width("plain text").unwrap(), 10);
+    }
+
+    #[test]
+    fn width_of_nested_markup() {
+        assert_eq!(
+            width("a [fg:red](b [deco:bold;bg:blue](cd) e)[#: comment] f").unwrap(),
+            10
+        );
+    }
+
+    #[test]
+    fn width_of_escaped_characters() {
+        assert_eq!(width("\\[\\(x\\)\\] \\\\").unwrap(), 7);
+        assert_eq!(width("a \\\n   b").unwrap(), 3);
+    }
+
+    #[test]
+    fn width_of_wide_characters_and_escape_sequences() {
+        assert_eq!(width("[fg:red](日本) \x1b[1mx\x1b[22m").unwrap(), 6);
+    }
+
+    #[test]
+    fn width_of_invalid_markup() {
+        assert!(width("[fg:red](unclosed").is_err());
+    }
+
+    #[test]
+    fn empty_source() {
+        assert_eq!(truncate("", 5, None).unwrap(), "");
+    }
+
+    #[test]
+    fn unstyled_shorter_than_limit() {
+        assert_eq!(truncate("short", 10, Some("…")).unwrap(), "short");
+    }
+
+    #[test]
+    fn unstyled_exactly_at_limit() {
+        assert_eq!(truncate("exact", 5, Some("…")).unwrap(), "exact");
+    }
+
+    #[test]
+    fn unstyled_longer_than_limit() {
+        assert_eq!(truncate("too long", 5, None).unwrap(), "too l");
+    }
+
+    #[test]
+    fn unstyled_longer_than_limit_with_ellipsis() {
+        assert_eq!(truncate("too long", 5, Some("...")).unwrap(), "to...");
+    }
+
+    #[test]
+    fn zero_width() {
+        assert_eq!(truncate("[fg:red](content)", 0, None).unwrap(), "");
+    }
+
+    #[test]
+    fn ellipsis_wider_than_limit() {
+        assert_eq!(truncate("content", 2, Some("...")).unwrap(), "..");
+        assert_eq!(truncate("[fg:red](content)", 2, Some("...")).unwrap(), "..");
+        assert_eq!(truncate("content", 0, Some("...")).unwrap(), "");
+    }
+
+    #[test]
+    fn wide_ellipsis_that_does_not_fit_is_dropped() {
+        assert_eq!(truncate("content", 1, Some("……")).unwrap(), "…");
+
+        // the remaining column is used for the content instead
+        let result = truncate("content", 1, Some("日")).unwrap();
+        assert!(result.width() <= 1, "{result:?}");
+        assert_eq!(result, "c");
+    }
+
+    #[test]
+    fn comments_have_no_width() {
+        assert_eq!(
+            truncate("short[#: a long comment that is dropped]", 5, Some("…")).unwrap(),
+            "short"
+        );
+    }
+
+    #[test]
+    fn styled_shorter_than_limit() {
+        assert_eq!(
+            truncate("[fg:red](content)", 10, None).unwrap(),
+            "\x1b[31mcontent\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn styled_cut_inside_style() {
+        assert_eq!(
+            truncate("[fg:red](content)", 4, None).unwrap(),
+            "\x1b[31mcont\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn ellipsis_is_inside_active_style() {
+        assert_eq!(
+            truncate("leading [fg:red](content)", 12, Some("…")).unwrap(),
+            "leading \x1b[31mcon…\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn styled_cut_before_style() {
+        assert_eq!(
+            truncate("leading [fg:red](content)", 5, None).unwrap(),
+            "leadi"
+        );
+    }
+
+    #[test]
+    fn cut_mid_nesting_resets_all_open_styles() {
+        assert_eq!(
+            truncate(
+                "leading [fg:red](one [bg:blue](two [deco:dim](three) two) one) trailing",
+                20,
+                Some("~")
+            )
+            .unwrap(),
+            "leading \x1b[31mone \x1b[44mtwo \x1b[2mthr~\x1b[22m\x1b[49m\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn trailing_content_is_dropped() {
+        assert_eq!(
+            truncate("[fg:red](one)[fg:blue](two)", 3, None).unwrap(),
+            "\x1b[31mone\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn wide_characters_are_not_split() {
+        assert_eq!(truncate("日本語", 5, None).unwrap(), "日本");
+    }
+
+    #[test]
+    fn escaped_characters_count_once() {
+        assert_eq!(truncate("\\[\\]\\(\\)", 4, Some("…")).unwrap(), "[]()");
+    }
+
+    #[test]
+    fn existing_escape_sequences_do_not_count_towards_width() {
+        assert_eq!(
+            truncate("\x1b[1mbold\x1b[22m text", 6, None).unwrap(),
+            "\x1b[1mbold\x1b[22m t\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn existing_escape_sequences_are_reset_when_cut() {
+        assert_eq!(
+            truncate("\x1b[1mhello world", 3, None).unwrap(),
+            "\x1b[1mhel\x1b[0m"
+        );
+        assert_eq!(
+            truncate("[fg:red](\x1b[4mhello) world", 4, Some("…")).unwrap(),
+            "\x1b[31m\x1b[4mhel…\x1b[39m\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn existing_escape_sequences_are_kept_when_not_cut() {
+        assert_eq!(
+            truncate("\x1b[1mhello\x1b[22m", 10, None).unwrap(),
+            "\x1b[1mhello\x1b[22m"
+        );
+        assert_eq!(truncate("hello world\x1b[1m", 5, None).unwrap(), "hello");
+    }
+
+    #[test]
+    fn invalid_markup() {
+        assert!(truncate("[fg:red](unclosed", 5, None).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use crate::ast::Token;
+
+/// Convert a token tree back into styled markup
+///
+/// Parsing the markup produces the same tokens, aside from their spans. Control characters in the
+/// content are escaped, and the escape character is written as `\x1b` so it can't combine with the
+/// following text into an ANSI escape sequence. Existing ANSI escape sequences are kept as-is.
+/// Styles without any properties can't be written as markup, so their content is written on its
+/// own. [`Tokens`](crate::ast::Tokens) can be passed directly, as they dereference to a slice.
+pub fn to_markup(tokens: &[Token]) -> String {
+    let mut result = String::new();
+    convert_tokens(&mut result, tokens);
+    result
+}
+
+/// Convert the tokens into the resulting markup
+///
+/// The tokens are walked with an explicit stack, so arbitrarily deep nesting can't overflow the
+/// call stack. Each level tracks whether its content is wrapped in parentheses that must be closed.
+fn convert_tokens(output: &mut String, tokens: &[Token]) {
+    let mut stack = vec![(tokens.iter(), false)];
+    while let Some((tokens, _)) = stack.last_mut() {
+        match tokens.next() {
+            Some(Token::Content(content, _)) => push_escaped(output, content),
+            Some(Token::Raw(sequence, _)) => output.push_str(sequence),
+            Some(Token::Styled { content, style, .. }) => {
+                if !style.is_empty() {
+                    output.push_str(&style.to_string());
+                    output.push('(');
+                }
+                stack.push((content.iter(), !style.is_empty()));
+            }
+            None => {
+                if let Some((_, true)) = stack.pop() {
+                    output.push(')');
+                }
+            }
+        }
+    }
+}
+
+/// Push the content to the output, escaping any characters that have a special meaning in markup
+fn push_escaped(output: &mut String, content: &str) {
+    for ch in content.chars() {
+        match ch {
+            '\\' | '[' | ']' | '(' | ')' => {
+                output.push('\\');
+                output.push(ch);
+            }
+            '\x1b' => output.push_str("\\x1b"),
+            _ => output.push(ch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_markup;
+    use crate::{
+        ast::{Token, Tokens},
+        parser::Parser,
+    };
+    use std::{fs, path::Path};
+    use text_size::TextRange;
+
+    fn parse(source: &str) -> Vec<Token<'_>> {
+        let (mut tokens, errors) = Parser::new(source).parse();
+        assert!(errors.is_empty(), "{source:?}: {errors:?}");
+        without_spans(&mut tokens);
+        tokens
+    }
+
+    /// Remove the spans from the tokens, as they change when the markup is written differently
+    fn without_spans(tokens: &mut [Token]) {
+        for token in tokens {
+            match token {
+                Token::Content(_, span) | Token::Raw(_, span) => *span = TextRange::default(),
+                Token::Styled { content, span, .. } => {
+                    *span = TextRange::default();
+                    without_spans(content);
+                }
+            }
+        }
+    }
+
+    fn assert_round_trips(source: &str) {
+        let tokens = parse(source);
+        let markup = to_markup(&tokens);
+        assert_eq!(parse(&markup), tokens, "{source:?} became {markup:?}");
+    }
+
+    /// Read the source of each snapshot from its description
+    fn snapshot_sources() -> Vec<String> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut sources = Vec::new();
+
+        for directory in [
+            root.join("snapshots"),
+            root.join("parser").join("snapshots"),
+        ] {
+            for entry in fs::read_dir(directory).unwrap() {
+                let snapshot = fs::read_to_string(entry.unwrap().path()).unwrap();
+                let Some(description) = snapshot
+                    .lines()
+                    .find_map(|line| line.strip_prefix("description: "))
+                else {
+                    continue;
+                };
+
+                sources.push(unquote(description));
+            }
+        }
+
+        sources.sort();
+        sources.dedup();
+        sources
+    }
+
+    /// Decode a YAML scalar, which is only quoted when it contains special characters
+    fn unquote(value: &str) -> String {
+        let Some(quoted) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) else {
+            return value.to_owned();
+        };
+
+        let mut result = String::new();
+        let mut chars = quoted.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '\\' {
+                result.push(ch);
+                continue;
+            }
+
+            match chars.next().unwrap() {
+                'n' => result.push('\n'),
+                't' => result.push('\t'),
+                'r' => result.push('\r'),
+                'e' => result.push('\x1b'),
+                escaped => result.push(escaped),
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(to_markup(&parse("plain text")), "plain text");
+    }
+
+    #[test]
+    fn styled() {
+        assert_eq!(
+            to_markup(&parse("a [deco:bold;fg:red](b [reset;bg:blue](c)) d")),
+            "a [fg:red;deco:bold](b [reset;bg:blue](c)) d"
+        );
+    }
+
+    #[test]
+    fn control_characters_are_escaped() {
+        assert_eq!(
+            to_markup(&parse("\\(\\[\\\\\\]\\) [fg:red](\\()")),
+            "\\(\\[\\\\\\]\\) [fg:red](\\()"
+        );
+    }
+
+    #[test]
+    fn sticky_styles_become_explicit() {
+        assert_eq!(
+            to_markup(&parse("a [fg:red]b [deco:bold]c")),
+            "a [fg:red](b [deco:bold](c))"
+        );
+    }
+
+    #[test]
+    fn escape_character_in_content() {
+        assert_eq!(to_markup(&parse("\\x1b\\[1m")), "\\x1b\\[1m");
+        assert_round_trips("\\x1b\\[1m");
+    }
+
+    #[test]
+    fn raw_sequences_are_kept() {
+        assert_eq!(
+            to_markup(&parse("\x1b[1mbold\x1b[22m")),
+            "\x1b[1mbold\x1b[22m"
+        );
+    }
+
+    #[test]
+    fn empty_style_writes_content() {
+        let tokens = vec![Token::Styled {
+            content: vec![Token::Content("text".into(), TextRange::default())],
+            style: style!(),
+            span: TextRange::default(),
+        }];
+        assert_eq!(to_markup(&tokens), "text");
+    }
+
+    #[test]
+    fn parsed_tokens() {
+        let tokens = Tokens::parse("a [fg:red](b)").unwrap();
+        assert_eq!(to_markup(&tokens), "a [fg:red](b)");
+    }
+
+    #[test]
+    fn round_trips() {
+        for source in [
+            "",
+            "[fg:red]()",
+            "[fg:Bright-Red;deco:faint,reverse,strikethrough](aliases)",
+            "[deco:bold,dim;deco:italic](repeated)",
+            "[fg:red](a [bg:blue]b [deco:bold](c) d) e",
+            "before \x1b[31m [fg:red](\x1b[1mbold\x1b[22m) after",
+            "comments[#: are dropped] \\\n  and \\ escaped whitespace",
+            "\\x41 caf\\u{e9} \\u{1F600}",
+            "[fg:default;bg:default](defaults)",
+        ] {
+            assert_round_trips(source);
+        }
+    }
+
+    #[test]
+    fn snapshot_corpus_round_trips() {
+        let sources = snapshot_sources();
+        let valid = sources
+            .iter()
+            .filter(|source| Parser::new(source).parse().1.is_empty())
+            .collect::<Vec<_>>();
+        assert!(valid.len() > 20, "only found {} sources", valid.len());
+
+        for source in valid {
+            assert_round_trips(source);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    ast::{Color, Decoration, Tokens},
+    error::Error,
+    parser::{Event, Events},
+};
+use indexmap::IndexSet;
+
+/// The colors and decorations referenced by a piece of styled markup
+///
+/// Each set is in the order the values first appear in the source.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct StyleUsage {
+    /// The foreground, background, and underline colors
+    pub colors: IndexSet<Color>,
+    /// The text decorations
+    pub decorations: IndexSet<Decoration>,
+}
+
+/// A summary of the styling in a piece of markup
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Analysis {
+    /// The number of pieces of styled markup
+    pub styled_count: usize,
+    /// How deeply the styled markup is nested, which is 0 when there is no markup
+    pub max_depth: usize,
+    /// The foreground, background, and underline colors, in the order they first appear
+    pub colors: IndexSet<Color>,
+    /// The text decorations, in the order they first appear
+    pub decorations: IndexSet<Decoration>,
+}
+
+/// Find all the colors and decorations used by styled markup
+///
+/// Every style specifier is included, even if it has no visible effect (i.e. the markup has no
+/// content, or the style is already applied by the enclosing markup).
+pub fn used_colors(source: &str) -> Result<StyleUsage, Vec<Error>> {
+    let analysis = analyze(source)?;
+    Ok(StyleUsage {
+        colors: analysis.colors,
+        decorations: analysis.decorations,
+    })
+}
+
+/// Summarize the styled markup in a piece of text
+///
+/// The colors and decorations are collected the same way as [`used_colors`]. The depth is counted
+/// the same way as the parser's maximum depth, so sticky styles in the same content aren't nested
+/// within each other.
+pub fn analyze(source: &str) -> Result<Analysis, Vec<Error>> {
+    let mut analysis = Analysis::default();
+
+    let mut events = Events::new(source);
+    loop {
+        // the unstyled text at the top level isn't nested within any markup
+        let depth = events.depth() + 1;
+        match events.next() {
+            Some(Ok(Event::StyleOpen(style))) => {
+                let colors = [style.foreground, style.background, style.underline_color];
+                analysis.colors.extend(colors.into_iter().flatten());
+                analysis
+                    .decorations
+                    .extend(style.decoration.iter().flatten());
+                analysis.styled_count += 1;
+                analysis.max_depth = analysis.max_depth.max(depth);
+            }
+            Some(Ok(_)) => {}
+            Some(Err(error)) => {
+                // the events stop at the first error, while the parser reports all of them
+                return Err(Tokens::parse(source).err().unwrap_or_else(|| vec![error]));
+            }
+            None => return Ok(analysis),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze, used_colors, Analysis, StyleUsage};
+    use crate::ast::{Color, Decoration};
+
+    #[test]
+    fn unstyled() {
+        assert_eq!(used_colors("plain text").unwrap(), StyleUsage::default());
+    }
+
+    #[test]
+    fn kitchen_sink() {
+        let usage =
+            used_colors("leading [fg:red](one [bg:blue](two [deco:dim](three) two) one) trailing")
+                .unwrap();
+        assert_eq!(usage.colors, set![Color::Red, Color::Blue]);
+        assert_eq!(usage.decorations, set![Decoration::Dim]);
+    }
+
+    #[test]
+    fn every_kind_of_color() {
+        let usage = used_colors("[fg:green;bg:black;ul:bright-cyan](text)").unwrap();
+        assert_eq!(
+            usage.colors,
+            set![Color::Green, Color::Black, Color::BrightCyan]
+        );
+    }
+
+    #[test]
+    fn duplicates_are_listed_once() {
+        let usage =
+            used_colors("[fg:red;bg:red;deco:bold](a) [deco:bold,italic;fg:red](b)").unwrap();
+        assert_eq!(usage.colors, set![Color::Red]);
+        assert_eq!(
+            usage.decorations,
+            set![Decoration::Bold, Decoration::Italic]
+        );
+    }
+
+    #[test]
+    fn styles_without_content_are_included() {
+        let usage = used_colors("[fg:default;deco:slow-blink]()").unwrap();
+        assert_eq!(usage.colors, set![Color::Default]);
+        assert_eq!(usage.decorations, set![Decoration::SlowBlink]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(used_colors("[fg:red](unclosed").is_err());
+        assert!(analyze("[fg:red](unclosed").is_err());
+    }
+
+    #[test]
+    fn analyze_unstyled() {
+        assert_eq!(analyze("plain text").unwrap(), Analysis::default());
+    }
+
+    #[test]
+    fn analyze_kitchen_sink() {
+        let analysis =
+            analyze("leading [fg:red](one [bg:blue](two [deco:dim](three) two) one) trailing")
+                .unwrap();
+        assert_eq!(
+            analysis,
+            Analysis {
+                styled_count: 3,
+                max_depth: 3,
+                colors: set![Color::Red, Color::Blue],
+                decorations: set![Decoration::Dim],
+            }
+        );
+    }
+
+    #[test]
+    fn analyze_siblings_are_not_nested() {
+        let analysis = analyze("[fg:red](a) [fg:blue](b [deco:bold](c)) [bg:red](d)").unwrap();
+        assert_eq!(analysis.styled_count, 4);
+        assert_eq!(analysis.max_depth, 2);
+    }
+
+    #[test]
+    fn analyze_counts_sticky_and_empty_markup() {
+        let analysis = analyze("[fg:red](a [deco:bold] b) [bg:green]()").unwrap();
+        assert_eq!(analysis.styled_count, 3);
+        assert_eq!(analysis.max_depth, 2);
+    }
+
+    #[test]
+    fn analyze_sticky_styles_in_the_same_content_are_not_nested() {
+        let analysis = analyze(&"[fg:red]a [fg:blue]b ".repeat(200)).unwrap();
+        assert_eq!(analysis.styled_count, 400);
+        assert_eq!(analysis.max_depth, 1);
+    }
+}